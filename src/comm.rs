@@ -1,12 +1,21 @@
 use std::fmt::{Display, Formatter};
 
+use druid::{Data, Selector, SingleUse};
 use hanteker_lib::device::cfg::{
-    AwgType, Coupling, DeviceFunction, Probe, RunningStatus, Scale, TimeScale, TriggerMode,
+    AwgType, Coupling, DeviceFunction, HantekConfig, Probe, RunningStatus, Scale, TimeScale,
+    TriggerMode,
 };
 
+pub(crate) type RequestId = u64;
+
+/// Posted by the device thread once a [`DevRequest`] has been executed.
+pub(crate) const DEV_RESPONSE: Selector<SingleUse<DevResponse>> =
+    Selector::new("hanteker.dev-response");
+
 #[derive(Debug)]
 pub(crate) enum DevCommand {
     Capture(Vec<usize>, usize),
+    ReadSettings,
 
     Connect,
     Disconnect,
@@ -38,9 +47,108 @@ pub(crate) enum DevCommand {
     AwgDutyTrap(f32, f32, f32),
 }
 
+impl DevCommand {
+    pub(crate) fn control(&self) -> Control {
+        match self {
+            DevCommand::Capture(_, _) => Control::Capture,
+            DevCommand::ReadSettings => Control::ReadSettings,
+            DevCommand::Connect => Control::Connect,
+            DevCommand::Disconnect => Control::Disconnect,
+            DevCommand::DeviceFunction(_) => Control::DeviceFunction,
+            DevCommand::ScopeRunning(_) => Control::Running,
+            DevCommand::ChannelEnable(channel, _) => Control::ChannelEnable(*channel),
+            DevCommand::Coupling(channel, _) => Control::Coupling(*channel),
+            DevCommand::Probe(channel, _) => Control::Probe(*channel),
+            DevCommand::Scale(channel, _) => Control::Scale(*channel),
+            DevCommand::Offset(channel, _) => Control::Offset(*channel),
+            DevCommand::BwLimit(channel, _) => Control::BwLimit(*channel),
+            DevCommand::TimeScale(_) => Control::TimeScale,
+            DevCommand::TimeOffset(_) => Control::TimeOffset,
+            DevCommand::TriggerSource(_) => Control::TriggerSource,
+            DevCommand::TriggerMode(_) => Control::TriggerMode,
+            DevCommand::TriggerLevel(_) => Control::TriggerLevel,
+            DevCommand::AwgRunningStatus(_) => Control::AwgRunning,
+            DevCommand::AwgFrequency(_) => Control::AwgFrequency,
+            DevCommand::AwgAmplitude(_) => Control::AwgAmplitude,
+            DevCommand::AwgType(_) => Control::AwgType,
+            DevCommand::AwgOffset(_) => Control::AwgOffset,
+            DevCommand::AwgDutySquare(_) => Control::AwgDutySquare,
+            DevCommand::AwgDutyRamp(_) => Control::AwgDutyRamp,
+            DevCommand::AwgDutyTrap(_, _, _) => Control::AwgDutyTrap,
+        }
+    }
+}
+
 pub(crate) enum DevCommandResult {
     EmptyResult,
     CaptureResult(Vec<u8>),
+    /// Settings as the device reports them, `None` where it can't.
+    Settings(Box<HantekConfig>),
+    /// The device can't report its settings at all.
+    SettingsUnsupported,
+}
+
+pub(crate) struct DevRequest {
+    pub(crate) id: RequestId,
+    pub(crate) cmd: DevCommand,
+}
+
+pub(crate) struct DevResponse {
+    pub(crate) id: RequestId,
+    pub(crate) result: Result<DevCommandResult, String>,
+}
+
+/// The UI control a [`DevCommand`] originates from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Data)]
+pub(crate) enum Control {
+    Capture,
+    ReadSettings,
+
+    Connect,
+    Disconnect,
+
+    DeviceFunction,
+
+    Running,
+
+    ChannelEnable(usize),
+    Coupling(usize),
+    Probe(usize),
+    Scale(usize),
+    Offset(usize),
+    BwLimit(usize),
+
+    TimeScale,
+    TimeOffset,
+    TriggerSource,
+    TriggerMode,
+    TriggerLevel,
+
+    AwgRunning,
+    AwgFrequency,
+    AwgAmplitude,
+    AwgType,
+    AwgOffset,
+    AwgDutySquare,
+    AwgDutyRamp,
+    AwgDutyTrap,
+}
+
+impl Control {
+    /// Whether only the latest value matters, so a newer request can wait
+    /// for an in-flight one instead of queueing behind it.
+    pub(crate) fn is_coalesced(&self) -> bool {
+        !matches!(
+            self,
+            Control::Capture | Control::ReadSettings | Control::Connect | Control::Disconnect
+        )
+    }
+}
+
+#[derive(Debug, Clone, Data)]
+pub(crate) struct PendingRequest {
+    pub(crate) control: Control,
+    pub(crate) done: String,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
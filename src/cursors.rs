@@ -0,0 +1,27 @@
+/// Measurement cursors on the waveform display: a pair of vertical lines at
+/// two times and a pair of horizontal lines at two voltages of one channel.
+///
+/// Positions are in seconds and volts so the cursors stay on the same part of
+/// the waveform when the view changes. A pair without positions yet is placed
+/// on screen by the display.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Cursors {
+    pub(crate) time_enabled: bool,
+    pub(crate) times: Option<[f64; 2]>,
+    pub(crate) volts_enabled: bool,
+    pub(crate) volts: Option<[f64; 2]>,
+    /// Channel whose scale the voltage cursors are measured in.
+    pub(crate) volts_channel: usize,
+}
+
+impl Default for Cursors {
+    fn default() -> Self {
+        Self {
+            time_enabled: false,
+            times: None,
+            volts_enabled: false,
+            volts: None,
+            volts_channel: 1,
+        }
+    }
+}
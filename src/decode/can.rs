@@ -0,0 +1,643 @@
+use crate::decode::{Annotation, DecodeError, Decoded, Logic, Row, MIN_SAMPLES_PER_BIT};
+use crate::waveform::Waveform;
+
+/// Recessive bits before a start of frame: EOF, intermission and the ACK
+/// delimiter before them. Anything shorter is taken for the inside of a
+/// frame the capture started in.
+const IDLE_BITS: f64 = 10.0;
+/// Identical bits after which the sender inserts a stuff bit.
+const STUFF_RUN: usize = 5;
+const CRC_POLYNOMIAL: u16 = 0x4599;
+const EOF_BITS: usize = 7;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CanConfig {
+    /// CAN_H against ground, or the math trace set up as CAN_H - CAN_L.
+    pub(crate) channel: usize,
+    pub(crate) bitrate: usize,
+    /// Where in a bit its level is read, as a fraction of the bit time.
+    pub(crate) sample_point: f64,
+    /// Logic threshold in volts, dominant bits are above it.
+    pub(crate) threshold: f64,
+}
+
+impl Default for CanConfig {
+    fn default() -> Self {
+        Self {
+            channel: 1,
+            bitrate: 500_000,
+            sample_point: 0.875,
+            threshold: 3.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum FrameError {
+    /// The capture ends inside the frame.
+    Truncated,
+    Stuff,
+    Form(&'static str),
+}
+
+/// Reads bits of a frame at the sample point, resynchronizing on edges and
+/// removing stuff bits while `stuffing` is set.
+struct BitReader<'a> {
+    logic: &'a Logic,
+    samples_per_bit: f64,
+    sample_point: f64,
+    /// Sample index where the next bit starts.
+    bit_start: f64,
+    last: bool,
+    run: usize,
+    stuffing: bool,
+    crc: u16,
+}
+
+impl BitReader<'_> {
+    fn raw_bit(&mut self) -> Result<bool, FrameError> {
+        // Rounded down, bit_start is the first sample after an edge and
+        // so up to a sample late already.
+        let level = self
+            .logic
+            .at((self.bit_start + self.sample_point * self.samples_per_bit).floor())
+            .ok_or(FrameError::Truncated)?;
+
+        // Follow the sender's clock: an edge within half a bit of the
+        // expected end of the bit marks where the next one really starts.
+        let expected = self.bit_start + self.samples_per_bit;
+        let window = self.samples_per_bit / 2.0;
+        let from = (expected - window).max(1.0).ceil() as usize;
+        let to = ((expected + window).ceil() as usize).min(self.logic.len());
+        self.bit_start = (from..to)
+            .find(|i| self.logic.levels[*i] != self.logic.levels[*i - 1])
+            .map(|i| i as f64)
+            .unwrap_or(expected);
+        Ok(level)
+    }
+
+    fn bit(&mut self) -> Result<bool, FrameError> {
+        let mut bit = self.raw_bit()?;
+        if self.stuffing && self.run == STUFF_RUN {
+            if bit == self.last {
+                return Err(FrameError::Stuff);
+            }
+            self.last = bit;
+            self.run = 1;
+            bit = self.raw_bit()?;
+        }
+        match bit == self.last {
+            true => self.run += 1,
+            false => {
+                self.last = bit;
+                self.run = 1;
+            }
+        }
+        if self.stuffing {
+            let feedback = bit != (self.crc >> 14 & 1 == 1);
+            self.crc = (self.crc << 1) & 0x7fff;
+            if feedback {
+                self.crc ^= CRC_POLYNOMIAL;
+            }
+        }
+        Ok(bit)
+    }
+
+    /// Ends the stuffed part of the frame, which still gets a stuff bit
+    /// after a run at its very end.
+    fn end_stuffing(&mut self) -> Result<(), FrameError> {
+        if self.run == STUFF_RUN && self.raw_bit()? == self.last {
+            return Err(FrameError::Stuff);
+        }
+        self.stuffing = false;
+        Ok(())
+    }
+
+    /// `bits` bits, most significant first.
+    fn field(&mut self, bits: usize) -> Result<u32, FrameError> {
+        let mut value = 0;
+        for _ in 0..bits {
+            value = (value << 1) | self.bit()? as u32;
+        }
+        Ok(value)
+    }
+}
+
+/// Builds the annotations of one frame as its fields are read.
+struct Frame<'a> {
+    reader: BitReader<'a>,
+    channel: usize,
+    annotations: Vec<Annotation>,
+    field_start: f64,
+}
+
+impl Frame<'_> {
+    fn annotate(&mut self, text: String, error: bool) {
+        let logic = self.reader.logic;
+        self.annotations.push(Annotation {
+            channel: self.channel,
+            start: logic.time(self.field_start),
+            end: logic.time(self.reader.bit_start),
+            text,
+            error,
+        });
+        self.field_start = self.reader.bit_start;
+    }
+
+    /// Skips a field that is annotated along with the next one.
+    fn skip(&mut self, bits: usize) -> Result<u32, FrameError> {
+        self.reader.field(bits)
+    }
+
+    fn field(&mut self, bits: usize, text: impl Fn(u32) -> String) -> Result<u32, FrameError> {
+        let value = self.reader.field(bits)?;
+        self.annotate(text(value), false);
+        Ok(value)
+    }
+
+    /// Checks a fixed form bit, which must be recessive.
+    fn delimiter(&mut self, bits: usize, name: &'static str) -> Result<(), FrameError> {
+        for _ in 0..bits {
+            if !self.reader.bit()? {
+                self.annotate(name.to_string(), true);
+                return Err(FrameError::Form(name));
+            }
+        }
+        self.annotate(name.to_string(), false);
+        Ok(())
+    }
+}
+
+/// What a frame carried, for the table.
+struct Message {
+    id: u32,
+    extended: bool,
+    remote: bool,
+    dlc: u32,
+    data: Vec<u8>,
+}
+
+impl Message {
+    fn text(&self) -> String {
+        let mut text = match self.extended {
+            true => format!("0x{:08X}", self.id),
+            false => format!("0x{:03X}", self.id),
+        };
+        if self.remote {
+            text.push_str(" RTR");
+        }
+        text.push_str(&format!(" [{}]", self.dlc));
+        for byte in &self.data {
+            text.push_str(&format!(" {:02X}", byte));
+        }
+        text
+    }
+}
+
+impl CanConfig {
+    pub(crate) fn decode(&self, waveform: &Waveform) -> Result<Decoded, DecodeError> {
+        if !(0.5..=0.95).contains(&self.sample_point) {
+            return Err(DecodeError::Settings(
+                "sample point must be 50 to 95 %".to_string(),
+            ));
+        }
+        if self.bitrate == 0 {
+            return Err(DecodeError::Settings(
+                "bitrate must be positive".to_string(),
+            ));
+        }
+        // Levels are recessive high, i.e. a logic one on the bus.
+        let logic = Logic::new(waveform, self.channel, self.threshold, true)?;
+        let samples_per_bit = 1.0 / (self.bitrate as f64 * logic.sample_interval);
+        if samples_per_bit < MIN_SAMPLES_PER_BIT as f64 {
+            return Err(DecodeError::TooFewSamples(
+                format!("{:.1} samples", samples_per_bit),
+                MIN_SAMPLES_PER_BIT,
+            ));
+        }
+
+        let mut decoded = Decoded::default();
+        let mut position = 0;
+        while let Some(sof) = find_start_of_frame(&logic, position, samples_per_bit) {
+            let mut frame = Frame {
+                reader: BitReader {
+                    logic: &logic,
+                    samples_per_bit,
+                    sample_point: self.sample_point,
+                    bit_start: sof as f64,
+                    last: true,
+                    run: 0,
+                    stuffing: true,
+                    crc: 0,
+                },
+                channel: self.channel,
+                annotations: Vec::new(),
+                field_start: sof as f64,
+            };
+            let result = decode_frame(&mut frame);
+            let end = frame.reader.bit_start;
+            let (message, error) = match result {
+                Ok((message, error)) => (Some(message), error),
+                // Left for a later capture to show whole.
+                Err(FrameError::Truncated) => break,
+                Err(FrameError::Stuff) => {
+                    frame.annotate("stuff error".to_string(), true);
+                    (None, Some("stuff error".to_string()))
+                }
+                Err(FrameError::Form(field)) => (None, Some(format!("form error in {}", field))),
+            };
+            decoded.annotations.append(&mut frame.annotations);
+            decoded.rows.push(Row {
+                time: logic.time(sof as f64),
+                text: match message {
+                    Some(message) => message.text(),
+                    None => "frame".to_string(),
+                },
+                error,
+            });
+            position = (end.ceil() as usize).max(sof + 1);
+        }
+
+        Ok(decoded)
+    }
+}
+
+/// First dominant edge from `from` on that follows an idle bus. A frame
+/// whose idle time the capture cut short is skipped, since it may as well
+/// be the middle of a frame that started earlier.
+fn find_start_of_frame(logic: &Logic, from: usize, samples_per_bit: f64) -> Option<usize> {
+    let mut from = from;
+    loop {
+        let recessive = logic.find(from, true)?;
+        let dominant = logic.find(recessive, false)?;
+        let mut idle = recessive;
+        while idle > 0 && logic.levels[idle - 1] {
+            idle -= 1;
+        }
+        if (dominant - idle) as f64 >= (IDLE_BITS - 0.5) * samples_per_bit {
+            return Some(dominant);
+        }
+        from = dominant;
+    }
+}
+
+/// Reads one frame from its start of frame bit. The message comes with the
+/// error of a frame that was read to the end but failed its checks.
+fn decode_frame(frame: &mut Frame) -> Result<(Message, Option<String>), FrameError> {
+    frame.field(1, |_| "SOF".to_string())?;
+    let id_a = frame.skip(11)?;
+    let rtr_or_srr = frame.skip(1)? == 1;
+    let extended = frame.skip(1)? == 1;
+
+    // The identifier box also covers the bits up to IDE, which tells how
+    // long the identifier is.
+    let (id, remote) = match extended {
+        false => {
+            frame.annotate(format!("ID 0x{:03X}", id_a), false);
+            (id_a, rtr_or_srr)
+        }
+        true => {
+            let id_b = frame.skip(18)?;
+            let id = (id_a << 18) | id_b;
+            frame.annotate(format!("ID 0x{:08X}", id), false);
+            let remote = frame.skip(1)? == 1;
+            // Reserved bit r1.
+            frame.skip(1)?;
+            (id, remote)
+        }
+    };
+    // Reserved bit r0.
+    frame.skip(1)?;
+    let dlc = frame.field(4, |dlc| match remote {
+        true => format!("RTR DLC {}", dlc),
+        false => format!("DLC {}", dlc),
+    })?;
+
+    let mut data = Vec::new();
+    if !remote {
+        for _ in 0..dlc.min(8) {
+            data.push(frame.field(8, |byte| format!("0x{:02X}", byte))? as u8);
+        }
+    }
+
+    let expected_crc = frame.reader.crc;
+    let crc = frame.skip(15)? as u16;
+    let crc_ok = crc == expected_crc;
+    frame.annotate(format!("CRC 0x{:04X}", crc), !crc_ok);
+    frame.reader.end_stuffing()?;
+    frame.delimiter(1, "CRC delimiter")?;
+
+    let acked = !frame.reader.bit()?;
+    frame.annotate(
+        match acked {
+            true => "ACK",
+            false => "NACK",
+        }
+        .to_string(),
+        !acked,
+    );
+    frame.delimiter(1, "ACK delimiter")?;
+    frame.delimiter(EOF_BITS, "EOF")?;
+
+    let mut errors = Vec::new();
+    if !crc_ok {
+        errors.push(format!("CRC error, expected 0x{:04X}", expected_crc));
+    }
+    if !acked {
+        errors.push("not acknowledged".to_string());
+    }
+    let error = match errors.is_empty() {
+        true => None,
+        false => Some(errors.join(", ")),
+    };
+    Ok((
+        Message {
+            id,
+            extended,
+            remote,
+            dlc,
+            data,
+        },
+        error,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::logic_waveform;
+
+    const SAMPLES_PER_BIT: usize = 10;
+
+    fn bits_of(value: u32, count: usize) -> Vec<bool> {
+        (0..count).rev().map(|bit| value >> bit & 1 == 1).collect()
+    }
+
+    fn crc15(bits: &[bool]) -> u16 {
+        let mut crc = 0u16;
+        for bit in bits {
+            let feedback = *bit != (crc >> 14 & 1 == 1);
+            crc = (crc << 1) & 0x7fff;
+            if feedback {
+                crc ^= 0x4599;
+            }
+        }
+        crc
+    }
+
+    /// `bits` with stuff bits inserted, and where they went.
+    fn stuff(bits: &[bool]) -> (Vec<bool>, Vec<usize>) {
+        let mut stuffed = Vec::new();
+        let mut positions = Vec::new();
+        let mut last = true;
+        let mut run = 0;
+        for bit in bits {
+            if run == STUFF_RUN {
+                positions.push(stuffed.len());
+                stuffed.push(!last);
+                last = !last;
+                run = 1;
+            }
+            match *bit == last {
+                true => run += 1,
+                false => {
+                    last = *bit;
+                    run = 1;
+                }
+            }
+            stuffed.push(*bit);
+        }
+        if run == STUFF_RUN {
+            positions.push(stuffed.len());
+            stuffed.push(!last);
+        }
+        (stuffed, positions)
+    }
+
+    /// The stuffed part of a data frame, SOF to CRC, with `crc_error`
+    /// flipped into its CRC.
+    fn stuffed_frame(
+        id: u32,
+        extended: bool,
+        data: &[u8],
+        crc_error: u16,
+    ) -> (Vec<bool>, Vec<usize>) {
+        let mut bits = vec![false];
+        match extended {
+            false => {
+                bits.extend(bits_of(id, 11));
+                bits.extend([false, false, false]);
+            }
+            true => {
+                bits.extend(bits_of(id >> 18, 11));
+                bits.extend([true, true]);
+                bits.extend(bits_of(id & 0x3ffff, 18));
+                bits.extend([false, false, false]);
+            }
+        }
+        bits.extend(bits_of(data.len() as u32, 4));
+        for byte in data {
+            bits.extend(bits_of(*byte as u32, 8));
+        }
+        let crc = crc15(&bits) ^ crc_error;
+        bits.extend(bits_of(crc as u32, 15));
+        stuff(&bits)
+    }
+
+    /// CRC delimiter to EOF.
+    fn tail(acked: bool) -> Vec<bool> {
+        let mut bits = vec![true, !acked, true];
+        bits.extend([true; EOF_BITS]);
+        bits
+    }
+
+    fn frame(id: u32, extended: bool, data: &[u8]) -> Vec<bool> {
+        let (mut bits, _) = stuffed_frame(id, extended, data, 0);
+        bits.extend(tail(true));
+        bits
+    }
+
+    /// CAN_H of a bus carrying `frames`, with idle time around each.
+    fn bus(frames: &[Vec<bool>]) -> Vec<bool> {
+        let mut levels = Vec::new();
+        let idle = [false; 12 * SAMPLES_PER_BIT];
+        levels.extend(idle);
+        for frame in frames {
+            for bit in frame {
+                levels.extend([!bit; SAMPLES_PER_BIT]);
+            }
+            levels.extend(idle);
+        }
+        levels
+    }
+
+    fn decode(frames: &[Vec<bool>]) -> Decoded {
+        let config = CanConfig::default();
+        let sample_interval = 1.0 / (config.bitrate * SAMPLES_PER_BIT) as f64;
+        config
+            .decode(&logic_waveform(&[bus(frames)], sample_interval))
+            .unwrap()
+    }
+
+    fn rows(decoded: &Decoded) -> Vec<(&str, Option<&str>)> {
+        decoded
+            .rows
+            .iter()
+            .map(|row| (row.text.as_str(), row.error.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn crc_and_destuffing() {
+        // CRC-15/CAN check value of "123456789".
+        let message: Vec<bool> = b"123456789"
+            .iter()
+            .flat_map(|byte| bits_of(*byte as u32, 8))
+            .collect();
+        assert_eq!(crc15(&message), 0x059E);
+
+        let (stuffed, _) = stuff(&message);
+        let levels: Vec<bool> = stuffed
+            .iter()
+            .flat_map(|bit| [!bit; SAMPLES_PER_BIT])
+            .collect();
+        let waveform = logic_waveform(&[levels], 1.0);
+        let logic = Logic::new(&waveform, 1, 3.0, true).unwrap();
+        let mut reader = BitReader {
+            logic: &logic,
+            samples_per_bit: SAMPLES_PER_BIT as f64,
+            sample_point: 0.875,
+            bit_start: 0.0,
+            last: true,
+            run: 0,
+            stuffing: true,
+            crc: 0,
+        };
+        for byte in b"123456789" {
+            assert_eq!(reader.field(8).unwrap(), *byte as u32);
+        }
+        assert_eq!(reader.crc, 0x059E);
+    }
+
+    #[test]
+    fn standard_frame() {
+        let decoded = decode(&[frame(0x123, false, &[0x11, 0x22, 0xFF, 0x00])]);
+        assert_eq!(rows(&decoded), [("0x123 [4] 11 22 FF 00", None)]);
+        let texts: Vec<&str> = decoded
+            .annotations
+            .iter()
+            .map(|a| a.text.as_str())
+            .collect();
+        assert_eq!(texts[..3], ["SOF", "ID 0x123", "DLC 4"]);
+        assert!(texts.contains(&"ACK"));
+    }
+
+    #[test]
+    fn extended_frame() {
+        let decoded = decode(&[frame(0x1ABCDE12, true, &[0xAA]), frame(0x7FF, false, &[])]);
+        assert_eq!(
+            rows(&decoded),
+            [("0x1ABCDE12 [1] AA", None), ("0x7FF [0]", None)]
+        );
+    }
+
+    #[test]
+    fn stuff_bit_after_crc() {
+        // A data byte whose frame ends its CRC on a run of five.
+        let (byte, (mut bits, positions)) = (0..=255u8)
+            .map(|byte| (byte, stuffed_frame(0x123, false, &[byte], 0)))
+            .find(|(_, (bits, positions))| positions.last() == Some(&(bits.len() - 1)))
+            .unwrap();
+        let mut good = bits.clone();
+        good.extend(tail(true));
+        let decoded = decode(&[good]);
+        assert_eq!(
+            rows(&decoded),
+            [(format!("0x123 [1] {:02X}", byte).as_str(), None)]
+        );
+
+        // The same level again instead makes a sixth equal bit.
+        let last = positions.len() - 1;
+        bits[positions[last]] = !bits[positions[last]];
+        bits.extend(tail(true));
+        assert_eq!(rows(&decode(&[bits])), [("frame", Some("stuff error"))]);
+    }
+
+    #[test]
+    fn crc_error() {
+        let (mut bits, _) = stuffed_frame(0x123, false, &[0x11, 0x22, 0xFF, 0x00], 0x0001);
+        bits.extend(tail(true));
+        let decoded = decode(&[bits]);
+        assert_eq!(decoded.rows.len(), 1);
+        assert_eq!(decoded.rows[0].text, "0x123 [4] 11 22 FF 00");
+        let expected = crc15(&{
+            let mut bits = vec![false];
+            bits.extend(bits_of(0x123, 11));
+            bits.extend([false, false, false]);
+            bits.extend(bits_of(4, 4));
+            for byte in [0x11, 0x22, 0xFF, 0x00] {
+                bits.extend(bits_of(byte, 8));
+            }
+            bits
+        });
+        assert_eq!(
+            decoded.rows[0].error,
+            Some(format!("CRC error, expected 0x{:04X}", expected))
+        );
+    }
+
+    #[test]
+    fn stuff_error() {
+        let (mut bits, positions) = stuffed_frame(0x000, false, &[0x00], 0);
+        bits[positions[0]] = !bits[positions[0]];
+        bits.extend(tail(true));
+        let decoded = decode(&[bits]);
+        assert_eq!(rows(&decoded), [("frame", Some("stuff error"))]);
+        assert!(decoded.annotations.last().unwrap().error);
+    }
+
+    #[test]
+    fn missing_ack() {
+        let (mut bits, _) = stuffed_frame(0x123, false, &[0x42], 0);
+        bits.extend(tail(false));
+        let decoded = decode(&[bits]);
+        assert_eq!(rows(&decoded), [("0x123 [1] 42", Some("not acknowledged"))]);
+        let nack = decoded
+            .annotations
+            .iter()
+            .find(|annotation| annotation.text == "NACK")
+            .unwrap();
+        assert!(nack.error);
+    }
+
+    #[test]
+    fn form_error() {
+        let (mut bits, _) = stuffed_frame(0x123, false, &[0x42], 0);
+        let mut tail = tail(true);
+        // Dominant CRC delimiter.
+        tail[0] = false;
+        bits.extend(tail);
+        assert_eq!(
+            rows(&decode(&[bits])),
+            [("frame", Some("form error in CRC delimiter"))]
+        );
+    }
+
+    #[test]
+    fn capture_starting_inside_a_frame() {
+        let cut = frame(0x0F0, false, &[0x55, 0xAA]);
+        let config = CanConfig::default();
+        let sample_interval = 1.0 / (config.bitrate * SAMPLES_PER_BIT) as f64;
+        for start in 1..cut.len() {
+            let mut levels: Vec<bool> = cut[start..]
+                .iter()
+                .flat_map(|bit| [!bit; SAMPLES_PER_BIT])
+                .collect();
+            levels.extend(bus(&[frame(0x123, false, &[0x01])]));
+            let decoded = config
+                .decode(&logic_waveform(&[levels], sample_interval))
+                .unwrap();
+            assert_eq!(rows(&decoded), [("0x123 [1] 01", None)], "start={}", start);
+        }
+    }
+}
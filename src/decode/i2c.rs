@@ -0,0 +1,380 @@
+use crate::decode::{Annotation, DecodeError, Decoded, Logic, Row};
+use crate::waveform::Waveform;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct I2cConfig {
+    /// Channel probing SCL, SDA is on the other one.
+    pub(crate) scl_channel: usize,
+    /// Logic threshold in volts.
+    pub(crate) threshold: f64,
+}
+
+impl Default for I2cConfig {
+    fn default() -> Self {
+        Self {
+            scl_channel: 1,
+            threshold: 1.5,
+        }
+    }
+}
+
+/// Transaction being decoded, from a (repeated) start to the next one or a
+/// stop.
+struct Transaction {
+    time: f64,
+    repeated: bool,
+    /// Address byte and the data bytes that followed, with their acks.
+    bytes: Vec<(u8, bool)>,
+    incomplete: bool,
+}
+
+impl Transaction {
+    fn row(&self) -> Row {
+        let mut text = String::from(match self.repeated {
+            true => "Sr",
+            false => "S",
+        });
+        let mut error = None;
+        if let Some((address, ack)) = self.bytes.first() {
+            text.push_str(&format!(" {}", format_address(*address)));
+            if !ack {
+                error = Some("address not acknowledged".to_string());
+            }
+        }
+        let read = matches!(self.bytes.first(), Some((address, _)) if address & 1 == 1);
+        let data = self.bytes.iter().skip(1);
+        for (index, (value, ack)) in data.enumerate() {
+            text.push_str(&format!(" {:02X}", value));
+            // The master ends a read with a NACK, anywhere else it means the
+            // slave refused a byte.
+            if !ack && !read && error.is_none() {
+                error = Some(format!("data byte {} not acknowledged", index + 1));
+            }
+        }
+        if self.incomplete && error.is_none() {
+            error = Some("incomplete byte".to_string());
+        }
+        Row {
+            time: self.time,
+            text,
+            error,
+        }
+    }
+}
+
+fn format_address(byte: u8) -> String {
+    let direction = match byte & 1 {
+        0 => "W",
+        _ => "R",
+    };
+    format!("0x{:02X} {}", byte >> 1, direction)
+}
+
+impl I2cConfig {
+    pub(crate) fn sda_channel(&self) -> usize {
+        match self.scl_channel {
+            1 => 2,
+            _ => 1,
+        }
+    }
+
+    pub(crate) fn decode(&self, waveform: &Waveform) -> Result<Decoded, DecodeError> {
+        let sda_channel = self.sda_channel();
+        let scl = Logic::new(waveform, self.scl_channel, self.threshold, false)?;
+        let sda = Logic::new(waveform, sda_channel, self.threshold, false)?;
+
+        let mut decoded = Decoded::default();
+        let mut annotate = |start: f64, end: f64, text: String, error: bool| {
+            decoded.annotations.push(Annotation {
+                channel: sda_channel,
+                start,
+                end,
+                text,
+                error,
+            })
+        };
+        let mut rows = Vec::new();
+        let mut transaction: Option<Transaction> = None;
+        // Bits of the byte being received and where its first bit started.
+        let mut bits: Vec<bool> = Vec::new();
+        let mut byte_start = 0;
+        let mut last_scl_rise = 0;
+
+        for i in 1..scl.len().min(sda.len()) {
+            let scl_rise = !scl.levels[i - 1] && scl.levels[i];
+            let scl_high = scl.levels[i - 1] && scl.levels[i];
+
+            if scl_high && sda.levels[i - 1] != sda.levels[i] {
+                let stop = sda.levels[i];
+                let repeated = match transaction.take() {
+                    Some(mut done) => {
+                        // The clock pulse around a stop or repeated start
+                        // reads as one bit, more are a cut off byte.
+                        done.incomplete = bits.len() > 1;
+                        rows.push(done.row());
+                        true
+                    }
+                    None => false,
+                };
+                bits.clear();
+
+                let (start, end, text) = match stop {
+                    // The box of a stop covers the last clock pulse.
+                    true => (last_scl_rise, i, "P"),
+                    // The box of a start runs until SCL goes low for the
+                    // first bit.
+                    false => {
+                        let end = scl.find(i, false).unwrap_or(i);
+                        (i, end, if repeated { "Sr" } else { "S" })
+                    }
+                };
+                annotate(
+                    scl.time(start as f64),
+                    scl.time(end as f64),
+                    text.to_string(),
+                    false,
+                );
+                if !stop {
+                    transaction = Some(Transaction {
+                        time: scl.time(i as f64),
+                        repeated,
+                        bytes: Vec::new(),
+                        incomplete: false,
+                    });
+                }
+                continue;
+            }
+
+            if !scl_rise {
+                continue;
+            }
+            last_scl_rise = i;
+            let current = match transaction.as_mut() {
+                Some(current) => current,
+                // Clock pulses before the first start can't be framed.
+                None => continue,
+            };
+            if bits.is_empty() {
+                byte_start = i;
+            }
+            bits.push(sda.levels[i]);
+            if bits.len() < 9 {
+                continue;
+            }
+
+            let value = bits[..8]
+                .iter()
+                .fold(0u8, |value, bit| (value << 1) | *bit as u8);
+            let ack = !bits[8];
+            let end = scl.find(i, false).unwrap_or(scl.len() - 1);
+            let text = match current.bytes.is_empty() {
+                true => format_address(value),
+                false => format!("0x{:02X}", value),
+            };
+            let text = match ack {
+                true => format!("{} A", text),
+                false => format!("{} N", text),
+            };
+            annotate(
+                scl.time(byte_start as f64),
+                scl.time(end as f64),
+                text,
+                !ack && current.bytes.is_empty(),
+            );
+            current.bytes.push((value, ack));
+            bits.clear();
+        }
+
+        // A transaction the capture cut off before its stop.
+        if let Some(current) = transaction {
+            rows.push(current.row());
+        }
+        decoded.rows = rows;
+        Ok(decoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::logic_waveform;
+
+    /// Samples per quarter of a clock period.
+    const QUARTER: usize = 2;
+
+    /// SCL and SDA of a synthesized bus, driven one step at a time.
+    struct Bus {
+        scl: Vec<bool>,
+        sda: Vec<bool>,
+    }
+
+    impl Bus {
+        fn new() -> Self {
+            let mut bus = Self {
+                scl: Vec::new(),
+                sda: Vec::new(),
+            };
+            bus.hold(true, true, 4);
+            bus
+        }
+
+        fn hold(&mut self, scl: bool, sda: bool, quarters: usize) {
+            self.scl.extend(vec![scl; quarters * QUARTER]);
+            self.sda.extend(vec![sda; quarters * QUARTER]);
+        }
+
+        fn start(&mut self) -> &mut Self {
+            self.hold(true, false, 2);
+            self.hold(false, false, 1);
+            self
+        }
+
+        fn repeated_start(&mut self) -> &mut Self {
+            self.hold(false, true, 1);
+            self.hold(true, true, 2);
+            self.start()
+        }
+
+        fn stop(&mut self) -> &mut Self {
+            self.hold(false, false, 1);
+            self.hold(true, false, 2);
+            self.hold(true, true, 4);
+            self
+        }
+
+        fn bit(&mut self, bit: bool) -> &mut Self {
+            self.hold(false, bit, 1);
+            self.hold(true, bit, 2);
+            self.hold(false, bit, 1);
+            self
+        }
+
+        fn byte(&mut self, value: u8, ack: bool) -> &mut Self {
+            for bit in (0..8).rev() {
+                self.bit(value >> bit & 1 == 1);
+            }
+            self.bit(!ack)
+        }
+
+        fn decode(&self) -> Decoded {
+            let waveform = logic_waveform(&[self.scl.clone(), self.sda.clone()], 1e-6);
+            I2cConfig::default().decode(&waveform).unwrap()
+        }
+    }
+
+    fn rows(decoded: &Decoded) -> Vec<(&str, Option<&str>)> {
+        decoded
+            .rows
+            .iter()
+            .map(|row| (row.text.as_str(), row.error.as_deref()))
+            .collect()
+    }
+
+    fn annotations(decoded: &Decoded) -> Vec<(&str, bool)> {
+        decoded
+            .annotations
+            .iter()
+            .map(|annotation| (annotation.text.as_str(), annotation.error))
+            .collect()
+    }
+
+    #[test]
+    fn write() {
+        let decoded = Bus::new()
+            .start()
+            .byte(0x50 << 1, true)
+            .byte(0x12, true)
+            .byte(0x34, true)
+            .stop()
+            .decode();
+        assert_eq!(rows(&decoded), [("S 0x50 W 12 34", None)]);
+        assert_eq!(
+            annotations(&decoded),
+            [
+                ("S", false),
+                ("0x50 W A", false),
+                ("0x12 A", false),
+                ("0x34 A", false),
+                ("P", false)
+            ]
+        );
+    }
+
+    #[test]
+    fn read_after_repeated_start() {
+        let decoded = Bus::new()
+            .start()
+            .byte(0x50 << 1, true)
+            .byte(0x00, true)
+            .repeated_start()
+            .byte(0x50 << 1 | 1, true)
+            .byte(0xAB, true)
+            // The master ends the read with a NACK, which is no error.
+            .byte(0xCD, false)
+            .stop()
+            .decode();
+        assert_eq!(
+            rows(&decoded),
+            [("S 0x50 W 00", None), ("Sr 0x50 R AB CD", None)]
+        );
+        assert_eq!(
+            annotations(&decoded),
+            [
+                ("S", false),
+                ("0x50 W A", false),
+                ("0x00 A", false),
+                ("Sr", false),
+                ("0x50 R A", false),
+                ("0xAB A", false),
+                ("0xCD N", false),
+                ("P", false)
+            ]
+        );
+    }
+
+    #[test]
+    fn address_not_acknowledged() {
+        let decoded = Bus::new().start().byte(0x51 << 1, false).stop().decode();
+        assert_eq!(
+            rows(&decoded),
+            [("S 0x51 W", Some("address not acknowledged"))]
+        );
+        assert_eq!(annotations(&decoded)[1], ("0x51 W N", true));
+    }
+
+    #[test]
+    fn write_not_acknowledged() {
+        let decoded = Bus::new()
+            .start()
+            .byte(0x50 << 1, true)
+            .byte(0x12, true)
+            .byte(0x34, false)
+            .stop()
+            .decode();
+        assert_eq!(
+            rows(&decoded),
+            [("S 0x50 W 12 34", Some("data byte 2 not acknowledged"))]
+        );
+    }
+
+    #[test]
+    fn incomplete_byte() {
+        let mut bus = Bus::new();
+        bus.start().byte(0x50 << 1, true);
+        for _ in 0..4 {
+            bus.bit(true);
+        }
+        let decoded = bus.stop().decode();
+        assert_eq!(rows(&decoded), [("S 0x50 W", Some("incomplete byte"))]);
+    }
+
+    #[test]
+    fn cut_off_before_stop() {
+        let decoded = Bus::new()
+            .start()
+            .byte(0x50 << 1, true)
+            .byte(0x12, true)
+            .decode();
+        assert_eq!(rows(&decoded), [("S 0x50 W 12", None)]);
+    }
+}
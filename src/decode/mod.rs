@@ -0,0 +1,219 @@
+use druid::Data;
+use thiserror::Error;
+
+use crate::decode::can::CanConfig;
+use crate::decode::i2c::I2cConfig;
+use crate::decode::spi::SpiConfig;
+use crate::decode::uart::UartConfig;
+use crate::math::channel_name;
+#[cfg(test)]
+use crate::waveform::Trace;
+use crate::waveform::Waveform;
+
+pub(crate) mod can;
+pub(crate) mod i2c;
+pub(crate) mod spi;
+pub(crate) mod uart;
+
+/// Fewest samples per bit that still give a sample near the bit center.
+pub(crate) const MIN_SAMPLES_PER_BIT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum Protocol {
+    Off,
+    Uart,
+    I2c,
+    Spi,
+    Can,
+}
+
+impl Protocol {
+    pub(crate) fn my_options() -> Vec<(&'static str, Protocol)> {
+        vec![
+            ("Off", Protocol::Off),
+            ("UART", Protocol::Uart),
+            ("I2C", Protocol::I2c),
+            ("SPI", Protocol::Spi),
+            ("CAN", Protocol::Can),
+        ]
+    }
+}
+
+/// Settings of all decoders, so switching protocols keeps them.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DecoderConfig {
+    pub(crate) protocol: Protocol,
+    pub(crate) uart: UartConfig,
+    pub(crate) i2c: I2cConfig,
+    pub(crate) spi: SpiConfig,
+    pub(crate) can: CanConfig,
+}
+
+impl Default for DecoderConfig {
+    fn default() -> Self {
+        Self {
+            protocol: Protocol::Off,
+            uart: UartConfig::default(),
+            i2c: I2cConfig::default(),
+            spi: SpiConfig::default(),
+            can: CanConfig::default(),
+        }
+    }
+}
+
+impl DecoderConfig {
+    /// Runs the selected decoder on `waveform`, `None` if decoding is off.
+    pub(crate) fn decode(&self, waveform: &Waveform) -> Option<Result<Decoded, DecodeError>> {
+        match self.protocol {
+            Protocol::Off => None,
+            Protocol::Uart => Some(self.uart.decode(waveform)),
+            Protocol::I2c => Some(self.i2c.decode(waveform)),
+            Protocol::Spi => Some(self.spi.decode(waveform)),
+            Protocol::Can => Some(self.can.decode(waveform)),
+        }
+    }
+}
+
+/// A decoded item drawn over the trace it was decoded from, e.g. one byte.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Annotation {
+    pub(crate) channel: usize,
+    /// Time span in seconds, relative to the trigger point like the
+    /// waveform.
+    pub(crate) start: f64,
+    pub(crate) end: f64,
+    pub(crate) text: String,
+    pub(crate) error: bool,
+}
+
+/// One line of the decoder table.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Row {
+    pub(crate) time: f64,
+    pub(crate) text: String,
+    pub(crate) error: Option<String>,
+}
+
+/// Everything a decoder found in a capture.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Decoded {
+    pub(crate) annotations: Vec<Annotation>,
+    pub(crate) rows: Vec<Row>,
+    /// Settings the decoder worked out itself, like a detected baud rate.
+    pub(crate) note: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub(crate) enum DecodeError {
+    #[error("{} is not in the capture", channel_name(*.0))]
+    MissingChannel(usize),
+    #[error("{0}")]
+    Settings(String),
+    #[error("{0} per bit at this time scale, at least {1} are needed")]
+    TooFewSamples(String, usize),
+    #[error("no edges found to detect the bit rate")]
+    NoEdges,
+}
+
+/// A trace turned into logic levels by a threshold.
+pub(crate) struct Logic {
+    pub(crate) levels: Vec<bool>,
+    pub(crate) start_time: f64,
+    pub(crate) sample_interval: f64,
+}
+
+impl Logic {
+    /// Levels of `channel`, high above `threshold` volts, or the other way
+    /// round if `inverted`.
+    pub(crate) fn new(
+        waveform: &Waveform,
+        channel: usize,
+        threshold: f64,
+        inverted: bool,
+    ) -> Result<Self, DecodeError> {
+        let trace = waveform
+            .trace(channel)
+            .ok_or(DecodeError::MissingChannel(channel))?;
+        Ok(Self {
+            levels: trace
+                .volts
+                .iter()
+                .map(|volts| (*volts > threshold) != inverted)
+                .collect(),
+            start_time: waveform.start_time,
+            sample_interval: waveform.sample_interval,
+        })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Time of sample `index`, which may be fractional.
+    pub(crate) fn time(&self, index: f64) -> f64 {
+        self.start_time + index * self.sample_interval
+    }
+
+    /// Level at the sample closest to `index`, if it is in the capture.
+    pub(crate) fn at(&self, index: f64) -> Option<bool> {
+        match index.round() {
+            i if i < 0.0 => None,
+            i => self.levels.get(i as usize).copied(),
+        }
+    }
+
+    /// First index from `from` on where the level is `level`.
+    pub(crate) fn find(&self, from: usize, level: bool) -> Option<usize> {
+        (from..self.len()).find(|i| self.levels[*i] == level)
+    }
+
+    /// Lengths in samples of the runs of equal levels, leaving out the first
+    /// and last one which the capture may have cut short.
+    pub(crate) fn run_lengths(&self) -> Vec<usize> {
+        let mut runs = Vec::new();
+        let mut start = 0;
+        for i in 1..self.len() {
+            if self.levels[i] != self.levels[i - 1] {
+                runs.push(i - start);
+                start = i;
+            }
+        }
+        match runs.len() {
+            0 => runs,
+            _ => runs.split_off(1),
+        }
+    }
+}
+
+/// `value` as hex, with the character it stands for if it is printable.
+pub(crate) fn format_byte(value: u32) -> String {
+    match char::from_u32(value) {
+        Some(c) if value < 0x80 && !c.is_ascii_control() => format!("0x{:02X} '{}'", value, c),
+        _ => format!("0x{:02X}", value),
+    }
+}
+
+/// Logic traces on channels 1, 2, ... as a waveform for the decoder tests,
+/// high at 3.3 V and low at 0 V.
+#[cfg(test)]
+pub(crate) fn logic_waveform(traces: &[Vec<bool>], sample_interval: f64) -> Waveform {
+    Waveform {
+        traces: traces
+            .iter()
+            .enumerate()
+            .map(|(index, levels)| Trace {
+                channel: index + 1,
+                volts: levels
+                    .iter()
+                    .map(|high| match high {
+                        true => 3.3,
+                        false => 0.0,
+                    })
+                    .collect(),
+            })
+            .collect(),
+        num_samples: traces.iter().map(|levels| levels.len()).min().unwrap_or(0),
+        start_time: 0.0,
+        sample_interval,
+    }
+}
@@ -0,0 +1,340 @@
+use druid::Data;
+
+use crate::decode::{format_byte, Annotation, DecodeError, Decoded, Logic, Row};
+use crate::math::channel_name;
+use crate::waveform::Waveform;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum BitOrder {
+    MsbFirst,
+    LsbFirst,
+}
+
+impl BitOrder {
+    pub(crate) fn my_options() -> Vec<(&'static str, BitOrder)> {
+        vec![
+            ("MSB first", BitOrder::MsbFirst),
+            ("LSB first", BitOrder::LsbFirst),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum DataLine {
+    Mosi,
+    Miso,
+}
+
+impl DataLine {
+    pub(crate) fn my_options() -> Vec<(&'static str, DataLine)> {
+        vec![("MOSI", DataLine::Mosi), ("MISO", DataLine::Miso)]
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            DataLine::Mosi => "MOSI",
+            DataLine::Miso => "MISO",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SpiConfig {
+    /// Channel probing SCLK, MOSI or MISO is on the other one.
+    pub(crate) sclk_channel: usize,
+    /// Which data line the other channel probes.
+    pub(crate) data_line: DataLine,
+    /// Clock idles high.
+    pub(crate) cpol: bool,
+    /// Data is sampled on the second clock edge of a bit.
+    pub(crate) cpha: bool,
+    pub(crate) bit_order: BitOrder,
+    pub(crate) word_size: usize,
+    /// Start a new word after the clock stopped for `idle_gap`, standing in
+    /// for the chip select line there is no channel left for.
+    pub(crate) cs_emulation: bool,
+    /// In seconds.
+    pub(crate) idle_gap: f64,
+    /// Logic threshold in volts.
+    pub(crate) threshold: f64,
+}
+
+impl Default for SpiConfig {
+    fn default() -> Self {
+        Self {
+            sclk_channel: 1,
+            data_line: DataLine::Mosi,
+            cpol: false,
+            cpha: false,
+            bit_order: BitOrder::MsbFirst,
+            word_size: 8,
+            cs_emulation: false,
+            idle_gap: 10e-6,
+            threshold: 1.5,
+        }
+    }
+}
+
+/// `value` in hex, padded to the digits a `bits` wide word needs.
+fn format_word(value: u32, bits: usize) -> String {
+    match bits {
+        8 => format_byte(value),
+        _ => format!("0x{:0width$X}", value, width = bits.div_ceil(4)),
+    }
+}
+
+impl SpiConfig {
+    pub(crate) fn data_channel(&self) -> usize {
+        match self.sclk_channel {
+            1 => 2,
+            _ => 1,
+        }
+    }
+
+    pub(crate) fn decode(&self, waveform: &Waveform) -> Result<Decoded, DecodeError> {
+        if !(1..=32).contains(&self.word_size) {
+            return Err(DecodeError::Settings(
+                "word size must be 1 to 32 bits".to_string(),
+            ));
+        }
+        let data_channel = self.data_channel();
+        let sclk = Logic::new(waveform, self.sclk_channel, self.threshold, false)?;
+        let data = Logic::new(waveform, data_channel, self.threshold, false)?;
+        // Modes 0 and 3 sample on the rising edge, 1 and 2 on the falling.
+        let sample_level = self.cpol == self.cpha;
+        let idle_gap = match self.cs_emulation {
+            true => Some(self.idle_gap / sclk.sample_interval),
+            false => None,
+        };
+
+        let mut decoded = Decoded {
+            note: Some(format!(
+                "{} on {}",
+                self.data_line.name(),
+                channel_name(data_channel)
+            )),
+            ..Decoded::default()
+        };
+        let mut bits: Vec<bool> = Vec::new();
+        let mut word_start = 0;
+        let mut last_edge: Option<usize> = None;
+
+        for i in 1..sclk.len().min(data.len()) {
+            if sclk.levels[i - 1] == sclk.levels[i] {
+                continue;
+            }
+            let gap = last_edge.map(|edge| (i - edge) as f64);
+            last_edge = Some(i);
+            if let (Some(gap), Some(idle_gap)) = (gap, idle_gap) {
+                if gap > idle_gap && !bits.is_empty() {
+                    let time = sclk.time(word_start as f64);
+                    decoded.annotations.push(Annotation {
+                        channel: data_channel,
+                        start: time,
+                        end: sclk.time((i - gap as usize) as f64),
+                        text: format!("{} bits", bits.len()),
+                        error: true,
+                    });
+                    decoded.rows.push(Row {
+                        time,
+                        text: format!("{} bits", bits.len()),
+                        error: Some("incomplete word".to_string()),
+                    });
+                    bits.clear();
+                }
+            }
+            if sclk.levels[i] != sample_level {
+                continue;
+            }
+
+            if bits.is_empty() {
+                word_start = i;
+            }
+            bits.push(data.levels[i]);
+            if bits.len() < self.word_size {
+                continue;
+            }
+
+            let shift_in = |value: u32, bit: &bool| (value << 1) | *bit as u32;
+            let value = match self.bit_order {
+                BitOrder::MsbFirst => bits.iter().fold(0, shift_in),
+                BitOrder::LsbFirst => bits.iter().rev().fold(0, shift_in),
+            };
+            // The word ends with the clock edge after its last sample.
+            let end = sclk.find(i, !sample_level).unwrap_or(i);
+            let text = format_word(value, self.word_size);
+            decoded.annotations.push(Annotation {
+                channel: data_channel,
+                start: sclk.time(word_start as f64),
+                end: sclk.time(end as f64),
+                text: text.clone(),
+                error: false,
+            });
+            decoded.rows.push(Row {
+                time: sclk.time(word_start as f64),
+                text,
+                error: None,
+            });
+            bits.clear();
+        }
+
+        Ok(decoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::logic_waveform;
+
+    /// Samples per half clock period.
+    const HALF: usize = 4;
+    const SAMPLE_INTERVAL: f64 = 1e-6;
+
+    /// SCLK and data of a synthesized bus in the mode of `config`, with data
+    /// changing a sample after the clock edges it isn't sampled on.
+    struct Bus {
+        cpol: bool,
+        cpha: bool,
+        sclk: Vec<bool>,
+        data: Vec<bool>,
+    }
+
+    impl Bus {
+        fn new(config: &SpiConfig) -> Self {
+            let mut bus = Self {
+                cpol: config.cpol,
+                cpha: config.cpha,
+                sclk: Vec::new(),
+                data: Vec::new(),
+            };
+            bus.idle(4);
+            bus
+        }
+
+        fn idle(&mut self, samples: usize) -> &mut Self {
+            self.sclk.extend(vec![self.cpol; samples]);
+            self.data.extend(vec![false; samples]);
+            self
+        }
+
+        fn bits(&mut self, bits: &[bool]) -> &mut Self {
+            for bit in bits {
+                let halves = match self.cpha {
+                    false => [self.cpol, !self.cpol],
+                    true => [!self.cpol, self.cpol],
+                };
+                for sclk in halves {
+                    self.sclk.extend([sclk; HALF]);
+                    self.data.extend([*bit; HALF]);
+                }
+            }
+            self
+        }
+
+        /// `value` most significant bit first.
+        fn word(&mut self, value: u32, bits: usize) -> &mut Self {
+            let bits: Vec<bool> = (0..bits).rev().map(|bit| value >> bit & 1 == 1).collect();
+            self.bits(&bits)
+        }
+
+        fn decode(&self, config: &SpiConfig) -> Vec<(String, Option<String>)> {
+            let mut sclk = self.sclk.clone();
+            sclk.extend(vec![self.cpol; 4]);
+            let mut data = vec![false];
+            data.extend(&self.data);
+            data.extend(vec![false; 3]);
+            let waveform = logic_waveform(&[sclk, data], SAMPLE_INTERVAL);
+            config
+                .decode(&waveform)
+                .unwrap()
+                .rows
+                .into_iter()
+                .map(|row| (row.text, row.error))
+                .collect()
+        }
+    }
+
+    fn words(texts: &[&str]) -> Vec<(String, Option<String>)> {
+        texts.iter().map(|text| (text.to_string(), None)).collect()
+    }
+
+    fn check_mode(cpol: bool, cpha: bool) {
+        let config = SpiConfig {
+            cpol,
+            cpha,
+            ..SpiConfig::default()
+        };
+        let mut bus = Bus::new(&config);
+        bus.word(0xA5, 8).word(0x96, 8);
+        assert_eq!(bus.decode(&config), words(&["0xA5", "0x96"]));
+    }
+
+    #[test]
+    fn mode_0() {
+        check_mode(false, false);
+    }
+
+    #[test]
+    fn mode_1() {
+        check_mode(false, true);
+    }
+
+    #[test]
+    fn mode_2() {
+        check_mode(true, false);
+    }
+
+    #[test]
+    fn mode_3() {
+        check_mode(true, true);
+    }
+
+    #[test]
+    fn bit_order() {
+        let config = SpiConfig {
+            bit_order: BitOrder::LsbFirst,
+            ..SpiConfig::default()
+        };
+        let mut bus = Bus::new(&config);
+        // 0xC1 least significant bit first.
+        bus.word(0x83, 8);
+        assert_eq!(bus.decode(&config), words(&["0xC1"]));
+    }
+
+    #[test]
+    fn word_size() {
+        let config = SpiConfig {
+            word_size: 12,
+            ..SpiConfig::default()
+        };
+        let mut bus = Bus::new(&config);
+        bus.word(0xABC, 12).word(0x00F, 12);
+        assert_eq!(bus.decode(&config), words(&["0xABC", "0x00F"]));
+    }
+
+    #[test]
+    fn idle_gap_ends_incomplete_word() {
+        let config = SpiConfig {
+            cs_emulation: true,
+            idle_gap: 10.0 * SAMPLE_INTERVAL,
+            ..SpiConfig::default()
+        };
+        let mut bus = Bus::new(&config);
+        bus.word(0b101, 3).idle(20).word(0xA5, 8);
+        assert_eq!(
+            bus.decode(&config),
+            [
+                ("3 bits".to_string(), Some("incomplete word".to_string())),
+                ("0xA5".to_string(), None)
+            ]
+        );
+
+        // Without chip select emulation the words run into each other.
+        let config = SpiConfig {
+            cs_emulation: false,
+            ..config
+        };
+        assert_eq!(bus.decode(&config), words(&["0xB4"]));
+    }
+}
@@ -0,0 +1,366 @@
+use druid::Data;
+
+use crate::decode::{
+    format_byte, Annotation, DecodeError, Decoded, Logic, Row, MIN_SAMPLES_PER_BIT,
+};
+use crate::waveform::Waveform;
+
+/// Rates auto-baud snaps to when the measured rate is close enough.
+const STANDARD_BAUD_RATES: [usize; 16] = [
+    300, 600, 1200, 2400, 4800, 9600, 14400, 19200, 28800, 38400, 57600, 76800, 115200, 230400,
+    460800, 921600,
+];
+const BAUD_SNAP_TOLERANCE: f64 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+impl Parity {
+    pub(crate) fn my_options() -> Vec<(&'static str, Parity)> {
+        vec![
+            ("None", Parity::None),
+            ("Even", Parity::Even),
+            ("Odd", Parity::Odd),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum StopBits {
+    One,
+    OneAndHalf,
+    Two,
+}
+
+impl StopBits {
+    pub(crate) fn my_options() -> Vec<(&'static str, StopBits)> {
+        vec![
+            ("1", StopBits::One),
+            ("1.5", StopBits::OneAndHalf),
+            ("2", StopBits::Two),
+        ]
+    }
+
+    fn bits(&self) -> f64 {
+        match self {
+            StopBits::One => 1.0,
+            StopBits::OneAndHalf => 1.5,
+            StopBits::Two => 2.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct UartConfig {
+    pub(crate) channel: usize,
+    pub(crate) baud_rate: usize,
+    /// Measure the baud rate from the shortest pulses instead.
+    pub(crate) auto_baud: bool,
+    pub(crate) data_bits: usize,
+    pub(crate) parity: Parity,
+    pub(crate) stop_bits: StopBits,
+    /// Idle low and start bit high, like on the RS-232 side of a driver.
+    pub(crate) inverted: bool,
+    /// Logic threshold in volts.
+    pub(crate) threshold: f64,
+}
+
+impl Default for UartConfig {
+    fn default() -> Self {
+        Self {
+            channel: 1,
+            baud_rate: 9600,
+            auto_baud: false,
+            data_bits: 8,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            inverted: false,
+            threshold: 1.5,
+        }
+    }
+}
+
+impl UartConfig {
+    pub(crate) fn decode(&self, waveform: &Waveform) -> Result<Decoded, DecodeError> {
+        if !(5..=9).contains(&self.data_bits) {
+            return Err(DecodeError::Settings(
+                "data bits must be 5 to 9".to_string(),
+            ));
+        }
+        // Levels are normalized so that idle and stop bits are high.
+        let logic = Logic::new(waveform, self.channel, self.threshold, self.inverted)?;
+
+        let (baud_rate, note) = match self.auto_baud {
+            true => {
+                let baud_rate = detect_baud_rate(&logic)?;
+                (
+                    baud_rate,
+                    Some(format!("detected {} baud", baud_rate.round())),
+                )
+            }
+            false => (self.baud_rate as f64, None),
+        };
+        if baud_rate <= 0.0 {
+            return Err(DecodeError::Settings(
+                "baud rate must be positive".to_string(),
+            ));
+        }
+        let samples_per_bit = 1.0 / (baud_rate * logic.sample_interval);
+        if samples_per_bit < MIN_SAMPLES_PER_BIT as f64 {
+            return Err(DecodeError::TooFewSamples(
+                format!("{:.1} samples", samples_per_bit),
+                MIN_SAMPLES_PER_BIT,
+            ));
+        }
+
+        let mut decoded = Decoded {
+            note,
+            ..Decoded::default()
+        };
+        let parity_bits = match self.parity {
+            Parity::None => 0,
+            _ => 1,
+        };
+        let frame_bits = 1.0 + (self.data_bits + parity_bits) as f64 + self.stop_bits.bits();
+        let center = |start: usize, bit: f64| start as f64 + (bit + 0.5) * samples_per_bit;
+
+        let mut position = 0;
+        while let Some(start) = logic
+            .find(position, true)
+            .and_then(|idle| logic.find(idle, false))
+        {
+            // A glitch rather than a start bit, look for the next edge.
+            match logic.at(center(start, 0.0)) {
+                Some(false) => {}
+                Some(true) => {
+                    position = start + 1;
+                    continue;
+                }
+                None => break,
+            }
+            let end = start as f64 + frame_bits * samples_per_bit;
+            if end > logic.len() as f64 {
+                break;
+            }
+
+            let mut value = 0u32;
+            let mut ones = 0;
+            for bit in 0..self.data_bits {
+                if logic.at(center(start, 1.0 + bit as f64)) == Some(true) {
+                    value |= 1 << bit;
+                    ones += 1;
+                }
+            }
+            let mut errors = Vec::new();
+            if parity_bits == 1 {
+                let parity = logic.at(center(start, 1.0 + self.data_bits as f64)) == Some(true);
+                let odd = (ones + parity as usize) % 2 == 1;
+                if odd != (self.parity == Parity::Odd) {
+                    errors.push("parity error");
+                }
+            }
+            let first_stop = 1.0 + (self.data_bits + parity_bits) as f64;
+            let last_stop = first_stop + self.stop_bits.bits().ceil() - 1.0;
+            let stop_ok = [first_stop, last_stop]
+                .iter()
+                .all(|bit| logic.at(center(start, *bit)) == Some(true));
+            if !stop_ok {
+                errors.push("framing error");
+            }
+
+            let text = format_byte(value);
+            let error = match errors.is_empty() {
+                true => None,
+                false => Some(errors.join(", ")),
+            };
+            decoded.annotations.push(Annotation {
+                channel: self.channel,
+                start: logic.time(start as f64),
+                end: logic.time(end),
+                text: match error {
+                    Some(_) => format!("{} !", text),
+                    None => text.clone(),
+                },
+                error: error.is_some(),
+            });
+            decoded.rows.push(Row {
+                time: logic.time(start as f64),
+                text,
+                error,
+            });
+
+            // Resume in the first stop bit, a framing error may leave the
+            // line low.
+            position = center(start, first_stop) as usize;
+        }
+
+        Ok(decoded)
+    }
+}
+
+/// Baud rate from the shortest pulses, which are a single bit long in any
+/// but the most uniform data. Glitches too short to be a bit are ignored.
+fn detect_baud_rate(logic: &Logic) -> Result<f64, DecodeError> {
+    let runs: Vec<usize> = logic
+        .run_lengths()
+        .into_iter()
+        .filter(|run| *run >= MIN_SAMPLES_PER_BIT)
+        .collect();
+    let shortest = *runs.iter().min().ok_or(DecodeError::NoEdges)?;
+    let single_bits: Vec<usize> = runs
+        .into_iter()
+        .filter(|run| (*run as f64) < shortest as f64 * 1.5)
+        .collect();
+    let samples_per_bit = single_bits.iter().sum::<usize>() as f64 / single_bits.len() as f64;
+    let baud_rate = 1.0 / (samples_per_bit * logic.sample_interval);
+
+    let standard = STANDARD_BAUD_RATES
+        .iter()
+        .map(|rate| *rate as f64)
+        .find(|rate| ((baud_rate - rate) / rate).abs() < BAUD_SNAP_TOLERANCE);
+    Ok(standard.unwrap_or(baud_rate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::logic_waveform;
+
+    const SAMPLES_PER_BIT: usize = 8;
+    const SAMPLE_INTERVAL: f64 = 1.0 / (9600 * SAMPLES_PER_BIT) as f64;
+
+    /// Line levels of `bytes` sent with `config`, after some idle time.
+    fn line(config: &UartConfig, bytes: &[u32]) -> Vec<bool> {
+        let mut bits = vec![true; 4];
+        for byte in bytes {
+            bits.push(false);
+            let data: Vec<bool> = (0..config.data_bits)
+                .map(|bit| byte >> bit & 1 == 1)
+                .collect();
+            let ones = data.iter().filter(|bit| **bit).count();
+            bits.extend(&data);
+            match config.parity {
+                Parity::None => {}
+                Parity::Even => bits.push(ones % 2 == 1),
+                Parity::Odd => bits.push(ones % 2 == 0),
+            }
+            bits.extend([true; 2]);
+        }
+        bits.extend([true; 4]);
+        bits.iter()
+            .flat_map(|bit| [*bit != config.inverted; SAMPLES_PER_BIT])
+            .collect()
+    }
+
+    fn decode(config: &UartConfig, levels: Vec<bool>) -> Result<Decoded, DecodeError> {
+        config.decode(&logic_waveform(&[levels], SAMPLE_INTERVAL))
+    }
+
+    fn rows(decoded: &Decoded) -> Vec<(&str, Option<&str>)> {
+        decoded
+            .rows
+            .iter()
+            .map(|row| (row.text.as_str(), row.error.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn bytes() {
+        let config = UartConfig::default();
+        let decoded = decode(&config, line(&config, &[0x48, 0x00, 0xFF])).unwrap();
+        assert_eq!(
+            rows(&decoded),
+            [("0x48 'H'", None), ("0x00", None), ("0xFF", None)]
+        );
+        assert_eq!(
+            decoded.annotations[0].start,
+            4.0 * SAMPLES_PER_BIT as f64 * SAMPLE_INTERVAL
+        );
+    }
+
+    #[test]
+    fn parity() {
+        for parity in [Parity::Even, Parity::Odd] {
+            let config = UartConfig {
+                parity,
+                ..UartConfig::default()
+            };
+            let decoded = decode(&config, line(&config, &[0x41, 0x43])).unwrap();
+            assert_eq!(rows(&decoded), [("0x41 'A'", None), ("0x43 'C'", None)]);
+
+            let other = UartConfig {
+                parity: match parity {
+                    Parity::Even => Parity::Odd,
+                    _ => Parity::Even,
+                },
+                ..config.clone()
+            };
+            let decoded = decode(&other, line(&config, &[0x41])).unwrap();
+            assert_eq!(rows(&decoded), [("0x41 'A'", Some("parity error"))]);
+        }
+    }
+
+    #[test]
+    fn framing_error() {
+        let config = UartConfig::default();
+        let mut levels = line(&config, &[0x55]);
+        // Hold the line low through the stop bit, like a break.
+        let stop = (4 + 9) * SAMPLES_PER_BIT;
+        levels[stop..stop + SAMPLES_PER_BIT].fill(false);
+        let decoded = decode(&config, levels).unwrap();
+        assert_eq!(rows(&decoded), [("0x55 'U'", Some("framing error"))]);
+        assert!(decoded.annotations[0].error);
+    }
+
+    #[test]
+    fn inverted() {
+        let config = UartConfig {
+            inverted: true,
+            ..UartConfig::default()
+        };
+        let levels = line(&config, &[0x21]);
+        assert!(!levels[0]);
+        let decoded = decode(&config, levels.clone()).unwrap();
+        assert_eq!(rows(&decoded), [("0x21 '!'", None)]);
+
+        // Read with the wrong polarity, the idle line looks like a start bit.
+        let decoded = decode(&UartConfig::default(), levels).unwrap();
+        assert_ne!(rows(&decoded), [("0x21 '!'", None)]);
+    }
+
+    #[test]
+    fn auto_baud() {
+        let config = UartConfig {
+            auto_baud: true,
+            ..UartConfig::default()
+        };
+        let mut levels = line(&config, &[0x55, 0x48, 0x69]);
+        let decoded = decode(&config, levels.clone()).unwrap();
+        assert_eq!(decoded.note.as_deref(), Some("detected 9600 baud"));
+        assert_eq!(rows(&decoded)[0], ("0x55 'U'", None));
+
+        // A one sample glitch on the idle line isn't the bit time.
+        levels[2] = false;
+        let logic = Logic::new(
+            &logic_waveform(&[levels], SAMPLE_INTERVAL),
+            1,
+            config.threshold,
+            false,
+        )
+        .unwrap();
+        assert_eq!(detect_baud_rate(&logic), Ok(9600.0));
+    }
+
+    #[test]
+    fn auto_baud_without_edges() {
+        let config = UartConfig {
+            auto_baud: true,
+            ..UartConfig::default()
+        };
+        assert_eq!(decode(&config, vec![true; 100]), Err(DecodeError::NoEdges));
+    }
+}
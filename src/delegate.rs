@@ -0,0 +1,73 @@
+use druid::{
+    AppDelegate, Command, DelegateCtx, Env, FileInfo, Handled, Selector, SingleUse, Target,
+    WindowId,
+};
+
+use crate::comm::DEV_RESPONSE;
+use crate::HantekState;
+
+/// Accept commands of the setup file dialogs.
+pub(crate) const SAVE_SETUP: Selector<FileInfo> = Selector::new("hanteker.save-setup");
+pub(crate) const LOAD_SETUP: Selector<FileInfo> = Selector::new("hanteker.load-setup");
+
+/// Accept commands of the export file dialogs.
+pub(crate) const EXPORT_CSV: Selector<FileInfo> = Selector::new("hanteker.export-csv");
+pub(crate) const EXPORT_WAV: Selector<FileInfo> = Selector::new("hanteker.export-wav");
+pub(crate) const EXPORT_SIGROK: Selector<FileInfo> = Selector::new("hanteker.export-sigrok");
+pub(crate) const EXPORT_PNG: Selector<FileInfo> = Selector::new("hanteker.export-png");
+pub(crate) const EXPORT_SVG: Selector<FileInfo> = Selector::new("hanteker.export-svg");
+pub(crate) const EXPORT_DECODED: Selector<FileInfo> = Selector::new("hanteker.export-decoded");
+
+pub(crate) struct Delegate;
+
+impl AppDelegate<HantekState> for Delegate {
+    fn command(
+        &mut self,
+        _ctx: &mut DelegateCtx,
+        _target: Target,
+        cmd: &Command,
+        data: &mut HantekState,
+        _env: &Env,
+    ) -> Handled {
+        if let Some(response) = cmd.get(DEV_RESPONSE).and_then(SingleUse::take) {
+            data.on_response(response);
+            Handled::Yes
+        } else if let Some(file) = cmd.get(SAVE_SETUP) {
+            data.save_setup(file.path());
+            Handled::Yes
+        } else if let Some(file) = cmd.get(LOAD_SETUP) {
+            data.load_setup(file.path());
+            Handled::Yes
+        } else if let Some(file) = cmd.get(EXPORT_CSV) {
+            data.export_csv(file.path());
+            Handled::Yes
+        } else if let Some(file) = cmd.get(EXPORT_WAV) {
+            data.export_wav(file.path());
+            Handled::Yes
+        } else if let Some(file) = cmd.get(EXPORT_SIGROK) {
+            data.export_sigrok(file.path());
+            Handled::Yes
+        } else if let Some(file) = cmd.get(EXPORT_PNG) {
+            data.export_png(file.path());
+            Handled::Yes
+        } else if let Some(file) = cmd.get(EXPORT_SVG) {
+            data.export_svg(file.path());
+            Handled::Yes
+        } else if let Some(file) = cmd.get(EXPORT_DECODED) {
+            data.export_decoded(file.path());
+            Handled::Yes
+        } else {
+            Handled::No
+        }
+    }
+
+    fn window_removed(
+        &mut self,
+        _id: WindowId,
+        data: &mut HantekState,
+        _env: &Env,
+        _ctx: &mut DelegateCtx,
+    ) {
+        data.save_session();
+    }
+}
@@ -1,12 +1,22 @@
 use std::sync::mpsc;
 use std::sync::mpsc::{Receiver, Sender};
 use std::thread;
-use std::time::Duration;
 
-use hanteker_lib::device::cfg::RunningStatus;
-use hanteker_lib::models::hantek2d42::Hantek2D42;
+use druid::{ExtEventSink, SingleUse, Target};
 
-use crate::comm::{DevCommand, DevCommandResult};
+use crate::comm::{DevCommand, DevCommandResult, DevRequest, DevResponse, DEV_RESPONSE};
+use crate::device::{DeviceOpener, ScopeDevice, UsbOpener};
+use crate::sim::SimulatorOpener;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Backend {
+    Usb,
+    /// `front_panel_edits` makes the simulator change settings by itself
+    /// now and then, like someone at its front panel.
+    Simulated {
+        front_panel_edits: bool,
+    },
+}
 
 fn exit() -> ! {
     std::process::exit(0);
@@ -17,450 +27,245 @@ fn exit_err(message: String) -> ! {
     std::process::exit(0);
 }
 
-fn handle(rx: Receiver<DevCommand>, tx: Sender<Result<DevCommandResult, String>>) {
-    let context = match libusb::Context::new() {
-        Ok(context) => context,
-        // TODO show a popup window about failure and then quit.
-        Err(error) => exit_err(format!("error while opening USB context: {}", error)),
-    };
+fn execute(device: &mut dyn ScopeDevice, cmd: DevCommand) -> Result<DevCommandResult, String> {
+    let empty = |result: Result<(), String>| result.map(|_| DevCommandResult::EmptyResult);
+
+    match cmd {
+        DevCommand::Connect => unreachable!(),
+        DevCommand::Disconnect => unreachable!(),
+        DevCommand::Coupling(channel, coupling) => empty(
+            device
+                .set_channel_coupling(channel, coupling.clone())
+                .map_err(|error| {
+                    format!(
+                        "failed to set channel coupling, channel={}, coupling={}, error={}",
+                        channel,
+                        coupling.my_to_string(),
+                        error
+                    )
+                }),
+        ),
+        DevCommand::ChannelEnable(channel, enabled) => empty(
+            device
+                .set_channel_enabled(channel, enabled)
+                .map_err(|error| {
+                    format!(
+                        "failed to set channel status, channel={}, error={}",
+                        channel, error
+                    )
+                }),
+        ),
+        DevCommand::Probe(channel, probe) => empty(
+            device
+                .set_channel_probe(channel, probe.clone())
+                .map_err(|error| {
+                    format!(
+                        "failed to set channel probe, channel={}, probe={}, error={}",
+                        channel,
+                        probe.my_to_string(),
+                        error
+                    )
+                }),
+        ),
+        DevCommand::Scale(channel, scale) => empty(
+            device
+                .set_channel_scale(channel, scale.clone())
+                .map_err(|error| {
+                    format!(
+                        "failed to set channel scale, channel={}, scale={}, error={}",
+                        channel,
+                        scale.my_to_string(),
+                        error
+                    )
+                }),
+        ),
+        DevCommand::Offset(channel, offset) => {
+            empty(device.set_channel_offset(channel, offset).map_err(|error| {
+                format!(
+                    "failed to set channel offset, channel={}, offset={}, error={}",
+                    channel, offset, error
+                )
+            }))
+        }
+        DevCommand::BwLimit(channel, bw_limit) => empty(
+            device
+                .set_bandwidth_limit(channel, bw_limit)
+                .map_err(|error| {
+                    format!(
+                        "failed to set channel bandwidth limit, channel={}, error={}",
+                        channel, error
+                    )
+                }),
+        ),
+        DevCommand::TimeScale(time_scale) => {
+            empty(device.set_time_scale(time_scale.clone()).map_err(|error| {
+                format!(
+                    "failed to set time scale, time_scale={}, error={}",
+                    time_scale.my_to_string(),
+                    error
+                )
+            }))
+        }
+        DevCommand::TimeOffset(offset) => empty(device.set_time_offset(offset).map_err(|error| {
+            format!(
+                "failed to set time offset, time_offset={}, error={}",
+                offset, error
+            )
+        })),
+        DevCommand::TriggerSource(channel) => {
+            empty(device.set_trigger_source(channel).map_err(|error| {
+                format!(
+                    "failed to set trigger source, trigger_source={}, error={}",
+                    channel, error
+                )
+            }))
+        }
+        DevCommand::TriggerMode(mode) => {
+            empty(device.set_trigger_mode(mode.clone()).map_err(|error| {
+                format!(
+                    "failed to set trigger mode, trigger_mode={}, error={}",
+                    mode.my_to_string(),
+                    error
+                )
+            }))
+        }
+        DevCommand::TriggerLevel(level) => {
+            empty(device.set_trigger_level(level).map_err(|error| {
+                format!(
+                    "failed to set trigger level, trigger_level={}, error={}",
+                    level, error
+                )
+            }))
+        }
+        DevCommand::ScopeRunning(status) => empty(
+            device
+                .set_running(status)
+                .map_err(|error| format!("failed to change running status, error={}", error)),
+        ),
+        DevCommand::AwgRunningStatus(status) => empty(
+            device
+                .set_awg_running(status)
+                .map_err(|error| format!("failed to change awg running status, error={}", error)),
+        ),
+        DevCommand::AwgFrequency(frequency) => empty(
+            device
+                .set_awg_frequency(frequency)
+                .map_err(|error| format!("failed to set awg frequency, error={}", error)),
+        ),
+        DevCommand::AwgAmplitude(amplitude) => empty(
+            device
+                .set_awg_amplitude(amplitude)
+                .map_err(|error| format!("failed to set awg amplitude, error={}", error)),
+        ),
+        DevCommand::AwgType(awg_type) => empty(
+            device
+                .set_awg_type(awg_type)
+                .map_err(|error| format!("failed to set awg type, error={}", error)),
+        ),
+        DevCommand::AwgOffset(offset) => empty(
+            device
+                .set_awg_offset(offset)
+                .map_err(|error| format!("failed to set awg offset, error={}", error)),
+        ),
+        DevCommand::AwgDutySquare(duty_square) => empty(
+            device
+                .set_awg_duty_square(duty_square)
+                .map_err(|error| format!("failed to set awg duty::square, error={}", error)),
+        ),
+        DevCommand::AwgDutyRamp(duty_ramp) => empty(
+            device
+                .set_awg_duty_ramp(duty_ramp)
+                .map_err(|error| format!("failed to set awg duty::ramp, error={}", error)),
+        ),
+        DevCommand::AwgDutyTrap(high, low, rise) => empty(
+            device
+                .set_awg_duty_trap(high, low, rise)
+                .map_err(|error| format!("failed to set awg duty::trap, error={}", error)),
+        ),
+        DevCommand::DeviceFunction(device_function) => empty(
+            device
+                .set_device_function(device_function)
+                .map_err(|error| format!("failed to set device_function, error={}", error)),
+        ),
+        DevCommand::Capture(channels, num_samples) => {
+            if channels.is_empty() {
+                Err("no channel selected for capture".to_string())
+            } else {
+                device
+                    .capture(channels, num_samples)
+                    .map(DevCommandResult::CaptureResult)
+                    .map_err(|error| format!("failed to capture, error={}", error))
+            }
+        }
+        DevCommand::ReadSettings => match device.read_settings() {
+            Ok(Some(settings)) => Ok(DevCommandResult::Settings(Box::new(settings))),
+            Ok(None) => Ok(DevCommandResult::SettingsUnsupported),
+            Err(error) => Err(format!("failed to read settings, error={}", error)),
+        },
+    }
+}
 
-    let mut device: Option<Hantek2D42> = None;
+fn handle(rx: Receiver<DevRequest>, sink: ExtEventSink, opener: &dyn DeviceOpener) {
+    let mut device: Option<Box<dyn ScopeDevice + '_>> = None;
 
     loop {
-        match rx.recv() {
+        let DevRequest { id, cmd } = match rx.recv() {
             Err(_) => exit(),
-            Ok(cmd) => {
-                match &cmd {
-                    DevCommand::Connect => {
-                        if let Some(old_device) = &mut device {
-                            old_device.usb.release().expect("could not release device");
-                        }
-                        match Hantek2D42::open(&context, Duration::from_millis(1000)) {
-                            Ok(hantek) => {
-                                device = Some(hantek);
-                                match device.as_mut().unwrap().usb.claim() {
-                                    Ok(_) => tx
-                                        .send(Ok(DevCommandResult::EmptyResult))
-                                        .unwrap_or_else(|_| exit()),
-                                    Err(error) => {
-                                        tx.send(Err(format!(
-                                            "failed to claim device: {}",
-                                            error.my_to_string()
-                                        )))
-                                        .unwrap_or_else(|_| exit());
-                                    }
-                                }
-                            }
-                            Err(error) => {
-                                device = None;
-                                tx.send(Err(format!(
-                                    "failed to open device: {}",
-                                    error.my_to_string()
-                                )))
-                                .unwrap_or_else(|_| exit());
-                            }
-                        }
-                        continue;
-                    }
-                    DevCommand::Disconnect => {
-                        if device.is_none() {
-                            tx.send(Ok(DevCommandResult::EmptyResult))
-                                .unwrap_or_else(|_| exit());
-                            continue;
-                        }
-                        device
-                            .as_mut()
-                            .unwrap()
-                            .usb
-                            .release()
-                            .expect("could not disconnect");
-                        device = None;
-                        tx.send(Ok(DevCommandResult::EmptyResult))
-                            .unwrap_or_else(|_| exit());
-                        continue;
-                    }
-                    _ => {}
-                }
+            Ok(request) => request,
+        };
 
-                let device = match &mut device {
-                    None => {
-                        tx.send(Err("not connected".to_string()))
-                            .unwrap_or_else(|_| exit());
-                        continue;
-                    }
-                    Some(device) => device,
-                };
-
-                match cmd {
-                    DevCommand::Connect => unreachable!(),
-                    DevCommand::Disconnect => unreachable!(),
-                    DevCommand::Coupling(channel, coupling) => {
-                        match device.set_channel_coupling(channel, coupling.clone()) {
-                            Ok(_) => {
-                                tx.send(Ok(DevCommandResult::EmptyResult))
-                                    .unwrap_or_else(|_| exit());
-                            }
-                            Err(error) => {
-                                tx.send(Err(format!("failed to set channel coupling, channel={}, coupling={}, error={}",
-                                                    channel, coupling.my_to_string(), error.my_to_string())))
-                                    .unwrap_or_else(|_| exit());
-                            }
-                        }
-                    }
-                    DevCommand::ChannelEnable(channel, enabled) => {
-                        let result = match enabled {
-                            true => device.enable_channel(channel),
-                            false => device.disable_channel(channel),
-                        };
-                        match result {
-                            Ok(_) => {
-                                tx.send(Ok(DevCommandResult::EmptyResult))
-                                    .unwrap_or_else(|_| exit());
-                            }
-                            Err(error) => {
-                                tx.send(Err(format!(
-                                    "failed to set channel status, channel={}, error={}",
-                                    channel,
-                                    error.my_to_string()
-                                )))
-                                .unwrap_or_else(|_| exit());
-                            }
-                        }
-                    }
-                    DevCommand::Probe(channel, probe) => {
-                        match device.set_channel_probe(channel, probe.clone()) {
-                            Ok(_) => {
-                                tx.send(Ok(DevCommandResult::EmptyResult))
-                                    .unwrap_or_else(|_| exit());
-                            }
-                            Err(error) => {
-                                tx.send(Err(format!(
-                                    "failed to set channel probe, channel={}, probe={}, error={}",
-                                    channel,
-                                    probe.my_to_string(),
-                                    error.my_to_string()
-                                )))
-                                .unwrap_or_else(|_| exit());
-                            }
-                        }
-                    }
-                    DevCommand::Scale(channel, scale) => {
-                        match device.set_channel_scale(channel, scale.clone()) {
-                            Ok(_) => {
-                                tx.send(Ok(DevCommandResult::EmptyResult))
-                                    .unwrap_or_else(|_| exit());
-                            }
-                            Err(error) => {
-                                tx.send(Err(format!(
-                                    "failed to set channel scale, channel={}, scale={}, error={}",
-                                    channel,
-                                    scale.my_to_string(),
-                                    error.my_to_string()
-                                )))
-                                .unwrap_or_else(|_| exit());
-                            }
-                        }
-                    }
-                    DevCommand::Offset(channel, offset) => {
-                        match device.set_channel_offset_with_auto_adjustment(channel, offset) {
-                            Ok(_) => {
-                                tx.send(Ok(DevCommandResult::EmptyResult))
-                                    .unwrap_or_else(|_| exit());
-                            }
-                            Err(error) => {
-                                tx.send(Err(format!(
-                                    "failed to set channel offset, channel={}, offset={}, error={}",
-                                    channel,
-                                    offset,
-                                    error.my_to_string()
-                                )))
-                                .unwrap_or_else(|_| exit());
-                            }
-                        }
-                    }
-                    DevCommand::BwLimit(channel, bw_limit) => {
-                        let result = match bw_limit {
-                            true => device.channel_enable_bandwidth_limit(channel),
-                            false => device.channel_disable_bandwidth_limit(channel),
-                        };
-                        match result {
-                            Ok(_) => {
-                                tx.send(Ok(DevCommandResult::EmptyResult))
-                                    .unwrap_or_else(|_| exit());
-                            }
-                            Err(error) => {
-                                tx.send(Err(format!(
-                                    "failed to set channel bandwidth limit, channel={}, error={}",
-                                    channel,
-                                    error.my_to_string()
-                                )))
-                                .unwrap_or_else(|_| exit());
-                            }
-                        }
-                    }
-                    DevCommand::TimeScale(time_scale) => {
-                        match device.set_time_scale(time_scale.clone()) {
-                            Ok(_) => {
-                                tx.send(Ok(DevCommandResult::EmptyResult))
-                                    .unwrap_or_else(|_| exit());
-                            }
-                            Err(error) => {
-                                tx.send(Err(format!(
-                                    "failed to set time scale, time_scale={}, error={}",
-                                    time_scale.my_to_string(),
-                                    error.my_to_string()
-                                )))
-                                .unwrap_or_else(|_| exit());
-                            }
-                        }
-                    }
-                    DevCommand::TimeOffset(offset) => {
-                        match device.set_time_offset_with_auto_adjustment(offset) {
-                            Ok(_) => {
-                                tx.send(Ok(DevCommandResult::EmptyResult))
-                                    .unwrap_or_else(|_| exit());
-                            }
-                            Err(error) => {
-                                tx.send(Err(format!(
-                                    "failed to set time offset, time_offset={}, error={}",
-                                    offset,
-                                    error.my_to_string()
-                                )))
-                                .unwrap_or_else(|_| exit());
-                            }
-                        }
-                    }
-                    DevCommand::TriggerSource(channel) => {
-                        match device.set_trigger_source(channel) {
-                            Ok(_) => {
-                                tx.send(Ok(DevCommandResult::EmptyResult))
-                                    .unwrap_or_else(|_| exit());
-                            }
-                            Err(error) => {
-                                tx.send(Err(format!(
-                                    "failed to set trigger source, trigger_source={}, error={}",
-                                    channel,
-                                    error.my_to_string()
-                                )))
-                                .unwrap_or_else(|_| exit());
-                            }
-                        }
-                    }
-                    DevCommand::TriggerMode(mode) => match device.set_trigger_mode(mode.clone()) {
-                        Ok(_) => {
-                            tx.send(Ok(DevCommandResult::EmptyResult))
-                                .unwrap_or_else(|_| exit());
-                        }
-                        Err(error) => {
-                            tx.send(Err(format!(
-                                "failed to set trigger mode, trigger_mode={}, error={}",
-                                mode.my_to_string(),
-                                error.my_to_string()
-                            )))
-                            .unwrap_or_else(|_| exit());
-                        }
-                    },
-                    DevCommand::TriggerLevel(level) => {
-                        match device.set_trigger_level_with_auto_adjustment(level) {
-                            Ok(_) => {
-                                tx.send(Ok(DevCommandResult::EmptyResult))
-                                    .unwrap_or_else(|_| exit());
-                            }
-                            Err(error) => {
-                                tx.send(Err(format!(
-                                    "failed to set trigger level, trigger_level={}, error={}",
-                                    level,
-                                    error.my_to_string()
-                                )))
-                                .unwrap_or_else(|_| exit());
-                            }
-                        }
-                    }
-                    DevCommand::ScopeRunning(status) => {
-                        let running_result = match status {
-                            RunningStatus::Start => device.start(),
-                            RunningStatus::Stop => device.stop(),
-                        };
-                        match running_result {
-                            Ok(_) => {
-                                tx.send(Ok(DevCommandResult::EmptyResult))
-                                    .unwrap_or_else(|_| exit());
-                            }
-                            Err(error) => {
-                                tx.send(Err(format!(
-                                    "failed to change running status, error={}",
-                                    error.my_to_string()
-                                )))
-                                .unwrap_or_else(|_| exit());
-                            }
-                        }
-                    }
-                    DevCommand::AwgRunningStatus(status) => {
-                        let running_result = match status {
-                            RunningStatus::Start => device.awg_start(),
-                            RunningStatus::Stop => device.awg_stop(),
-                        };
-                        match running_result {
-                            Ok(_) => {
-                                tx.send(Ok(DevCommandResult::EmptyResult))
-                                    .unwrap_or_else(|_| exit());
-                            }
-                            Err(error) => {
-                                tx.send(Err(format!(
-                                    "failed to change awg running status, error={}",
-                                    error.my_to_string()
-                                )))
-                                .unwrap_or_else(|_| exit());
-                            }
-                        }
-                    }
-                    DevCommand::AwgFrequency(frequency) => {
-                        match device.set_awg_frequency(frequency) {
-                            Ok(_) => {
-                                tx.send(Ok(DevCommandResult::EmptyResult))
-                                    .unwrap_or_else(|_| exit());
-                            }
-                            Err(error) => {
-                                tx.send(Err(format!(
-                                    "failed to set awg frequency, error={}",
-                                    error.my_to_string()
-                                )))
-                                .unwrap_or_else(|_| exit());
-                            }
-                        }
-                    }
-                    DevCommand::AwgAmplitude(amplitude) => {
-                        match device.set_awg_amplitude(amplitude) {
-                            Ok(_) => {
-                                tx.send(Ok(DevCommandResult::EmptyResult))
-                                    .unwrap_or_else(|_| exit());
-                            }
-                            Err(error) => {
-                                tx.send(Err(format!(
-                                    "failed to set awg amplitude, error={}",
-                                    error.my_to_string()
-                                )))
-                                .unwrap_or_else(|_| exit());
-                            }
-                        }
-                    }
-                    DevCommand::AwgType(awg_type) => match device.set_awg_type(awg_type) {
-                        Ok(_) => {
-                            tx.send(Ok(DevCommandResult::EmptyResult))
-                                .unwrap_or_else(|_| exit());
-                        }
-                        Err(error) => {
-                            tx.send(Err(format!(
-                                "failed to set awg type, error={}",
-                                error.my_to_string()
-                            )))
-                            .unwrap_or_else(|_| exit());
-                        }
-                    },
-                    DevCommand::AwgOffset(offset) => match device.set_awg_offset(offset) {
-                        Ok(_) => {
-                            tx.send(Ok(DevCommandResult::EmptyResult))
-                                .unwrap_or_else(|_| exit());
-                        }
-                        Err(error) => {
-                            tx.send(Err(format!(
-                                "failed to set awg offset, error={}",
-                                error.my_to_string()
-                            )))
-                            .unwrap_or_else(|_| exit());
-                        }
-                    },
-                    DevCommand::AwgDutySquare(duty_square) => {
-                        match device.set_awg_duty_square(duty_square) {
-                            Ok(_) => {
-                                tx.send(Ok(DevCommandResult::EmptyResult))
-                                    .unwrap_or_else(|_| exit());
-                            }
-                            Err(error) => {
-                                tx.send(Err(format!(
-                                    "failed to set awg duty::square, error={}",
-                                    error.my_to_string()
-                                )))
-                                .unwrap_or_else(|_| exit());
-                            }
-                        }
-                    }
-                    DevCommand::AwgDutyRamp(duty_ramp) => match device.set_awg_duty_ramp(duty_ramp)
-                    {
+        let result = match cmd {
+            DevCommand::Connect => {
+                if let Some(old_device) = &mut device {
+                    old_device.release().expect("could not release device");
+                }
+                device = None;
+                match opener.open() {
+                    Ok(mut new_device) => match new_device.claim() {
                         Ok(_) => {
-                            tx.send(Ok(DevCommandResult::EmptyResult))
-                                .unwrap_or_else(|_| exit());
-                        }
-                        Err(error) => {
-                            tx.send(Err(format!(
-                                "failed to set awg duty::ramp, error={}",
-                                error.my_to_string()
-                            )))
-                            .unwrap_or_else(|_| exit());
+                            device = Some(new_device);
+                            Ok(DevCommandResult::EmptyResult)
                         }
+                        Err(error) => Err(format!("failed to claim device: {}", error)),
                     },
-                    DevCommand::AwgDutyTrap(high, low, rise) => {
-                        match device.set_awg_duty_trap(high, low, rise) {
-                            Ok(_) => {
-                                tx.send(Ok(DevCommandResult::EmptyResult))
-                                    .unwrap_or_else(|_| exit());
-                            }
-                            Err(error) => {
-                                tx.send(Err(format!(
-                                    "failed to set awg duty::trap, error={}",
-                                    error.my_to_string()
-                                )))
-                                .unwrap_or_else(|_| exit());
-                            }
-                        }
-                    }
-                    DevCommand::DeviceFunction(device_function) => {
-                        match device.set_device_function(device_function) {
-                            Ok(_) => {
-                                tx.send(Ok(DevCommandResult::EmptyResult))
-                                    .unwrap_or_else(|_| exit());
-                            }
-                            Err(error) => {
-                                tx.send(Err(format!(
-                                    "failed to set device_function, error={}",
-                                    error.my_to_string()
-                                )))
-                                .unwrap_or_else(|_| exit());
-                            }
-                        }
-                    }
-                    DevCommand::Capture(channels, num_samples) => {
-                        if channels.is_empty() {
-                            tx.send(Err(format!("no channel selected for capture",)))
-                                .unwrap_or_else(|_| exit());
-                        } else {
-                            match device.capture(&channels, num_samples) {
-                                Ok(capture) => {
-                                    tx.send(Ok(DevCommandResult::CaptureResult(capture)))
-                                        .unwrap_or_else(|_| exit());
-                                }
-                                Err(error) => {
-                                    tx.send(Err(format!(
-                                        "failed to capture, error={}",
-                                        error.my_to_string()
-                                    )))
-                                    .unwrap_or_else(|_| exit());
-                                }
-                            }
-                        }
-                    }
+                    Err(error) => Err(format!("failed to open device: {}", error)),
                 }
             }
-        }
+            DevCommand::Disconnect => {
+                if let Some(old_device) = &mut device {
+                    old_device.release().expect("could not disconnect");
+                }
+                device = None;
+                Ok(DevCommandResult::EmptyResult)
+            }
+            cmd => match &mut device {
+                None => Err("not connected".to_string()),
+                Some(device) => execute(device.as_mut(), cmd),
+            },
+        };
+
+        sink.submit_command(
+            DEV_RESPONSE,
+            SingleUse::new(DevResponse { id, result }),
+            Target::Auto,
+        )
+        .unwrap_or_else(|_| exit());
     }
 }
 
-pub(crate) fn handler_thread() -> (
-    Sender<DevCommand>,
-    Receiver<Result<DevCommandResult, String>>,
-) {
-    let (tx0, rx0) = mpsc::channel();
-    let (tx1, rx1) = mpsc::channel();
-    thread::spawn(move || handle(rx0, tx1));
-    (tx0, rx1)
+/// Spawns the device thread; results are posted back to the UI as
+/// [`DEV_RESPONSE`] commands through `sink`.
+pub(crate) fn handler_thread(backend: Backend, sink: ExtEventSink) -> Sender<DevRequest> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || match backend {
+        Backend::Usb => match UsbOpener::new() {
+            Ok(opener) => handle(rx, sink, &opener),
+            // TODO show a popup window about failure and then quit.
+            Err(error) => exit_err(error),
+        },
+        Backend::Simulated { front_panel_edits } => {
+            handle(rx, sink, &SimulatorOpener { front_panel_edits })
+        }
+    });
+    tx
 }
@@ -0,0 +1,216 @@
+use std::time::Duration;
+
+use hanteker_lib::device::cfg::{
+    AwgType, Coupling, DeviceFunction, HantekConfig, Probe, RunningStatus, Scale, TimeScale,
+    TriggerMode,
+};
+use hanteker_lib::models::hantek2d42::Hantek2D42;
+
+pub(crate) type DeviceResult<T> = Result<T, String>;
+
+/// Everything the command thread needs from a scope, independent of how the
+/// scope is actually reached (USB, simulation, ...).
+pub(crate) trait ScopeDevice {
+    fn claim(&mut self) -> DeviceResult<()>;
+    fn release(&mut self) -> DeviceResult<()>;
+
+    fn set_device_function(&mut self, device_function: DeviceFunction) -> DeviceResult<()>;
+    fn set_running(&mut self, status: RunningStatus) -> DeviceResult<()>;
+
+    fn set_channel_enabled(&mut self, channel: usize, enabled: bool) -> DeviceResult<()>;
+    fn set_channel_coupling(&mut self, channel: usize, coupling: Coupling) -> DeviceResult<()>;
+    fn set_channel_probe(&mut self, channel: usize, probe: Probe) -> DeviceResult<()>;
+    fn set_channel_scale(&mut self, channel: usize, scale: Scale) -> DeviceResult<()>;
+    fn set_channel_offset(&mut self, channel: usize, offset: f32) -> DeviceResult<()>;
+    fn set_bandwidth_limit(&mut self, channel: usize, enabled: bool) -> DeviceResult<()>;
+
+    fn set_time_scale(&mut self, time_scale: TimeScale) -> DeviceResult<()>;
+    fn set_time_offset(&mut self, offset: f32) -> DeviceResult<()>;
+
+    fn set_trigger_source(&mut self, channel: usize) -> DeviceResult<()>;
+    fn set_trigger_mode(&mut self, mode: TriggerMode) -> DeviceResult<()>;
+    fn set_trigger_level(&mut self, level: f32) -> DeviceResult<()>;
+
+    fn set_awg_running(&mut self, status: RunningStatus) -> DeviceResult<()>;
+    fn set_awg_frequency(&mut self, frequency: f32) -> DeviceResult<()>;
+    fn set_awg_amplitude(&mut self, amplitude: f32) -> DeviceResult<()>;
+    fn set_awg_type(&mut self, awg_type: AwgType) -> DeviceResult<()>;
+    fn set_awg_offset(&mut self, offset: f32) -> DeviceResult<()>;
+    fn set_awg_duty_square(&mut self, duty: f32) -> DeviceResult<()>;
+    fn set_awg_duty_ramp(&mut self, duty: f32) -> DeviceResult<()>;
+    fn set_awg_duty_trap(&mut self, high: f32, low: f32, rise: f32) -> DeviceResult<()>;
+
+    /// Raw ADC bytes, one per channel per sample, interleaved in the order
+    /// the channels are given.
+    fn capture(&mut self, channels: Vec<usize>, num_samples: usize) -> DeviceResult<Vec<u8>>;
+
+    /// Current settings of the device, including changes made on its front
+    /// panel. Settings the device can't report are `None`, a device that
+    /// can't report any returns `None` so it isn't asked again.
+    fn read_settings(&mut self) -> DeviceResult<Option<HantekConfig>>;
+}
+
+/// Finds and opens a device; the returned device is not claimed yet.
+pub(crate) trait DeviceOpener {
+    fn open(&self) -> DeviceResult<Box<dyn ScopeDevice + '_>>;
+}
+
+pub(crate) struct UsbOpener {
+    context: libusb::Context,
+}
+
+impl UsbOpener {
+    pub(crate) fn new() -> DeviceResult<Self> {
+        match libusb::Context::new() {
+            Ok(context) => Ok(Self { context }),
+            Err(error) => Err(format!("error while opening USB context: {}", error)),
+        }
+    }
+}
+
+impl DeviceOpener for UsbOpener {
+    fn open(&self) -> DeviceResult<Box<dyn ScopeDevice + '_>> {
+        match Hantek2D42::open(&self.context, Duration::from_millis(1000)) {
+            Ok(hantek) => Ok(Box::new(hantek)),
+            Err(error) => Err(error.my_to_string().to_string()),
+        }
+    }
+}
+
+impl ScopeDevice for Hantek2D42<'_> {
+    fn claim(&mut self) -> DeviceResult<()> {
+        self.usb
+            .claim()
+            .map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn release(&mut self) -> DeviceResult<()> {
+        self.usb
+            .release()
+            .map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn set_device_function(&mut self, device_function: DeviceFunction) -> DeviceResult<()> {
+        Hantek2D42::set_device_function(self, device_function)
+            .map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn set_running(&mut self, status: RunningStatus) -> DeviceResult<()> {
+        match status {
+            RunningStatus::Start => self.start(),
+            RunningStatus::Stop => self.stop(),
+        }
+        .map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn set_channel_enabled(&mut self, channel: usize, enabled: bool) -> DeviceResult<()> {
+        match enabled {
+            true => self.enable_channel(channel),
+            false => self.disable_channel(channel),
+        }
+        .map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn set_channel_coupling(&mut self, channel: usize, coupling: Coupling) -> DeviceResult<()> {
+        Hantek2D42::set_channel_coupling(self, channel, coupling)
+            .map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn set_channel_probe(&mut self, channel: usize, probe: Probe) -> DeviceResult<()> {
+        Hantek2D42::set_channel_probe(self, channel, probe)
+            .map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn set_channel_scale(&mut self, channel: usize, scale: Scale) -> DeviceResult<()> {
+        Hantek2D42::set_channel_scale(self, channel, scale)
+            .map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn set_channel_offset(&mut self, channel: usize, offset: f32) -> DeviceResult<()> {
+        self.set_channel_offset_with_auto_adjustment(channel, offset)
+            .map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn set_bandwidth_limit(&mut self, channel: usize, enabled: bool) -> DeviceResult<()> {
+        match enabled {
+            true => self.channel_enable_bandwidth_limit(channel),
+            false => self.channel_disable_bandwidth_limit(channel),
+        }
+        .map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn set_time_scale(&mut self, time_scale: TimeScale) -> DeviceResult<()> {
+        Hantek2D42::set_time_scale(self, time_scale)
+            .map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn set_time_offset(&mut self, offset: f32) -> DeviceResult<()> {
+        self.set_time_offset_with_auto_adjustment(offset)
+            .map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn set_trigger_source(&mut self, channel: usize) -> DeviceResult<()> {
+        Hantek2D42::set_trigger_source(self, channel)
+            .map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn set_trigger_mode(&mut self, mode: TriggerMode) -> DeviceResult<()> {
+        Hantek2D42::set_trigger_mode(self, mode).map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn set_trigger_level(&mut self, level: f32) -> DeviceResult<()> {
+        self.set_trigger_level_with_auto_adjustment(level)
+            .map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn set_awg_running(&mut self, status: RunningStatus) -> DeviceResult<()> {
+        match status {
+            RunningStatus::Start => self.awg_start(),
+            RunningStatus::Stop => self.awg_stop(),
+        }
+        .map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn set_awg_frequency(&mut self, frequency: f32) -> DeviceResult<()> {
+        Hantek2D42::set_awg_frequency(self, frequency)
+            .map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn set_awg_amplitude(&mut self, amplitude: f32) -> DeviceResult<()> {
+        Hantek2D42::set_awg_amplitude(self, amplitude)
+            .map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn set_awg_type(&mut self, awg_type: AwgType) -> DeviceResult<()> {
+        Hantek2D42::set_awg_type(self, awg_type).map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn set_awg_offset(&mut self, offset: f32) -> DeviceResult<()> {
+        Hantek2D42::set_awg_offset(self, offset).map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn set_awg_duty_square(&mut self, duty: f32) -> DeviceResult<()> {
+        Hantek2D42::set_awg_duty_square(self, duty)
+            .map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn set_awg_duty_ramp(&mut self, duty: f32) -> DeviceResult<()> {
+        Hantek2D42::set_awg_duty_ramp(self, duty).map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn set_awg_duty_trap(&mut self, high: f32, low: f32, rise: f32) -> DeviceResult<()> {
+        Hantek2D42::set_awg_duty_trap(self, high, low, rise)
+            .map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn capture(&mut self, channels: Vec<usize>, num_samples: usize) -> DeviceResult<Vec<u8>> {
+        Hantek2D42::capture(self, &channels, num_samples)
+            .map_err(|error| error.my_to_string().to_string())
+    }
+
+    fn read_settings(&mut self) -> DeviceResult<Option<HantekConfig>> {
+        // The protocol hanteker_lib implements only writes settings, there is
+        // no query for any of them yet.
+        Ok(None)
+    }
+}
@@ -0,0 +1,169 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use anyhow::Context;
+use druid::FileSpec;
+
+use crate::decode::Decoded;
+use crate::export::Capture;
+use crate::math::channel_name;
+
+pub(crate) const CSV_FILE: FileSpec = FileSpec::new("CSV", &["csv"]);
+
+/// Writes `capture` as CSV: a `#` commented header block with the settings,
+/// then one row per sample with the time in seconds and every trace in
+/// volts, each followed by its raw ADC value if `raw_column` is set.
+pub(crate) fn write_csv(
+    capture: &Capture,
+    raw_column: bool,
+    out: &mut impl Write,
+) -> std::io::Result<()> {
+    writeln!(out, "# Hanteker capture")?;
+    for line in capture.describe() {
+        writeln!(out, "# {}", line)?;
+    }
+
+    let waveform = capture.waveform;
+    let raw: Vec<Option<Vec<u8>>> = waveform
+        .traces
+        .iter()
+        .map(|trace| match raw_column {
+            true => capture.raw_samples(trace.channel),
+            false => None,
+        })
+        .collect();
+
+    write!(out, "time (s)")?;
+    for (trace, raw) in waveform.traces.iter().zip(&raw) {
+        let name = channel_name(trace.channel);
+        write!(out, ",{} (V)", name)?;
+        if raw.is_some() {
+            write!(out, ",{} (raw)", name)?;
+        }
+    }
+    writeln!(out)?;
+
+    for index in 0..waveform.num_samples {
+        write!(out, "{:e}", waveform.time(index))?;
+        for (trace, raw) in waveform.traces.iter().zip(&raw) {
+            match trace.volts.get(index) {
+                Some(volts) => write!(out, ",{:e}", volts)?,
+                None => write!(out, ",")?,
+            }
+            if let Some(raw) = raw {
+                match raw.get(index) {
+                    Some(sample) => write!(out, ",{}", sample)?,
+                    None => write!(out, ",")?,
+                }
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn save_csv(capture: &Capture, raw_column: bool, path: &Path) -> anyhow::Result<()> {
+    let file =
+        File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+    let mut out = BufWriter::new(file);
+    write_csv(capture, raw_column, &mut out)
+        .and_then(|_| out.flush())
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// `field` in double quotes, as decoded text may contain commas and quotes.
+fn quote(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
+
+/// Writes the decoder table as CSV, one row per decoded item.
+pub(crate) fn write_decoded(decoded: &Decoded, out: &mut impl Write) -> std::io::Result<()> {
+    writeln!(out, "time (s),data,error")?;
+    for row in &decoded.rows {
+        writeln!(
+            out,
+            "{:e},{},{}",
+            row.time,
+            quote(&row.text),
+            quote(row.error.as_deref().unwrap_or(""))
+        )?;
+    }
+    Ok(())
+}
+
+pub(crate) fn save_decoded(decoded: &Decoded, path: &Path) -> anyhow::Result<()> {
+    let file =
+        File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+    let mut out = BufWriter::new(file);
+    write_decoded(decoded, &mut out)
+        .and_then(|_| out.flush())
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::test_capture;
+
+    fn write(raw_column: bool) -> Vec<String> {
+        let (waveform, raw) = test_capture();
+        let capture = Capture {
+            waveform: &waveform,
+            raw: &raw,
+        };
+        let mut out = Vec::new();
+        write_csv(&capture, raw_column, &mut out).unwrap();
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// The rows after the settings header, split into fields.
+    fn table(lines: &[String]) -> Vec<Vec<String>> {
+        lines
+            .iter()
+            .skip_while(|line| line.starts_with('#'))
+            .map(|line| line.split(',').map(str::to_string).collect())
+            .collect()
+    }
+
+    #[test]
+    fn header_and_rows() {
+        let lines = write(false);
+        assert_eq!(lines[0], "# Hanteker capture");
+        assert!(lines.contains(&"# samples: 3".to_string()));
+        assert!(lines.iter().any(|line| line.starts_with("# CH2: scale=")));
+
+        let table = table(&lines);
+        assert_eq!(table[0], ["time (s)", "CH1 (V)", "CH2 (V)", "MATH (V)"]);
+        assert_eq!(table.len(), 1 + 3);
+        let values: Vec<f64> = table[2].iter().map(|v| v.parse().unwrap()).collect();
+        assert_eq!(values, [0.0, -0.25, 0.125, -0.125]);
+    }
+
+    #[test]
+    fn raw_columns_follow_their_channel() {
+        let table = table(&write(true));
+        assert_eq!(
+            table[0],
+            [
+                "time (s)",
+                "CH1 (V)",
+                "CH1 (raw)",
+                "CH2 (V)",
+                "CH2 (raw)",
+                "MATH (V)"
+            ]
+        );
+        for (index, row) in table[1..].iter().enumerate() {
+            assert_eq!(row.len(), 6);
+            assert_eq!(row[2], (10 + index).to_string());
+            assert_eq!(row[4], (20 + index).to_string());
+        }
+        assert_eq!(table[3][1].parse::<f64>().unwrap(), 1.0);
+        assert_eq!(table[3][3].parse::<f64>().unwrap(), -1.0);
+    }
+}
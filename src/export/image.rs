@@ -0,0 +1,121 @@
+use std::fs;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+use druid::piet::{Device, ImageFormat};
+use druid::{FileSpec, Point, RenderContext, Size};
+
+use crate::export::svg::SvgRenderContext;
+use crate::view::DisplayMode;
+use crate::widget::scope::{draw_text, TEXT_COLOR};
+use crate::widget::{scope, spectrum, xy};
+use crate::HantekState;
+
+pub(crate) const PNG_FILE: FileSpec = FileSpec::new("PNG image", &["png"]);
+pub(crate) const SVG_FILE: FileSpec = FileSpec::new("SVG image", &["svg"]);
+
+/// Draws the display as it is on screen, in whatever mode it is in, plus
+/// the selected measurements which only have their own panel on screen.
+fn render<R: RenderContext>(rc: &mut R, size: Size, state: &HantekState) {
+    match state.get_display_mode() {
+        DisplayMode::Time => scope::render(rc, size, state),
+        DisplayMode::Spectrum => spectrum::render(rc, size, state),
+        DisplayMode::Xy => xy::render(rc, size, state),
+    }
+    render_measurements(rc, size, state);
+}
+
+/// Measurement table in the bottom right corner, if any is selected.
+fn render_measurements<R: RenderContext>(rc: &mut R, size: Size, state: &HantekState) {
+    let text = state.get_measurements_text();
+    let lines: Vec<&str> = text.lines().collect();
+    if lines.len() < 2 {
+        return;
+    }
+
+    let padding = 4.0;
+    let mut bottom = size.height - padding;
+    for line in lines.iter().rev() {
+        draw_text(rc, line, TEXT_COLOR, |text_size| {
+            bottom -= text_size.height;
+            let origin = Point::new(size.width - text_size.width - padding, bottom);
+            bottom -= padding;
+            origin
+        });
+    }
+}
+
+/// Renders the display off-screen at `width` x `height` pixels.
+pub(crate) fn save_png(
+    state: &HantekState,
+    width: usize,
+    height: usize,
+    path: &Path,
+) -> anyhow::Result<()> {
+    let mut device = Device::new().map_err(|err| anyhow!("no render device: {}", err))?;
+    let mut target = device
+        .bitmap_target(width, height, 1.0)
+        .map_err(|err| anyhow!("failed to create {}x{} image: {}", width, height, err))?;
+    {
+        let mut rc = target.render_context();
+        render(&mut rc, Size::new(width as f64, height as f64), state);
+        rc.finish()
+            .map_err(|err| anyhow!("failed to render image: {}", err))?;
+    }
+    let mut pixels = vec![0; width * height * 4];
+    target
+        .copy_raw_pixels(ImageFormat::RgbaSeparate, &mut pixels)
+        .map_err(|err| anyhow!("failed to read back image: {}", err))?;
+
+    let file =
+        File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Renders the display as an SVG document of `width` x `height` units.
+pub(crate) fn save_svg(
+    state: &HantekState,
+    width: usize,
+    height: usize,
+    path: &Path,
+) -> anyhow::Result<()> {
+    let size = Size::new(width as f64, height as f64);
+    let mut rc = SvgRenderContext::new(size);
+    render(&mut rc, size, state);
+    fs::write(path, rc.into_svg()).with_context(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::export::svg::assert_well_formed;
+    use crate::export::{test_capture, test_path};
+
+    #[test]
+    fn every_display_mode_is_well_formed_svg() {
+        let mut state = HantekState::new(mpsc::channel().0);
+        let (waveform, raw) = test_capture();
+        state.capture = Some(Arc::new(waveform));
+        state.capture_raw = Some(Arc::new(raw));
+        for mode in [DisplayMode::Time, DisplayMode::Spectrum, DisplayMode::Xy] {
+            state.display_mode = mode;
+            let path = test_path("display.svg");
+            save_svg(&state, 640, 480, &path).unwrap();
+            let svg = fs::read_to_string(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+            assert_well_formed(&svg);
+            assert!(svg.contains("width=\"640\" height=\"480\""));
+        }
+    }
+}
@@ -0,0 +1,130 @@
+#[cfg(test)]
+use std::path::PathBuf;
+
+use hanteker_lib::device::cfg::HantekConfig;
+
+use crate::math::channel_name;
+#[cfg(test)]
+use crate::math::MATH_CHANNEL;
+#[cfg(test)]
+use crate::waveform::Trace;
+use crate::waveform::Waveform;
+
+pub(crate) mod csv;
+pub(crate) mod image;
+pub(crate) mod sigrok;
+pub(crate) mod svg;
+pub(crate) mod wav;
+
+/// What the device returned for a capture, kept next to the decoded
+/// [`Waveform`] for exporting.
+#[derive(Debug)]
+pub(crate) struct RawCapture {
+    /// Raw ADC bytes, interleaved in the order of `channels`.
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) channels: Vec<usize>,
+    /// Settings the capture was requested with.
+    pub(crate) cfg: HantekConfig,
+}
+
+/// The capture on screen, the math trace included, and its raw data.
+pub(crate) struct Capture<'a> {
+    pub(crate) waveform: &'a Waveform,
+    pub(crate) raw: &'a RawCapture,
+}
+
+impl Capture<'_> {
+    /// Raw ADC samples of `channel`, if the device captured it.
+    pub(crate) fn raw_samples(&self, channel: usize) -> Option<Vec<u8>> {
+        let channels = &self.raw.channels;
+        let position = channels.iter().position(|c| *c == channel)?;
+        Some(
+            self.raw
+                .bytes
+                .iter()
+                .skip(position)
+                .step_by(channels.len())
+                .take(self.waveform.num_samples)
+                .copied()
+                .collect(),
+        )
+    }
+
+    /// One line per setting the capture was taken with.
+    pub(crate) fn describe(&self) -> Vec<String> {
+        let cfg = &self.raw.cfg;
+        let mut lines = vec![
+            format!("samples: {}", self.waveform.num_samples),
+            format!("sample interval: {} s", self.waveform.sample_interval),
+            format!(
+                "time scale: {}/div",
+                show(&cfg.time_scale, |t| t.my_to_string().to_string())
+            ),
+            format!("time offset: {} s", show(&cfg.time_offset, f32::to_string)),
+            format!(
+                "trigger: source={}, mode={}, level={} V",
+                show(&cfg.trigger_source_channel, |c| channel_name(*c)),
+                show(&cfg.trigger_mode, |m| m.my_to_string().to_string()),
+                show(&cfg.trigger_level, f32::to_string),
+            ),
+        ];
+        for &channel in &self.raw.channels {
+            let scale = cfg.channel_scale.get(&channel).cloned().flatten();
+            let probe = cfg.channel_probe.get(&channel).cloned().flatten();
+            let coupling = cfg.channel_coupling.get(&channel).cloned().flatten();
+            let offset = cfg.channel_offset.get(&channel).copied().flatten();
+            let bw_limit = cfg.channel_bandwidth_limit.get(&channel).copied().flatten();
+            lines.push(format!(
+                "{}: scale={}/div, probe={}, coupling={}, offset={} V, bandwidth limit={}",
+                channel_name(channel),
+                show(&scale, |s| s.my_to_string().to_string()),
+                show(&probe, |p| p.my_to_string().to_string()),
+                show(&coupling, |c| c.my_to_string().to_string()),
+                show(&offset, f32::to_string),
+                show(&bw_limit, bool::to_string),
+            ));
+        }
+        lines
+    }
+}
+
+fn show<T>(value: &Option<T>, f: impl Fn(&T) -> String) -> String {
+    value
+        .as_ref()
+        .map(f)
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// CH1, CH2 and the math trace over three samples 1 µs apart, for the
+/// exporter tests. The raw bytes of CH1 are 10, 11, 12 and those of CH2 20,
+/// 21, 22; the math trace has none.
+#[cfg(test)]
+pub(crate) fn test_capture() -> (Waveform, RawCapture) {
+    let trace = |channel, volts: [f64; 3]| Trace {
+        channel,
+        volts: volts.to_vec(),
+    };
+    let waveform = Waveform {
+        traces: vec![
+            trace(1, [0.5, -0.25, 1.0]),
+            trace(2, [0.0, 0.125, -1.0]),
+            trace(MATH_CHANNEL, [0.5, -0.125, 0.0]),
+        ],
+        num_samples: 3,
+        start_time: -1e-6,
+        sample_interval: 1e-6,
+    };
+    let raw = RawCapture {
+        bytes: vec![10, 20, 11, 21, 12, 22],
+        channels: vec![1, 2],
+        cfg: HantekConfig::new(2),
+    };
+    (waveform, raw)
+}
+
+/// A path in the temporary directory unique to this test run, for exporters
+/// that only write files.
+#[cfg(test)]
+pub(crate) fn test_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("hanteker_gui-{}-{}", std::process::id(), name))
+}
@@ -0,0 +1,138 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+use druid::FileSpec;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::export::Capture;
+use crate::math::channel_name;
+
+pub(crate) const SIGROK_FILE: FileSpec = FileSpec::new("sigrok session", &["sr"]);
+
+/// Version of the session format PulseView and sigrok-cli read.
+const SESSION_VERSION: &str = "2";
+/// libsigrok release whose session files these mimic.
+const SIGROK_VERSION: &str = "0.5.2";
+
+/// Metadata of a session with every trace of `capture` as an analog channel,
+/// numbered from 1 like the chunks written by [`save_sigrok`].
+fn metadata(capture: &Capture, sample_rate: u64) -> String {
+    let traces = &capture.waveform.traces;
+    let mut metadata = String::new();
+    metadata.push_str("[global]\n");
+    metadata.push_str(&format!("sigrok version={}\n", SIGROK_VERSION));
+    metadata.push('\n');
+    metadata.push_str("[device 1]\n");
+    metadata.push_str("# samples are little-endian 32-bit floats, in volts\n");
+    metadata.push_str(&format!("samplerate={} Hz\n", sample_rate));
+    metadata.push_str(&format!("total analog={}\n", traces.len()));
+    for (index, trace) in traces.iter().enumerate() {
+        metadata.push_str(&format!(
+            "analog{}={}\n",
+            index + 1,
+            channel_name(trace.channel)
+        ));
+    }
+    metadata
+}
+
+/// Writes every trace of `capture` as an analog channel of a sigrok session
+/// (`.sr`): a zip holding the format version, the metadata and one chunk of
+/// samples per channel.
+pub(crate) fn save_sigrok(capture: &Capture, path: &Path) -> anyhow::Result<()> {
+    let waveform = capture.waveform;
+    if waveform.traces.is_empty() {
+        return Err(anyhow!("no channel to export"));
+    }
+    let sample_rate = match waveform.sample_interval {
+        interval if interval > 0.0 => (1.0 / interval).round() as u64,
+        _ => 0,
+    };
+    if sample_rate == 0 {
+        return Err(anyhow!("sample rate below 1 Hz can't be stored"));
+    }
+
+    let file =
+        File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+    let mut zip = ZipWriter::new(file);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut write = |name: &str, data: &[u8]| -> zip::result::ZipResult<()> {
+        zip.start_file(name, options)?;
+        zip.write_all(data)?;
+        Ok(())
+    };
+
+    write("version", SESSION_VERSION.as_bytes())
+        .and_then(|_| write("metadata", metadata(capture, sample_rate).as_bytes()))
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    for (index, trace) in waveform.traces.iter().enumerate() {
+        let samples: Vec<u8> = trace
+            .volts
+            .iter()
+            .flat_map(|volts| (*volts as f32).to_le_bytes())
+            .collect();
+        write(&format!("analog-1-{}-1", index + 1), &samples)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    zip.finish()
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+
+    use zip::ZipArchive;
+
+    use super::*;
+    use crate::export::{test_capture, test_path};
+
+    fn entry(zip: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        zip.by_name(name).unwrap().read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn session_entries() {
+        let (waveform, raw) = test_capture();
+        let capture = Capture {
+            waveform: &waveform,
+            raw: &raw,
+        };
+        let path = test_path("session.sr");
+        save_sigrok(&capture, &path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut zip = ZipArchive::new(Cursor::new(bytes)).unwrap();
+
+        assert_eq!(entry(&mut zip, "version"), b"2");
+
+        let metadata = String::from_utf8(entry(&mut zip, "metadata")).unwrap();
+        let lines: Vec<&str> = metadata.lines().collect();
+        for line in [
+            "[device 1]",
+            "samplerate=1000000 Hz",
+            "total analog=3",
+            "analog1=CH1",
+            "analog2=CH2",
+            "analog3=MATH",
+        ] {
+            assert!(lines.contains(&line), "missing {}", line);
+        }
+
+        for (index, trace) in waveform.traces.iter().enumerate() {
+            let data = entry(&mut zip, &format!("analog-1-{}-1", index + 1));
+            let samples: Vec<f64> = data
+                .chunks(4)
+                .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()) as f64)
+                .collect();
+            assert_eq!(samples, trace.volts);
+        }
+        assert_eq!(zip.len(), 2 + 3);
+    }
+}
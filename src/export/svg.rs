@@ -0,0 +1,533 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::ops::RangeBounds;
+
+use druid::kurbo::{Affine, BezPath, Point, Rect, Shape, Size};
+use druid::piet::{
+    Color, Error, FixedGradient, FontFamily, HitTestPoint, HitTestPosition, Image, ImageFormat,
+    InterpolationMode, IntoBrush, LineMetric, RenderContext, StrokeStyle, Text, TextAttribute,
+    TextLayout, TextLayoutBuilder, TextStorage,
+};
+
+/// Width of a glyph relative to the font size. Layouts are measured with
+/// this instead of real font metrics, which fits the monospace labels of
+/// the displays.
+const GLYPH_WIDTH: f64 = 0.6;
+const LINE_HEIGHT: f64 = 1.2;
+const ASCENT: f64 = 0.95;
+
+/// A [`RenderContext`] that records what is drawn as SVG elements, so the
+/// display renderers can produce vector images. Only solid colors are
+/// supported; images and gradients are not.
+pub(crate) struct SvgRenderContext {
+    size: Size,
+    defs: String,
+    body: String,
+    text: SvgText,
+    state: State,
+    saved: Vec<State>,
+    next_clip: usize,
+}
+
+#[derive(Clone, Default)]
+struct State {
+    transform: Affine,
+    /// Id of the clip path everything is drawn through.
+    clip: Option<String>,
+}
+
+impl SvgRenderContext {
+    pub(crate) fn new(size: Size) -> Self {
+        Self {
+            size,
+            defs: String::new(),
+            body: String::new(),
+            text: SvgText,
+            state: State::default(),
+            saved: Vec::new(),
+            next_clip: 0,
+        }
+    }
+
+    /// The complete SVG document.
+    pub(crate) fn into_svg(self) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+             viewBox=\"0 0 {w} {h}\">\n<defs>\n{}</defs>\n{}</svg>\n",
+            self.defs,
+            self.body,
+            w = self.size.width,
+            h = self.size.height,
+        )
+    }
+
+    fn path(&self, shape: impl Shape) -> String {
+        let path: BezPath = self.state.transform * shape.to_path(0.1);
+        path.to_svg()
+    }
+
+    fn clip_attribute(&self) -> String {
+        match &self.state.clip {
+            Some(id) => format!(" clip-path=\"url(#{})\"", id),
+            None => String::new(),
+        }
+    }
+
+    fn color(&mut self, brush: &impl IntoBrush<Self>, bbox: Rect) -> Color {
+        brush.make_brush(self, || bbox).0.clone()
+    }
+
+    fn draw_path(&mut self, shape: impl Shape, paint: String) {
+        let element = format!(
+            "<path d=\"{}\" {}{}/>\n",
+            self.path(shape),
+            paint,
+            self.clip_attribute()
+        );
+        self.body.push_str(&element);
+    }
+}
+
+/// `fill`/`stroke` attribute value and the matching opacity attribute.
+fn paint(attribute: &str, color: &Color) -> String {
+    let (r, g, b, a) = color.as_rgba8();
+    format!(
+        "{attribute}=\"#{:02x}{:02x}{:02x}\" {attribute}-opacity=\"{}\"",
+        r,
+        g,
+        b,
+        a as f64 / 255.0,
+        attribute = attribute
+    )
+}
+
+fn stroke_paint(color: &Color, width: f64, style: Option<&StrokeStyle>) -> String {
+    let mut paint = format!(
+        "fill=\"none\" {} stroke-width=\"{}\"",
+        paint("stroke", color),
+        width
+    );
+    if let Some(style) = style {
+        if !style.dash_pattern.is_empty() {
+            let dashes: Vec<String> = style.dash_pattern.iter().map(f64::to_string).collect();
+            let _ = write!(
+                paint,
+                " stroke-dasharray=\"{}\" stroke-dashoffset=\"{}\"",
+                dashes.join(" "),
+                style.dash_offset
+            );
+        }
+    }
+    paint
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[derive(Clone)]
+pub(crate) struct SvgBrush(Color);
+
+impl IntoBrush<SvgRenderContext> for SvgBrush {
+    fn make_brush<'a>(
+        &'a self,
+        _piet: &mut SvgRenderContext,
+        _bbox: impl FnOnce() -> Rect,
+    ) -> Cow<'a, SvgBrush> {
+        Cow::Borrowed(self)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct SvgImage;
+
+impl Image for SvgImage {
+    fn size(&self) -> Size {
+        Size::ZERO
+    }
+}
+
+impl RenderContext for SvgRenderContext {
+    type Brush = SvgBrush;
+    type Text = SvgText;
+    type TextLayout = SvgTextLayout;
+    type Image = SvgImage;
+
+    fn status(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn solid_brush(&mut self, color: Color) -> Self::Brush {
+        SvgBrush(color)
+    }
+
+    fn gradient(&mut self, _gradient: impl Into<FixedGradient>) -> Result<Self::Brush, Error> {
+        Err(Error::NotSupported)
+    }
+
+    fn clear(&mut self, region: impl Into<Option<Rect>>, color: Color) {
+        let rect = region.into().unwrap_or_else(|| self.size.to_rect());
+        let element = format!(
+            "<path d=\"{}\" {}/>\n",
+            rect.to_path(0.1).to_svg(),
+            paint("fill", &color)
+        );
+        self.body.push_str(&element);
+    }
+
+    fn stroke(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>, width: f64) {
+        let color = self.color(brush, shape.bounding_box());
+        self.draw_path(shape, stroke_paint(&color, width, None));
+    }
+
+    fn stroke_styled(
+        &mut self,
+        shape: impl Shape,
+        brush: &impl IntoBrush<Self>,
+        width: f64,
+        style: &StrokeStyle,
+    ) {
+        let color = self.color(brush, shape.bounding_box());
+        self.draw_path(shape, stroke_paint(&color, width, Some(style)));
+    }
+
+    fn fill(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>) {
+        let color = self.color(brush, shape.bounding_box());
+        self.draw_path(shape, paint("fill", &color));
+    }
+
+    fn fill_even_odd(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>) {
+        let color = self.color(brush, shape.bounding_box());
+        self.draw_path(
+            shape,
+            format!("{} fill-rule=\"evenodd\"", paint("fill", &color)),
+        );
+    }
+
+    fn clip(&mut self, shape: impl Shape) {
+        let id = format!("clip{}", self.next_clip);
+        self.next_clip += 1;
+        let element = format!(
+            "<clipPath id=\"{}\"{}><path d=\"{}\"/></clipPath>\n",
+            id,
+            self.clip_attribute(),
+            self.path(shape)
+        );
+        self.defs.push_str(&element);
+        self.state.clip = Some(id);
+    }
+
+    fn text(&mut self) -> &mut Self::Text {
+        &mut self.text
+    }
+
+    fn draw_text(&mut self, layout: &Self::TextLayout, pos: impl Into<Point>) {
+        let pos = self.state.transform * pos.into();
+        let element = format!(
+            "<text x=\"{}\" y=\"{}\" font-family=\"{}\" font-size=\"{}\" {} \
+             xml:space=\"preserve\"{}>{}</text>\n",
+            pos.x,
+            pos.y + layout.font_size * ASCENT,
+            layout.font.name(),
+            layout.font_size,
+            paint("fill", &layout.color),
+            self.clip_attribute(),
+            escape(&layout.text)
+        );
+        self.body.push_str(&element);
+    }
+
+    fn save(&mut self) -> Result<(), Error> {
+        self.saved.push(self.state.clone());
+        Ok(())
+    }
+
+    fn restore(&mut self) -> Result<(), Error> {
+        self.state = self.saved.pop().ok_or(Error::StackUnbalance)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn transform(&mut self, transform: Affine) {
+        self.state.transform *= transform;
+    }
+
+    fn make_image(
+        &mut self,
+        _width: usize,
+        _height: usize,
+        _buf: &[u8],
+        _format: ImageFormat,
+    ) -> Result<Self::Image, Error> {
+        Err(Error::NotSupported)
+    }
+
+    fn draw_image(
+        &mut self,
+        _image: &Self::Image,
+        _dst_rect: impl Into<Rect>,
+        _interp: InterpolationMode,
+    ) {
+    }
+
+    fn draw_image_area(
+        &mut self,
+        _image: &Self::Image,
+        _src_rect: impl Into<Rect>,
+        _dst_rect: impl Into<Rect>,
+        _interp: InterpolationMode,
+    ) {
+    }
+
+    fn capture_image_area(&mut self, _src_rect: impl Into<Rect>) -> Result<Self::Image, Error> {
+        Err(Error::NotSupported)
+    }
+
+    fn blurred_rect(&mut self, rect: Rect, _blur_radius: f64, brush: &impl IntoBrush<Self>) {
+        self.fill(rect, brush);
+    }
+
+    fn current_transform(&self) -> Affine {
+        self.state.transform
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct SvgText;
+
+impl Text for SvgText {
+    type TextLayoutBuilder = SvgTextLayoutBuilder;
+    type TextLayout = SvgTextLayout;
+
+    fn font_family(&mut self, family_name: &str) -> Option<FontFamily> {
+        Some(FontFamily::new_unchecked(family_name))
+    }
+
+    fn load_font(&mut self, _data: &[u8]) -> Result<FontFamily, Error> {
+        Err(Error::NotSupported)
+    }
+
+    fn new_text_layout(&mut self, text: impl TextStorage) -> Self::TextLayoutBuilder {
+        SvgTextLayoutBuilder(SvgTextLayout {
+            text: text.as_str().to_string(),
+            font: FontFamily::SANS_SERIF,
+            font_size: 12.0,
+            color: Color::BLACK,
+        })
+    }
+}
+
+pub(crate) struct SvgTextLayoutBuilder(SvgTextLayout);
+
+impl TextLayoutBuilder for SvgTextLayoutBuilder {
+    type Out = SvgTextLayout;
+
+    fn max_width(self, _width: f64) -> Self {
+        self
+    }
+
+    fn alignment(self, _alignment: druid::piet::TextAlignment) -> Self {
+        self
+    }
+
+    fn default_attribute(mut self, attribute: impl Into<TextAttribute>) -> Self {
+        match attribute.into() {
+            TextAttribute::FontFamily(font) => self.0.font = font,
+            TextAttribute::FontSize(size) => self.0.font_size = size,
+            TextAttribute::TextColor(color) => self.0.color = color,
+            _ => {}
+        }
+        self
+    }
+
+    fn range_attribute(
+        self,
+        _range: impl RangeBounds<usize>,
+        _attribute: impl Into<TextAttribute>,
+    ) -> Self {
+        self
+    }
+
+    fn build(self) -> Result<Self::Out, Error> {
+        Ok(self.0)
+    }
+}
+
+/// A single line of text in one font, size and color.
+#[derive(Clone)]
+pub(crate) struct SvgTextLayout {
+    text: String,
+    font: FontFamily,
+    font_size: f64,
+    color: Color,
+}
+
+impl SvgTextLayout {
+    fn char_width(&self) -> f64 {
+        self.font_size * GLYPH_WIDTH
+    }
+}
+
+impl TextLayout for SvgTextLayout {
+    fn size(&self) -> Size {
+        Size::new(
+            self.text.chars().count() as f64 * self.char_width(),
+            self.font_size * LINE_HEIGHT,
+        )
+    }
+
+    fn trailing_whitespace_width(&self) -> f64 {
+        let trailing = self.text.len() - self.text.trim_end().len();
+        trailing as f64 * self.char_width()
+    }
+
+    fn image_bounds(&self) -> Rect {
+        self.size().to_rect()
+    }
+
+    fn text(&self) -> &str {
+        &self.text
+    }
+
+    fn line_text(&self, line_number: usize) -> Option<&str> {
+        match line_number {
+            0 => Some(&self.text),
+            _ => None,
+        }
+    }
+
+    fn line_metric(&self, line_number: usize) -> Option<LineMetric> {
+        match line_number {
+            0 => Some(LineMetric {
+                start_offset: 0,
+                end_offset: self.text.len(),
+                trailing_whitespace: self.text.len() - self.text.trim_end().len(),
+                baseline: self.font_size * ASCENT,
+                height: self.font_size * LINE_HEIGHT,
+                y_offset: 0.0,
+            }),
+            _ => None,
+        }
+    }
+
+    fn line_count(&self) -> usize {
+        1
+    }
+
+    fn hit_test_point(&self, point: Point) -> HitTestPoint {
+        let column = (point.x / self.char_width()).round().max(0.0) as usize;
+        let idx = self
+            .text
+            .char_indices()
+            .nth(column)
+            .map(|(idx, _)| idx)
+            .unwrap_or(self.text.len());
+        let mut hit = HitTestPoint::default();
+        hit.idx = idx;
+        hit.is_inside = self.size().to_rect().contains(point);
+        hit
+    }
+
+    fn hit_test_text_position(&self, idx: usize) -> HitTestPosition {
+        let column = self.text[..idx.min(self.text.len())].chars().count();
+        let mut position = HitTestPosition::default();
+        position.point = Point::new(column as f64 * self.char_width(), self.font_size * ASCENT);
+        position
+    }
+}
+
+/// Panics unless `svg` is a single `<svg>` element whose tags all close in
+/// order and whose text and attributes are escaped.
+#[cfg(test)]
+pub(crate) fn assert_well_formed(svg: &str) {
+    let body = svg
+        .strip_prefix("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n")
+        .expect("missing XML declaration");
+    let mut open: Vec<&str> = Vec::new();
+    let mut roots = 0;
+    let mut rest = body;
+    while let Some(start) = rest.find('<') {
+        assert_escaped(&rest[..start]);
+        let end = start + rest[start..].find('>').expect("unterminated tag");
+        let tag = &rest[start + 1..end];
+        assert_eq!(
+            tag.matches('"').count() % 2,
+            0,
+            "unbalanced quotes: {}",
+            tag
+        );
+        assert!(!tag.contains('<'), "'<' inside tag: {}", tag);
+        if open.is_empty() {
+            roots += 1;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            assert_eq!(open.pop(), Some(name), "mismatched </{}>", name);
+        } else if !tag.ends_with('/') {
+            open.push(tag.split_whitespace().next().expect("empty tag"));
+        }
+        rest = &rest[end + 1..];
+    }
+    assert_escaped(rest);
+    assert!(open.is_empty(), "unclosed tags: {:?}", open);
+    assert_eq!(roots, 1);
+    assert!(body.starts_with("<svg "));
+}
+
+#[cfg(test)]
+fn assert_escaped(text: &str) {
+    assert!(!text.contains('>'), "unescaped '>' in {:?}", text);
+    for (index, _) in text.match_indices('&') {
+        let entity = &text[index..];
+        assert!(
+            ["&amp;", "&lt;", "&gt;", "&quot;"]
+                .iter()
+                .any(|known| entity.starts_with(known)),
+            "unescaped '&' in {:?}",
+            text
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use druid::kurbo::Line;
+
+    use super::*;
+
+    #[test]
+    fn text_is_escaped() {
+        let mut rc = SvgRenderContext::new(Size::new(100.0, 50.0));
+        let layout = rc
+            .text()
+            .new_text_layout("a < b & \"c\" > d")
+            .build()
+            .unwrap();
+        rc.draw_text(&layout, (10.0, 20.0));
+        let svg = rc.into_svg();
+        assert_well_formed(&svg);
+        assert!(svg.contains(">a &lt; b &amp; &quot;c&quot; &gt; d</text>"));
+    }
+
+    #[test]
+    fn clips_are_defined_and_restored() {
+        let mut rc = SvgRenderContext::new(Size::new(100.0, 50.0));
+        let brush = rc.solid_brush(Color::WHITE);
+        rc.save().unwrap();
+        rc.clip(Rect::new(0.0, 0.0, 50.0, 50.0));
+        rc.stroke(Line::new((0.0, 0.0), (100.0, 50.0)), &brush, 1.0);
+        rc.restore().unwrap();
+        rc.fill(Rect::new(60.0, 0.0, 70.0, 10.0), &brush);
+        assert!(rc.restore().is_err());
+        let svg = rc.into_svg();
+        assert_well_formed(&svg);
+        assert!(svg.contains("<defs>\n<clipPath id=\"clip0\"><path "));
+        assert_eq!(svg.matches("clip-path=\"url(#clip0)\"").count(), 1);
+    }
+}
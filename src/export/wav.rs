@@ -0,0 +1,153 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+use druid::{Data, FileSpec};
+use hound::{SampleFormat, WavSpec, WavWriter};
+
+use crate::export::Capture;
+
+pub(crate) const WAV_FILE: FileSpec = FileSpec::new("WAV audio", &["wav"]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum WavFormat {
+    Int16,
+    Float32,
+}
+
+impl WavFormat {
+    pub(crate) fn my_options() -> Vec<(&'static str, WavFormat)> {
+        vec![
+            ("16-bit", WavFormat::Int16),
+            ("32-bit float", WavFormat::Float32),
+        ]
+    }
+}
+
+/// Sample rate of `capture`, from the sample interval of its waveform.
+fn sample_rate(capture: &Capture) -> anyhow::Result<u32> {
+    let sample_interval = capture.waveform.sample_interval;
+    if !(sample_interval.is_finite() && sample_interval > 0.0) {
+        return Err(anyhow!("sample rate of the capture is unknown"));
+    }
+    let rate = (1.0 / sample_interval).round();
+    if rate < 1.0 || rate > u32::MAX as f64 {
+        return Err(anyhow!("sample rate of {} Hz doesn't fit a WAV file", rate));
+    }
+    Ok(rate as u32)
+}
+
+/// Writes every trace of `capture` as one audio channel. Normalized, each
+/// trace is scaled so its peak reaches full scale; otherwise one volt is
+/// full scale, and 16-bit samples beyond it are clipped.
+pub(crate) fn save_wav(
+    capture: &Capture,
+    format: WavFormat,
+    normalized: bool,
+    path: &Path,
+) -> anyhow::Result<()> {
+    let traces = &capture.waveform.traces;
+    if traces.is_empty() {
+        return Err(anyhow!("no channel to export"));
+    }
+    let spec = WavSpec {
+        channels: traces.len() as u16,
+        sample_rate: sample_rate(capture)?,
+        bits_per_sample: match format {
+            WavFormat::Int16 => 16,
+            WavFormat::Float32 => 32,
+        },
+        sample_format: match format {
+            WavFormat::Int16 => SampleFormat::Int,
+            WavFormat::Float32 => SampleFormat::Float,
+        },
+    };
+
+    let gains: Vec<f64> = traces
+        .iter()
+        .map(|trace| {
+            let peak = trace.volts.iter().fold(0.0f64, |peak, v| peak.max(v.abs()));
+            match normalized && peak > 0.0 {
+                true => 1.0 / peak,
+                false => 1.0,
+            }
+        })
+        .collect();
+
+    let mut writer = WavWriter::create(path, spec)
+        .with_context(|| format!("failed to create {}", path.display()))?;
+    for index in 0..capture.waveform.num_samples {
+        for (trace, gain) in traces.iter().zip(&gains) {
+            let value = trace.volts.get(index).copied().unwrap_or(0.0) * gain;
+            match format {
+                WavFormat::Int16 => {
+                    let sample = (value.clamp(-1.0, 1.0) * i16::MAX as f64).round() as i16;
+                    writer.write_sample(sample)
+                }
+                WavFormat::Float32 => writer.write_sample(value as f32),
+            }
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        }
+    }
+    writer
+        .finalize()
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use hound::WavReader;
+
+    use super::*;
+    use crate::export::{test_capture, test_path};
+
+    fn write(format: WavFormat, normalized: bool, name: &str) -> WavReader<Cursor<Vec<u8>>> {
+        let (waveform, raw) = test_capture();
+        let capture = Capture {
+            waveform: &waveform,
+            raw: &raw,
+        };
+        let path = test_path(name);
+        save_wav(&capture, format, normalized, &path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        WavReader::new(Cursor::new(bytes)).unwrap()
+    }
+
+    #[test]
+    fn sample_rate_from_sample_interval() {
+        let mut reader = write(WavFormat::Int16, false, "rate.wav");
+        let spec = reader.spec();
+        assert_eq!(spec.sample_rate, 1_000_000);
+        assert_eq!(spec.channels, 3);
+        assert_eq!(spec.bits_per_sample, 16);
+        let samples: Vec<i16> = reader.samples::<i16>().map(Result::unwrap).collect();
+        // Interleaved CH1, CH2, MATH; one volt is full scale.
+        assert_eq!(&samples[..3], &[16384, 0, 16384]);
+        assert_eq!(samples[6], i16::MAX);
+        assert_eq!(samples[7], -i16::MAX);
+    }
+
+    #[test]
+    fn normalized_float_samples() {
+        let mut reader = write(WavFormat::Float32, true, "normalized.wav");
+        assert_eq!(reader.spec().sample_format, SampleFormat::Float);
+        let samples: Vec<f32> = reader.samples::<f32>().map(Result::unwrap).collect();
+        assert_eq!(samples.len(), 3 * 3);
+        // The math trace peaks at 0.5 V, which becomes full scale.
+        assert_eq!(samples[2], 1.0);
+        assert_eq!(samples[5], -0.25);
+    }
+
+    #[test]
+    fn unknown_sample_rate_is_an_error() {
+        let (mut waveform, raw) = test_capture();
+        waveform.sample_interval = 0.0;
+        let capture = Capture {
+            waveform: &waveform,
+            raw: &raw,
+        };
+        assert!(sample_rate(&capture).is_err());
+    }
+}
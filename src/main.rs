@@ -1,40 +1,142 @@
 #![windows_subsystem = "windows"]
 
 use std::collections::VecDeque;
-use std::sync::mpsc::{Receiver, Sender};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::bail;
-use druid::im::Vector;
-use druid::widget::{Button, CrossAxisAlignment, Flex, Label, MainAxisAlignment, Switch};
-use druid::{AppLauncher, Data, UnitPoint, Widget, WidgetExt, WindowDesc};
+use druid::im::{HashMap, HashSet, Vector};
+use druid::widget::{
+    Button, Checkbox, CrossAxisAlignment, Flex, Label, LineBreaking, MainAxisAlignment, Switch,
+    TextBox, ViewSwitcher,
+};
+use druid::{
+    commands, AppLauncher, Application, Color, Data, FileDialogOptions, FontDescriptor, FontFamily,
+    UnitPoint, Widget, WidgetExt, WindowDesc,
+};
 use druid_widget_nursery::{DropdownSelect, WidgetExt as WidgetExtNursery};
 use hanteker_lib::device::cfg::*;
 use log::{debug, error, info, trace};
 use pretty_env_logger::formatted_builder;
 
-use crate::comm::{DevCommand, DevCommandResult, TextMessage};
-use crate::dev::handler_thread;
+use crate::comm::{
+    Control, DevCommand, DevCommandResult, DevRequest, DevResponse, PendingRequest, RequestId,
+    TextMessage,
+};
+use crate::cursors::Cursors;
+use crate::decode::spi::{BitOrder, DataLine};
+use crate::decode::uart::{Parity, StopBits};
+use crate::decode::{Decoded, DecoderConfig, Protocol};
+use crate::delegate::{
+    Delegate, EXPORT_CSV, EXPORT_DECODED, EXPORT_PNG, EXPORT_SIGROK, EXPORT_SVG, EXPORT_WAV,
+    LOAD_SETUP, SAVE_SETUP,
+};
+use crate::dev::{handler_thread, Backend};
+use crate::export::csv::{save_csv, save_decoded, CSV_FILE};
+use crate::export::image::{save_png, save_svg, PNG_FILE, SVG_FILE};
+use crate::export::sigrok::{save_sigrok, SIGROK_FILE};
+use crate::export::wav::{save_wav, WavFormat, WAV_FILE};
+use crate::export::{Capture, RawCapture};
+use crate::math::{channel_name, MathConfig, MathOperation, MATH_CHANNEL};
+use crate::measurements::{measure, Measurement, Statistics};
+use crate::reference::{
+    reference_name, CaptureSettings, ChannelSettings, Reference, ReferenceSlot, NUM_REFERENCES,
+};
+use crate::setup::{GuiSetup, Setup, SETUP_FILE};
+use crate::spectrum::{Spectra, SpectrumScale, Window};
+use crate::trigger::{find_rising_edge, SingleShot, TRIGGER_HYSTERESIS_DIVS};
+use crate::units::{format_si, probe_factor, volts_per_div};
+use crate::view::{DisplayMode, View};
+use crate::waveform::{Trace, Waveform};
+use crate::widget::acquisition::Acquisition;
 use crate::widget::f32_formatter::float_text_unrestricted;
-use crate::widget::label::{label, label_c, label_ct};
+use crate::widget::label::{label, label_c, label_ct, label_pending};
 use crate::widget::scope::ScopeGraph;
+use crate::widget::spectrum::SpectrumGraph;
 use crate::widget::usize_formatter::usize_text_unrestricted;
+use crate::widget::xy::XyGraph;
 use crate::widget::{lens_of, t, tt};
 
 mod comm;
+mod cursors;
+mod decode;
+mod delegate;
 mod dev;
+mod device;
+mod export;
+mod math;
+mod measurements;
+mod reference;
+mod setup;
+mod sim;
+mod spectrum;
+mod sync;
+mod trigger;
+mod units;
+mod view;
+mod waveform;
 mod widget;
 
+/// Lower bound for the acquisition interval, in milliseconds.
+const MIN_ACQUISITION_INTERVAL: usize = 10;
+const MAX_XY_PERSISTENCE: usize = 64;
+/// Bounds of exported images, in pixels.
+const MIN_IMAGE_SIZE: usize = 16;
+const MAX_IMAGE_SIZE: usize = 8192;
+/// How often the settings are read back from the device while connected.
+const SYNC_INTERVAL: Duration = Duration::from_secs(2);
+
 #[derive(Clone)]
 pub(crate) struct HantekState {
     messages: VecDeque<TextMessage>,
     connected: bool,
     initializing: bool,
     cfg: HantekConfig,
-    tx: Sender<DevCommand>,
-    rx: Arc<Receiver<Result<DevCommandResult, String>>>,
-    capture: Option<Vec<u8>>,
+    tx: Sender<DevRequest>,
+    next_request_id: RequestId,
+    pending: HashMap<RequestId, PendingRequest>,
+    stale: HashSet<Control>,
+    capture: Option<Arc<Waveform>>,
+    capture_raw: Option<Arc<RawCapture>>,
+    capture_channels: Vec<usize>,
+    capture_cfg: HantekConfig,
     num_captures: usize,
+    acquiring: bool,
+    acquisition_interval: usize,
+    single: Option<SingleShot>,
+    single_timeout: f32,
+    synced_at: Option<Instant>,
+    /// The device can't report its settings, so they aren't polled.
+    sync_unsupported: bool,
+    /// Setup of the last session, applied on connect.
+    session: Option<Setup>,
+    factory_defaults: bool,
+    view: View,
+    cursors: Cursors,
+    measurements: HashSet<Measurement>,
+    measurement_stats: HashMap<(usize, Measurement), Statistics>,
+    display_mode: DisplayMode,
+    spectra: Option<Arc<Spectra>>,
+    spectrum_window: Window,
+    spectrum_scale: SpectrumScale,
+    spectrum_averages: usize,
+    xy_history: Vector<Arc<Waveform>>,
+    xy_persistence: usize,
+    math: MathConfig,
+    math_error: Option<String>,
+    decoder: DecoderConfig,
+    decoded: Option<Arc<Decoded>>,
+    decode_error: Option<String>,
+    references: Vector<ReferenceSlot>,
+    export_raw: bool,
+    wav_format: WavFormat,
+    wav_normalized: bool,
+    image_width: usize,
+    image_height: usize,
+    /// Where `--save-image` saves the first capture before quitting.
+    save_image: Option<PathBuf>,
 }
 
 impl Data for HantekState {
@@ -43,20 +145,92 @@ impl Data for HantekState {
             && self.connected == other.connected
             && self.initializing == other.initializing
             && self.cfg.same(&other.cfg)
+            && self.pending.same(&other.pending)
+            && self.capture.same(&other.capture)
+            && self.acquiring == other.acquiring
+            && self.acquisition_interval == other.acquisition_interval
+            && self.single.as_ref().map(|single| single.armed_at)
+                == other.single.as_ref().map(|single| single.armed_at)
+            && self.single_timeout == other.single_timeout
+            && self.factory_defaults == other.factory_defaults
+            && self.view == other.view
+            && self.cursors == other.cursors
+            && self.measurements == other.measurements
+            && self.measurement_stats == other.measurement_stats
+            && self.display_mode == other.display_mode
+            && self.spectra.same(&other.spectra)
+            && self.spectrum_window == other.spectrum_window
+            && self.spectrum_scale == other.spectrum_scale
+            && self.spectrum_averages == other.spectrum_averages
+            && self.xy_history.same(&other.xy_history)
+            && self.xy_persistence == other.xy_persistence
+            && self.math == other.math
+            && self.math_error == other.math_error
+            && self.decoder == other.decoder
+            && self.decoded.same(&other.decoded)
+            && self.decode_error == other.decode_error
+            && self.references.same(&other.references)
+            && self.export_raw == other.export_raw
+            && self.wav_format == other.wav_format
+            && self.wav_normalized == other.wav_normalized
+            && self.image_width == other.image_width
+            && self.image_height == other.image_height
     }
 }
 
 impl HantekState {
-    fn new(rx: Sender<DevCommand>, tx: Arc<Receiver<Result<DevCommandResult, String>>>) -> Self {
+    fn new(tx: Sender<DevRequest>) -> Self {
+        let gui = GuiSetup::default();
         Self {
             cfg: HantekConfig::new(2),
             messages: VecDeque::new(),
             connected: false,
             initializing: true,
-            tx: rx,
-            rx: tx,
+            tx,
+            next_request_id: 0,
+            pending: HashMap::new(),
+            stale: HashSet::new(),
             capture: None,
-            num_captures: 1024,
+            capture_raw: None,
+            capture_channels: Vec::new(),
+            capture_cfg: HantekConfig::new(2),
+            num_captures: gui.num_captures,
+            acquiring: false,
+            acquisition_interval: gui.acquisition_interval,
+            single: None,
+            single_timeout: gui.single_timeout,
+            synced_at: None,
+            sync_unsupported: false,
+            session: None,
+            factory_defaults: false,
+            view: View::default(),
+            cursors: Cursors::default(),
+            measurements: HashSet::from(vec![
+                Measurement::Vpp,
+                Measurement::Vrms,
+                Measurement::Frequency,
+                Measurement::DutyCycle,
+            ]),
+            measurement_stats: HashMap::new(),
+            display_mode: DisplayMode::Time,
+            spectra: None,
+            spectrum_window: Window::Hann,
+            spectrum_scale: SpectrumScale::Dbv,
+            spectrum_averages: 1,
+            xy_history: Vector::new(),
+            xy_persistence: 1,
+            math: MathConfig::default(),
+            math_error: None,
+            decoder: DecoderConfig::default(),
+            decoded: None,
+            decode_error: None,
+            references: Vector::from(vec![ReferenceSlot::default(); NUM_REFERENCES]),
+            export_raw: false,
+            wav_format: WavFormat::Int16,
+            wav_normalized: true,
+            image_width: 1920,
+            image_height: 1080,
+            save_image: None,
         }
     }
 
@@ -95,10 +269,125 @@ impl HantekState {
 
     // ------------
 
+    /// Queues `cmd` for the device thread, the outcome arrives later through
+    /// [`HantekState::on_response`]. While a request for the same control is
+    /// in flight, the new value is only remembered and sent once the
+    /// in-flight request completes.
+    fn send(&mut self, cmd: DevCommand, done: impl Into<String>) {
+        let control = cmd.control();
+        if control.is_coalesced() && self.is_pending(control) {
+            trace!("UI => {:?}, DEFERRED/PENDING", control);
+            self.stale.insert(control);
+            return;
+        }
+
+        let id = self.next_request_id;
+        self.next_request_id += 1;
+        self.pending.insert(
+            id,
+            PendingRequest {
+                control,
+                done: done.into(),
+            },
+        );
+        self.tx.send(DevRequest { id, cmd }).unwrap();
+    }
+
+    fn is_pending(&self, control: Control) -> bool {
+        self.pending
+            .values()
+            .any(|pending| pending.control == control)
+    }
+
+    fn num_pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    fn on_response(&mut self, response: DevResponse) {
+        let pending = match self.pending.remove(&response.id) {
+            Some(pending) => pending,
+            None => {
+                error!("UI => response to unknown request, id={}", response.id);
+                return;
+            }
+        };
+        trace!("UI <= {:?}, id={}", pending.control, response.id);
+
+        let failed = response.result.is_err();
+        match response.result {
+            Ok(DevCommandResult::CaptureResult(capture)) => self.on_captured(capture),
+            Ok(DevCommandResult::Settings(settings)) => self.on_settings(*settings),
+            Ok(DevCommandResult::SettingsUnsupported) => self.on_settings_unsupported(),
+            Ok(DevCommandResult::EmptyResult) => {
+                self.message_info(pending.done);
+                match pending.control {
+                    Control::Connect => self.on_connected(),
+                    Control::Disconnect => self.on_disconnected(),
+                    _ => {}
+                }
+            }
+            Err(error) => {
+                if pending.control == Control::Capture && self.acquiring {
+                    self.acquiring = false;
+                    self.message_error(format!("acquisition stopped: {}", error));
+                } else if pending.control == Control::Capture && self.is_single_armed() {
+                    self.end_single();
+                    self.message_error(format!("single shot cancelled: {}", error));
+                } else {
+                    self.message_error(error);
+                }
+            }
+        }
+
+        if self.stale.remove(&pending.control).is_some() {
+            self.resend(pending.control);
+        }
+        self.continue_save_image(failed);
+    }
+
+    fn resend(&mut self, control: Control) {
+        let _err = match control {
+            Control::Connect | Control::Disconnect | Control::Capture | Control::ReadSettings => {
+                Ok(())
+            }
+            Control::DeviceFunction => self.send_device_function(),
+            Control::Running => self.send_running(),
+            Control::ChannelEnable(channel) => self.send_channel_enable(channel),
+            Control::Coupling(channel) => self.send_coupling(channel),
+            Control::Probe(channel) => self.send_probe(channel),
+            Control::Scale(channel) => self.send_scale(channel),
+            Control::Offset(channel) => self.send_offset(channel),
+            Control::BwLimit(channel) => self.send_bw_limit(channel),
+            Control::TimeScale => self.send_time_scale(),
+            Control::TimeOffset => self.send_time_offset(),
+            Control::TriggerSource => self.send_trigger_source(),
+            Control::TriggerMode => self.send_trigger_mode(),
+            Control::TriggerLevel => self.send_trigger_level(),
+            Control::AwgRunning => self.send_awg_running(),
+            Control::AwgFrequency => self.send_awg_frequency(),
+            Control::AwgAmplitude => self.send_awg_amplitude(),
+            Control::AwgType => self.send_awg_type(),
+            Control::AwgOffset => self.send_awg_offset(),
+            Control::AwgDutySquare => self.send_awg_duty_square(),
+            Control::AwgDutyRamp => self.send_awg_duty_ramp(),
+            Control::AwgDutyTrap => self.send_awg_duty_trap(),
+        };
+    }
+
+    // ------------
+
     fn is_connected(&self) -> bool {
         self.connected
     }
 
+    fn is_connect_disabled(&self) -> bool {
+        self.is_connected() || self.is_pending(Control::Connect)
+    }
+
+    fn is_disconnect_disabled(&self) -> bool {
+        !self.is_connected() || self.is_pending(Control::Disconnect)
+    }
+
     fn disconnect(&mut self) {
         let my_name = "disconnect()";
         trace!("UI => {}", my_name);
@@ -106,15 +395,19 @@ impl HantekState {
         self.initializing = false;
 
         self.message_info("disconnecting");
-        self.tx.send(DevCommand::Disconnect).unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.message_info("disconnected");
-                self.connected = false;
-                self.messages.clear();
-            }
-            Err(error) => self.message_error(error),
-        };
+        self.send(DevCommand::Disconnect, "disconnected");
+    }
+
+    fn on_disconnected(&mut self) {
+        if let Some(single) = self.single.take() {
+            self.set_trigger_mode(single.restore_mode);
+        }
+        self.save_session();
+        self.connected = false;
+        self.acquiring = false;
+        self.synced_at = None;
+        self.sync_unsupported = false;
+        self.messages.clear();
     }
 
     fn connect(&mut self) {
@@ -122,1126 +415,1426 @@ impl HantekState {
         trace!("UI => {}", my_name);
 
         self.message_info("connecting");
-        self.tx.send(DevCommand::Connect).unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.connected = true;
-                self.initializing = false;
-                match self.try_connect() {
-                    Ok(_) => {
-                        self.message_info("connected");
-                    }
-                    Err(error) => {
-                        self.connected = true;
-                        self.initializing = false;
-                        self.message_error(error.to_string());
-                    }
-                }
-            }
-            Err(error) => self.message_error(error),
-        };
+        self.send(DevCommand::Connect, "connected");
+    }
+
+    fn on_connected(&mut self) {
+        self.connected = true;
+        self.initializing = false;
+        if let Err(error) = self.try_connect() {
+            self.message_error(error.to_string());
+        }
+        self.read_settings();
     }
 
     fn try_connect(&mut self) -> anyhow::Result<()> {
+        self.set_defaults();
+        match self.session.clone() {
+            Some(_) if self.factory_defaults => {
+                self.message_info("using factory defaults");
+            }
+            Some(session) => match session.apply(&self.cfg) {
+                Ok(cfg) => {
+                    self.cfg = cfg;
+                    self.message_info("restoring last session");
+                }
+                Err(err) => {
+                    self.message_error(format!("failed to restore last session: {:#}", err))
+                }
+            },
+            None => {}
+        }
+        self.send_setup()
+    }
+
+    /// Factory defaults of every device setting.
+    fn set_defaults(&mut self) {
         self.set_running(true);
-        self.send_running()?;
 
         self.set_coupling(1, Coupling::DC);
-        self.send_coupling(1)?;
         self.set_coupling(2, Coupling::DC);
-        self.send_coupling(2)?;
-
         self.set_probe(1, Probe::X1);
-        self.send_probe(1)?;
         self.set_probe(2, Probe::X1);
-        self.send_probe(2)?;
-
         self.set_scale0(1, Scale::v10);
-        self.send_scale(1)?;
         self.set_scale0(2, Scale::v10);
-        self.send_scale(2)?;
-
         self.set_offset(1, 0.0);
-        self.send_offset(1)?;
         self.set_offset(2, 0.0);
-        self.send_offset(2)?;
-
         self.set_enabled_channel(1, true);
-        self.send_channel_enable(1)?;
         self.set_enabled_channel(2, false);
-        self.send_channel_enable(2)?;
-
         self.set_bw_limit(1, false);
-        self.send_bw_limit(1)?;
         self.set_bw_limit(2, false);
-        self.send_bw_limit(2)?;
 
         self.set_time_scale(TimeScale::ms1);
-        self.send_time_scale()?;
         self.set_time_offset(0.0);
-        self.send_time_offset()?;
         self.set_trigger_source(1);
-        self.send_trigger_source()?;
         self.set_trigger_mode(TriggerMode::Auto);
-        self.send_trigger_mode()?;
         self.set_trigger_level(0.0);
-        self.send_trigger_level()?;
 
         self.set_awg_running(RunningStatus::Stop);
-        self.send_awg_running()?;
         self.set_awg_frequency(1.0);
-        self.send_awg_frequency()?;
         self.set_awg_amplitude(1.0);
-        self.send_awg_amplitude()?;
         self.set_awg_type(AwgType::Square);
-        self.send_awg_type()?;
         self.set_awg_offset(0.0);
-        self.send_awg_offset()?;
         self.set_awg_duty_square(0.0);
-        self.send_awg_duty_square()?;
         self.set_awg_duty_ramp(0.0);
-        self.send_awg_duty_ramp()?;
         self.set_awg_duty_trap_low(0.0);
         self.set_awg_duty_trap_high(0.0);
         self.set_awg_duty_trap_rise(0.0);
-        self.send_awg_duty_trap()?;
-
-        Ok(())
     }
 
-    fn capture(&mut self) {
-        let mut channels = Vec::with_capacity(2);
-        if self.cfg.enabled_channels[&1].unwrap() {
-            channels.push(1);
+    /// Sends the whole configuration to the device. The device function
+    /// goes first, probes before the scales they multiply, the timebase
+    /// before the trigger, and the AWG output is only switched after its
+    /// waveform is set up. Everything but the device function has to be
+    /// known, as it is after [`HantekState::try_connect`].
+    fn send_setup(&mut self) -> anyhow::Result<()> {
+        if self.cfg.device_function.is_some() {
+            self.send_device_function()?;
         }
-        if self.cfg.enabled_channels[&2].unwrap() {
-            channels.push(2);
+        self.send_running()?;
+
+        for channel in [1, 2] {
+            self.send_coupling(channel)?;
+        }
+        for channel in [1, 2] {
+            self.send_probe(channel)?;
+        }
+        for channel in [1, 2] {
+            self.send_scale(channel)?;
+        }
+        for channel in [1, 2] {
+            self.send_offset(channel)?;
+        }
+        for channel in [1, 2] {
+            self.send_channel_enable(channel)?;
+        }
+        for channel in [1, 2] {
+            self.send_bw_limit(channel)?;
         }
 
-        self.tx
-            .send(DevCommand::Capture(channels, self.num_captures))
-            .unwrap();
+        self.send_time_scale()?;
+        self.send_time_offset()?;
+        self.send_trigger_source()?;
+        self.send_trigger_mode()?;
+        self.send_trigger_level()?;
 
-        match self.rx.recv().unwrap() {
-            Ok(dev_command_result) => match dev_command_result {
-                DevCommandResult::EmptyResult => panic!("unexpected result"),
-                DevCommandResult::CaptureResult(capture) => {
-                    // Subtle bug: without putting something into messages,
-                    // drawing area widget won't be updated.
-                    self.message_info(format!("captured number of bytes: {}", capture.len()));
-                    self.capture = Some(capture);
-                }
-            },
-            Err(error) => {
-                self.message_error(error);
-            }
+        self.send_awg_type()?;
+        self.send_awg_frequency()?;
+        self.send_awg_amplitude()?;
+        self.send_awg_offset()?;
+        self.send_awg_duty_square()?;
+        self.send_awg_duty_ramp()?;
+        self.send_awg_duty_trap()?;
+        self.send_awg_running()?;
+
+        Ok(())
+    }
+
+    fn save_setup(&mut self, path: &Path) {
+        debug!("UI => save_setup({})", path.display());
+        match Setup::new(&self.cfg, self.gui_setup()).save(path) {
+            Ok(()) => self.message_info(format!("setup saved to {}", path.display())),
+            Err(err) => self.message_error(format!("{:#}", err)),
         }
     }
 
-    // ------------
+    /// Applies the setup in `path` and replays it to the device. Nothing is
+    /// changed if any setting in the file is invalid.
+    fn load_setup(&mut self, path: &Path) {
+        debug!("UI => load_setup({})", path.display());
+        let loaded = Setup::load(path).and_then(|setup| Ok((setup.apply(&self.cfg)?, setup.gui)));
+        let (cfg, gui) = match loaded {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                self.message_error(format!("{:#}", err));
+                return;
+            }
+        };
 
-    fn on_device_function(&mut self) {
-        let _err = self.send_device_function();
+        self.cfg = cfg;
+        if let Some(gui) = gui {
+            self.apply_gui_setup(&gui);
+        }
+        self.message_info(format!("setup loaded from {}", path.display()));
+        if let Err(err) = self.send_setup() {
+            self.message_error(err.to_string());
+        }
     }
 
-    fn send_device_function(&mut self) -> anyhow::Result<()> {
-        let my_name = "send_device_function()".to_string();
-        if self.initializing {
-            trace!("UI => {}, SKIPPED/INIT", my_name);
-            return Ok(());
-        } else if !self.is_connected() {
-            self.message_error("not connected");
-            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
-            bail!("not connected");
-        } else {
-            trace!("UI => {}", my_name);
+    fn gui_setup(&self) -> GuiSetup {
+        GuiSetup {
+            num_captures: self.num_captures,
+            acquisition_interval: self.acquisition_interval,
+            single_timeout: self.single_timeout,
         }
+    }
 
-        let device_function = self.cfg.device_function.as_ref().unwrap().clone();
-        debug!("UI => {}::{}", my_name, device_function.my_to_string());
-
-        self.message_info(format!(
-            "setting device_function={}",
-            device_function.my_to_string(),
-        ));
+    fn apply_gui_setup(&mut self, gui: &GuiSetup) {
+        self.set_num_captures(gui.num_captures);
+        self.set_acquisition_interval(gui.acquisition_interval);
+        self.set_single_timeout(gui.single_timeout);
+    }
 
-        self.tx
-            .send(DevCommand::DeviceFunction(device_function))
-            .unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.message_info("device function set");
-                Ok(())
-            }
-            Err(error) => {
-                self.message_error(error.clone());
-                bail!(error);
+    /// Restores the GUI-only settings of the last session, the device
+    /// settings follow on connect.
+    fn load_session(&mut self) {
+        match Setup::load_session() {
+            Ok(Some(session)) => {
+                if let Some(gui) = &session.gui {
+                    self.apply_gui_setup(gui);
+                }
+                self.session = Some(session);
             }
+            Ok(None) => {}
+            Err(err) => self.message_error(format!("failed to load last session: {:#}", err)),
         }
     }
 
-    fn is_device_function_disabled(&self) -> bool {
-        !self.is_connected() || self.cfg.device_function.is_none()
+    /// Remembers the current settings for the next session. Only done while
+    /// connected, before that the device settings are not known.
+    fn save_session(&mut self) {
+        if !self.is_connected() {
+            return;
+        }
+        let session = Setup::new(&self.cfg, self.gui_setup());
+        if let Err(err) = session.save_session() {
+            error!("failed to save session: {:#}", err);
+        }
+        self.session = Some(session);
     }
 
-    fn get_device_function(&self) -> DeviceFunction {
-        self.cfg
-            .device_function
-            .as_ref()
-            .unwrap_or(&DeviceFunction::Scope)
-            .clone()
+    fn get_factory_defaults(&self) -> bool {
+        self.factory_defaults
     }
 
-    fn set_device_function(&mut self, new_value: DeviceFunction) {
-        self.cfg.device_function = Some(new_value);
+    fn set_factory_defaults(&mut self, new_value: bool) {
+        self.factory_defaults = new_value;
     }
 
-    // ------------
-
-    fn on_running(&mut self) {
-        let _err = self.send_running();
+    fn is_load_setup_disabled(&self) -> bool {
+        !self.is_connected()
     }
 
-    fn send_running(&mut self) -> anyhow::Result<()> {
-        let my_name = "send_running()";
-        if self.initializing {
-            trace!("UI => {}, SKIPPED/INIT", my_name);
-            return Ok(());
-        } else if !self.is_connected() {
-            self.message_error("not connected");
-            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
-            bail!("not connected");
-        } else if self.is_running_disabled() {
-            trace!("UI => {}, SKIPPED/NO_STATE", my_name);
-        } else {
-            trace!("UI => {}", my_name);
+    fn enabled_channels(&self) -> Vec<usize> {
+        let mut channels = Vec::with_capacity(2);
+        if self.get_enabled_channel(1) {
+            channels.push(1);
         }
+        if self.get_enabled_channel(2) {
+            channels.push(2);
+        }
+        channels
+    }
 
-        let new_status = self.cfg.running_status.as_ref().unwrap();
-        debug!("UI => {}::{}", my_name, new_status.my_to_string());
-
-        self.tx
-            .send(DevCommand::ScopeRunning(new_status.clone()))
-            .unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                match new_status {
-                    RunningStatus::Start => self.message_info("running"),
-                    RunningStatus::Stop => self.message_info("stopped"),
-                };
-                Ok(())
-            }
-            Err(error) => {
-                self.message_error(error.clone());
-                bail!(error);
-            }
+    /// Channels on screen: the enabled device channels and the math trace.
+    fn shown_channels(&self) -> Vec<usize> {
+        let mut channels = self.enabled_channels();
+        if self.math.enabled {
+            channels.push(MATH_CHANNEL);
         }
+        channels
     }
 
-    fn get_running(&self) -> bool {
-        self.cfg
-            .running_status
-            .as_ref()
-            .unwrap_or(&RunningStatus::Stop)
-            .is_start()
+    /// Whether a setting captures are decoded with is still on its way to
+    /// the device, which may not apply it, or not before the capture.
+    fn is_calibration_pending(&self) -> bool {
+        self.is_pending(Control::TimeScale)
+            || [1, 2].into_iter().any(|channel| {
+                self.is_pending(Control::Scale(channel)) || self.is_pending(Control::Probe(channel))
+            })
     }
 
-    fn set_running(&mut self, new_value: bool) {
-        self.cfg.running_status = Some(match new_value {
-            true => RunningStatus::Start,
-            false => RunningStatus::Stop,
-        });
+    fn capture(&mut self) {
+        let channels = self.enabled_channels();
+        self.capture_channels = channels.clone();
+        // Captures wait for pending scale, probe and time scale requests, see
+        // `is_calibration_pending`, so these are settings the device applied.
+        self.capture_cfg = self.cfg.clone();
+        self.send(DevCommand::Capture(channels, self.num_captures), "captured");
     }
 
-    fn is_running_disabled(&self) -> bool {
-        !self.is_connected() || self.cfg.running_status.is_none()
+    fn on_captured(&mut self, capture: Vec<u8>) {
+        trace!("UI <= captured number of bytes: {}", capture.len());
+        let raw = RawCapture {
+            bytes: capture,
+            channels: self.capture_channels.clone(),
+            cfg: self.capture_cfg.clone(),
+        };
+        let waveform = Waveform::decode(&raw.bytes, &raw.channels, &raw.cfg);
+        if self.is_single_armed() && !self.on_single_captured(&waveform, &raw.bytes) {
+            return;
+        }
+        let waveform = self.apply_math(waveform);
+        self.update_decoded(&waveform);
+        self.update_measurements(&waveform);
+        self.update_spectra(&waveform);
+        let waveform = Arc::new(waveform);
+        self.update_xy_history(waveform.clone());
+        self.capture = Some(waveform);
+        self.capture_raw = Some(Arc::new(raw));
+    }
+
+    fn is_capture_disabled(&self) -> bool {
+        !self.is_connected()
+            || self.acquiring
+            || self.is_single_armed()
+            || self.is_pending(Control::Capture)
+            || self.is_calibration_pending()
     }
 
     // ------------
 
-    fn on_coupling(&mut self, channel: usize) {
-        let _err = self.send_coupling(channel);
+    /// Asks the device for its current settings, the answer is reconciled
+    /// by [`HantekState::on_settings`].
+    fn read_settings(&mut self) {
+        if !self.is_connected() || self.is_pending(Control::ReadSettings) {
+            return;
+        }
+        self.synced_at = Some(Instant::now());
+        self.send(DevCommand::ReadSettings, "settings read");
     }
 
-    fn send_coupling(&mut self, channel: usize) -> anyhow::Result<()> {
-        let my_name = format!("send_coupling(channel={})", channel);
-        if self.initializing {
-            trace!("UI => {}, SKIPPED/INIT", my_name);
-            return Ok(());
-        } else if !self.is_connected() {
-            self.message_error("not connected");
-            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
-            bail!("not connected");
-        } else {
-            trace!("UI => {}", my_name);
+    fn is_sync_due(&self) -> bool {
+        if self.sync_unsupported {
+            return false;
         }
+        match self.synced_at {
+            Some(synced_at) => synced_at.elapsed() >= SYNC_INTERVAL,
+            None => self.is_connected(),
+        }
+    }
 
-        let coupling = self.cfg.channel_coupling[&channel]
-            .as_ref()
-            .unwrap()
-            .clone();
-        debug!("UI => {}::{}", my_name, coupling.my_to_string());
-
-        self.message_info(format!(
-            "setting coupling={}, channel={}",
-            coupling.my_to_string(),
-            channel
-        ));
-
-        self.tx
-            .send(DevCommand::Coupling(channel, coupling))
-            .unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.message_info("coupling set");
-                Ok(())
-            }
-            Err(error) => {
-                self.message_error(error.clone());
-                bail!(error);
+    /// Takes over whatever the device reports differently, e.g. after a
+    /// change on its front panel, and logs each difference. Like a change
+    /// made here, a different scale, probe, coupling or time scale restarts
+    /// the measurement statistics and spectrum averaging.
+    fn on_settings(&mut self, settings: HantekConfig) {
+        let mut cfg = self.cfg.clone();
+        let mismatches = sync::reconcile(&mut cfg, &settings, |control| self.is_pending(control));
+        self.cfg = cfg;
+        for mismatch in mismatches {
+            match mismatch.control {
+                Control::Coupling(channel) | Control::Probe(channel) | Control::Scale(channel) => {
+                    self.reset_channel_measurements(channel);
+                    self.spectra = None;
+                }
+                Control::TimeScale => {
+                    self.measurement_stats.clear();
+                    self.spectra = None;
+                }
+                _ => {}
             }
+            self.message_error(format!(
+                "device reports {}={}, GUI had {}",
+                mismatch.setting, mismatch.device, mismatch.gui
+            ));
         }
     }
 
-    fn is_coupling_disabled(&self, channel: usize) -> bool {
-        !self.is_connected() || self.cfg.channel_coupling[&channel].is_none()
+    fn on_settings_unsupported(&mut self) {
+        if !self.sync_unsupported {
+            self.sync_unsupported = true;
+            self.message_info(
+                "device can't report its settings, front panel changes won't show up here",
+            );
+        }
     }
 
-    fn get_coupling(&self, channel: usize) -> Coupling {
-        self.cfg.channel_coupling[&channel]
-            .as_ref()
-            .unwrap_or(&Coupling::AC)
-            .clone()
+    // ------------
+
+    fn start_acquisition(&mut self) {
+        trace!("UI => start_acquisition()");
+        self.message_info(format!(
+            "acquisition started, interval={}ms",
+            self.acquisition_interval
+        ));
+        self.acquiring = true;
+        self.ensure_running();
     }
 
-    fn set_coupling(&mut self, channel: usize, new_value: Coupling) {
-        self.cfg.channel_coupling.insert(channel, Some(new_value));
+    /// Frozen single shots leave the scope stopped, get it going again.
+    fn ensure_running(&mut self) {
+        if !self.get_running() {
+            self.set_running(true);
+            let _err = self.send_running();
+        }
     }
 
-    // ------------
+    fn stop_acquisition(&mut self) {
+        trace!("UI => stop_acquisition()");
+        self.message_info("acquisition stopped");
+        self.acquiring = false;
+    }
 
-    fn on_probe(&mut self, channel: usize) {
-        let _err = self.send_probe(channel);
+    fn toggle_acquisition(&mut self) {
+        match self.acquiring {
+            true => self.stop_acquisition(),
+            false => self.start_acquisition(),
+        }
     }
 
-    fn send_probe(&mut self, channel: usize) -> anyhow::Result<()> {
-        let my_name = format!("send_probe(channel={})", channel);
-        if self.initializing {
-            trace!("UI => {}, SKIPPED/INIT", my_name);
-            return Ok(());
-        } else if !self.is_connected() {
-            self.message_error("not connected");
-            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
-            bail!("not connected");
-        } else {
-            trace!("UI => {}", my_name);
+    /// Called periodically by [`Acquisition`]; issues the next capture unless
+    /// the previous one is still in flight, so a slow device just lowers the
+    /// effective rate instead of piling up requests.
+    fn on_acquisition_tick(&mut self) {
+        if self.is_sync_due() {
+            self.read_settings();
         }
 
-        let probe = self.cfg.channel_probe[&channel].as_ref().unwrap().clone();
-        debug!("UI => {}::{}", my_name, probe.my_to_string());
-
-        self.message_info(format!(
-            "setting probe={}, channel={}",
-            probe.my_to_string(),
-            channel
-        ));
-
-        self.tx.send(DevCommand::Probe(channel, probe)).unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.message_info("probe set");
-                Ok(())
-            }
-            Err(error) => {
-                self.message_error(error.clone());
-                bail!(error);
+        if let Some(single) = &self.single {
+            if single.armed_at.elapsed().as_secs_f32() > self.single_timeout {
+                self.end_single();
+                self.message_error(format!(
+                    "single shot timed out after {}s without a trigger",
+                    self.single_timeout
+                ));
+                return;
             }
+        } else if !self.acquiring {
+            return;
+        }
+
+        if !self.is_connected()
+            || self.is_pending(Control::Capture)
+            || self.is_calibration_pending()
+            || self.enabled_channels().is_empty()
+        {
+            return;
         }
+        self.capture();
     }
 
-    fn is_probe_disabled(&self, channel: usize) -> bool {
-        !self.is_connected() || self.cfg.channel_probe[&channel].is_none()
+    fn is_acquisition_disabled(&self) -> bool {
+        !self.is_connected() || self.is_single_armed()
     }
 
-    fn get_probe(&self, channel: usize) -> Probe {
-        self.cfg.channel_probe[&channel]
-            .as_ref()
-            .unwrap_or(&Probe::X1)
-            .clone()
+    fn get_acquisition_interval(&self) -> usize {
+        self.acquisition_interval
     }
 
-    fn set_probe(&mut self, channel: usize, new_value: Probe) {
-        self.cfg.channel_probe.insert(channel, Some(new_value));
+    fn set_acquisition_interval(&mut self, new_value: usize) {
+        self.acquisition_interval = new_value.max(MIN_ACQUISITION_INTERVAL);
     }
 
     // ------------
 
-    fn on_scale(&mut self, channel: usize) {
-        let _err = self.send_scale(channel);
-    }
+    /// Arms a single shot: the device is put in single trigger mode and
+    /// started, captures keep being polled by [`Acquisition`] until the
+    /// device delivers its triggered acquisition, which is kept, the scope
+    /// stopped and the previous trigger mode restored.
+    fn arm_single(&mut self) {
+        let my_name = "arm_single()";
+        trace!("UI => {}", my_name);
 
-    fn send_scale(&mut self, channel: usize) -> anyhow::Result<()> {
-        let my_name = format!("send_scale(channel={})", channel);
-        if self.initializing {
-            trace!("UI => {}, SKIPPED/INIT", my_name);
-            return Ok(());
-        } else if !self.is_connected() {
-            self.message_error("not connected");
-            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
-            bail!("not connected");
-        } else {
-            trace!("UI => {}", my_name);
+        let source = self.get_trigger_source();
+        if !self.get_enabled_channel(source) {
+            self.message_error(format!(
+                "cannot arm single shot, trigger source channel {} is not enabled",
+                source
+            ));
+            return;
         }
 
-        let scale = self.cfg.channel_scale[&channel].as_ref().unwrap().clone();
-        debug!("UI => {}::{}", my_name, scale.my_to_string());
-
+        self.acquiring = false;
+        let stale = self.capture_raw.as_ref().map(|raw| raw.bytes.clone());
+        self.single = Some(SingleShot::new(self.get_trigger_mode(), stale));
+        self.set_trigger_mode(TriggerMode::Single);
+        let _err = self.send_trigger_mode();
+        // Start even if running, that is what arms the device.
+        self.set_running(true);
+        let _err = self.send_running();
         self.message_info(format!(
-            "setting scale={}, channel={}",
-            scale.my_to_string(),
-            channel
+            "single shot armed, trigger_source_channel={}, trigger_level={}, timeout={}s",
+            source,
+            self.get_trigger_level(),
+            self.single_timeout
         ));
+    }
 
-        self.tx.send(DevCommand::Scale(channel, scale)).unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.message_info("scale set");
-                Ok(())
-            }
-            Err(error) => {
-                self.message_error(error.clone());
-                bail!(error);
-            }
+    fn cancel_single(&mut self) {
+        trace!("UI => cancel_single()");
+        self.end_single();
+        self.message_info("single shot cancelled");
+    }
+
+    /// Disarms the single shot and puts back the trigger mode it replaced.
+    fn end_single(&mut self) {
+        if let Some(single) = self.single.take() {
+            self.set_trigger_mode(single.restore_mode);
+            let _err = self.send_trigger_mode();
         }
     }
 
-    fn is_scale_disabled(&self, channel: usize) -> bool {
-        !self.is_connected() || self.cfg.channel_scale[&channel].is_none()
+    /// Returns whether `capture` triggered, in which case the single shot is
+    /// over and the capture should be shown.
+    fn on_single_captured(&mut self, waveform: &Waveform, capture: &[u8]) -> bool {
+        let single = self.single.as_mut().unwrap();
+        let waited = single.armed_at.elapsed().as_secs_f64();
+        if single.is_device_triggered(capture) {
+            self.message_info(format!("single shot triggered after {:.3}s", waited));
+        } else if single.is_fallback_due() {
+            let index = match self.find_software_trigger(waveform) {
+                Some(index) => index,
+                None => return false,
+            };
+            self.message_info(format!(
+                "single shot triggered after {:.3}s by the software fallback, the device \
+                 didn't deliver a triggered capture, trigger at t={:.6}s (sample {} of {})",
+                waited,
+                waveform.time(index),
+                index,
+                waveform.num_samples
+            ));
+        } else {
+            return false;
+        }
+
+        self.set_running(false);
+        let _err = self.send_running();
+        self.end_single();
+        true
     }
 
-    fn get_scale(&self, channel: usize) -> Scale {
-        self.cfg.channel_scale[&channel]
-            .as_ref()
-            .unwrap_or(&Scale::mv10)
-            .clone()
+    /// Rising edge through the trigger level on the trigger source, for
+    /// devices that don't stop by themselves in single trigger mode.
+    fn find_software_trigger(&self, waveform: &Waveform) -> Option<usize> {
+        let cfg = &self.capture_cfg;
+        let source = cfg.trigger_source_channel?;
+        let trace = waveform.trace(source)?;
+        let scale = cfg.channel_scale.get(&source).cloned().flatten();
+        let probe = cfg.channel_probe.get(&source).cloned().flatten();
+        let volts_per_div =
+            volts_per_div(&scale.unwrap_or(Scale::v10)) * probe_factor(&probe.unwrap_or(Probe::X1));
+        let level = cfg.trigger_level.unwrap_or(0.0) as f64;
+        find_rising_edge(trace, level, TRIGGER_HYSTERESIS_DIVS * volts_per_div)
     }
 
-    fn set_scale0(&mut self, channel: usize, new_value: Scale) {
-        self.cfg.channel_scale.insert(channel, Some(new_value));
+    fn is_single_armed(&self) -> bool {
+        self.single.is_some()
     }
 
-    // ------------
+    fn is_single_disabled(&self) -> bool {
+        !self.is_connected() || self.acquiring || self.is_single_armed()
+    }
 
-    fn on_offset(&mut self, channel: usize) {
-        let _err = self.send_offset(channel);
+    fn is_cancel_single_disabled(&self) -> bool {
+        !self.is_single_armed()
     }
 
-    fn send_offset(&mut self, channel: usize) -> anyhow::Result<()> {
-        let my_name = format!("send_offset(channel={})", channel);
-        if self.initializing {
-            trace!("UI => {}, SKIPPED/INIT", my_name);
-            return Ok(());
-        } else if !self.is_connected() {
-            self.message_error("not connected");
-            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
-            bail!("not connected");
-        } else {
-            trace!("UI => {}", my_name);
-        }
+    fn get_single_timeout(&self) -> f32 {
+        self.single_timeout
+    }
 
-        let offset = self.cfg.channel_offset[&channel].unwrap();
-        debug!("UI => {}::{}", my_name, offset);
+    fn set_single_timeout(&mut self, new_value: f32) {
+        self.single_timeout = new_value;
+    }
 
-        self.message_info(format!("setting offset={}, channel={}", offset, channel));
+    // ------------
 
-        self.tx.send(DevCommand::Offset(channel, offset)).unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.message_info("offset set");
-                Ok(())
-            }
-            Err(error) => {
-                self.message_error(error.clone());
-                bail!(error);
-            }
-        }
+    fn get_view(&self) -> View {
+        self.view
     }
 
-    fn is_offset_disabled(&self, channel: usize) -> bool {
-        !self.is_connected() || self.cfg.channel_offset[&channel].is_none()
+    fn set_view(&mut self, new_value: View) {
+        trace!("UI => set_view({:?})", new_value);
+        self.view = new_value;
     }
 
-    fn get_offset(&self, channel: usize) -> f32 {
-        self.cfg.channel_offset[&channel].unwrap_or(0.0f32)
+    fn reset_view(&mut self) {
+        trace!("UI => reset_view()");
+        self.view = View::default();
     }
 
-    fn set_offset(&mut self, channel: usize, new_value: f32) {
-        self.cfg.channel_offset.insert(channel, Some(new_value));
+    fn is_reset_view_disabled(&self) -> bool {
+        self.view.is_default()
     }
 
     // ------------
 
-    fn on_channel_enable(&mut self, channel: usize) {
-        let _err = self.send_channel_enable(channel);
+    fn get_cursors(&self) -> Cursors {
+        self.cursors
     }
 
-    fn send_channel_enable(&mut self, channel: usize) -> anyhow::Result<()> {
-        let my_name = format!("send_channel_enable(channel={})", channel);
-        if self.initializing {
-            trace!("UI => {}, SKIPPED/INIT", my_name);
-            return Ok(());
-        } else if !self.is_connected() {
-            self.message_error("not connected");
-            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
-            bail!("not connected");
-        } else {
-            trace!("UI => {}", my_name);
-        }
+    fn set_cursors(&mut self, new_value: Cursors) {
+        self.cursors = new_value;
+    }
 
-        let enabled = self.cfg.enabled_channels[&channel].unwrap();
-        debug!("UI => {}::{}", my_name, enabled);
+    fn get_time_cursors(&self) -> bool {
+        self.cursors.time_enabled
+    }
 
-        self.message_info(format!(
-            "setting channel status, channel={}, status={}",
-            channel,
-            match enabled {
-                true => "enabled",
-                false => "disabled",
-            }
-        ));
+    /// Turning a cursor pair on places it anew on the visible part of the plot.
+    fn set_time_cursors(&mut self, new_value: bool) {
+        self.cursors.time_enabled = new_value;
+        self.cursors.times = None;
+    }
 
-        self.tx
-            .send(DevCommand::ChannelEnable(channel, enabled))
-            .unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.message_info("channel status set");
-                Ok(())
-            }
-            Err(error) => {
-                self.message_error(error.clone());
-                bail!(error);
-            }
-        }
+    fn get_volt_cursors(&self) -> bool {
+        self.cursors.volts_enabled
     }
 
-    fn is_channel_enable_disabled(&self, channel: usize) -> bool {
-        !self.is_connected() || self.cfg.enabled_channels[&channel].is_none()
+    fn set_volt_cursors(&mut self, new_value: bool) {
+        self.cursors.volts_enabled = new_value;
+        self.cursors.volts = None;
     }
 
-    fn get_enabled_channel(&self, channel: usize) -> bool {
-        self.cfg.enabled_channels[&channel].unwrap_or(false)
+    fn get_volt_cursor_channel(&self) -> usize {
+        self.cursors.volts_channel
     }
 
-    fn set_enabled_channel(&mut self, channel: usize, new_value: bool) {
-        self.cfg.enabled_channels.insert(channel, Some(new_value));
+    fn set_volt_cursor_channel(&mut self, new_value: usize) {
+        self.cursors.volts_channel = new_value;
+        self.cursors.volts = None;
+    }
+
+    fn is_volt_cursor_channel_disabled(&self) -> bool {
+        !self.cursors.volts_enabled
     }
 
     // ------------
 
-    fn on_bw_limit(&mut self, channel: usize) {
-        let _err = self.send_bw_limit(channel);
+    /// Measures every channel of a new capture and adds the results to the
+    /// running statistics.
+    fn update_measurements(&mut self, waveform: &Waveform) {
+        for trace in &waveform.traces {
+            self.update_trace_measurements(trace, waveform.sample_interval);
+        }
     }
 
-    fn send_bw_limit(&mut self, channel: usize) -> anyhow::Result<()> {
-        let my_name = format!("send_bw_limit(channel={})", channel);
-        if self.initializing {
-            trace!("UI => {}, SKIPPED/INIT", my_name);
-            return Ok(());
-        } else if !self.is_connected() {
-            self.message_error("not connected");
-            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
-            bail!("not connected");
-        } else {
-            trace!("UI => {}", my_name);
+    fn update_trace_measurements(&mut self, trace: &Trace, sample_interval: f64) {
+        for (measurement, value) in measure(trace, sample_interval) {
+            self.measurement_stats
+                .entry((trace.channel, measurement))
+                .and_modify(|stats| stats.add(value))
+                .or_insert_with(|| Statistics::new(value));
         }
+    }
 
-        let enabled = self.cfg.channel_bandwidth_limit[&channel].unwrap();
-        debug!("UI => {}::{}", my_name, enabled);
+    fn reset_measurements(&mut self) {
+        trace!("UI => reset_measurements()");
+        self.measurement_stats.clear();
+    }
 
-        self.message_info(format!(
-            "setting channel bandwidth limit, channel={}, limit={}",
-            channel,
-            match enabled {
-                true => "enabled",
-                false => "disabled",
-            }
-        ));
+    /// Drops the statistics of `channel` after a settings change, which
+    /// makes its earlier values incomparable. The math trace is computed
+    /// from the device channels, so its statistics go too.
+    fn reset_channel_measurements(&mut self, channel: usize) {
+        self.measurement_stats.retain(|(stats_channel, _), _| {
+            *stats_channel != channel && *stats_channel != MATH_CHANNEL
+        });
+    }
 
-        self.tx.send(DevCommand::BwLimit(channel, enabled)).unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.message_info("channel bandwidth limit configuration set");
-                Ok(())
-            }
-            Err(error) => {
-                self.message_error(error.clone());
-                bail!(error);
-            }
-        }
+    fn is_reset_measurements_disabled(&self) -> bool {
+        self.measurement_stats.is_empty()
     }
 
-    fn get_bw_limit(&self, channel: usize) -> bool {
-        self.cfg.channel_bandwidth_limit[&channel].unwrap_or(false)
+    fn is_measurement_selected(&self, measurement: Measurement) -> bool {
+        self.measurements.contains(&measurement)
     }
 
-    fn set_bw_limit(&mut self, channel: usize, new_value: bool) {
-        self.cfg
-            .channel_bandwidth_limit
-            .insert(channel, Some(new_value));
+    fn set_measurement_selected(&mut self, measurement: Measurement, new_value: bool) {
+        match new_value {
+            true => self.measurements.insert(measurement),
+            false => self.measurements.remove(&measurement),
+        };
     }
 
-    fn is_bw_limit_enable_disabled(&self, channel: usize) -> bool {
-        !self.is_connected() || self.cfg.channel_bandwidth_limit[&channel].is_none()
+    fn get_measurements_text(&self) -> String {
+        let mut text = format!(
+            "{:<14}{:>10}{:>10}{:>10}{:>10}{:>10}{:>6}",
+            "", "last", "min", "max", "mean", "std", "n"
+        );
+        for channel in self.shown_channels() {
+            for measurement in Measurement::ALL {
+                if !self.is_measurement_selected(measurement) {
+                    continue;
+                }
+                let name = format!("{} {}", channel_name(channel), measurement.name());
+                let row = match self.measurement_stats.get(&(channel, measurement)) {
+                    Some(stats) => format!(
+                        "{:<14}{:>10}{:>10}{:>10}{:>10}{:>10}{:>6}",
+                        name,
+                        measurement.format(stats.last),
+                        measurement.format(stats.min),
+                        measurement.format(stats.max),
+                        measurement.format(stats.mean),
+                        measurement.format(stats.std()),
+                        stats.count
+                    ),
+                    None => format!("{:<14}{:>10}", name, "--"),
+                };
+                text.push('\n');
+                text.push_str(&row);
+            }
+        }
+        text
     }
 
     // ------------
 
-    fn on_time_scale(&mut self) {
-        let _err = self.send_time_scale();
+    fn get_display_mode(&self) -> DisplayMode {
+        self.display_mode
     }
 
-    fn send_time_scale(&mut self) -> anyhow::Result<()> {
-        let my_name = "send_time_scale()";
-        if self.initializing {
-            trace!("UI => {}, SKIPPED/INIT", my_name);
-            return Ok(());
-        } else if !self.is_connected() {
-            self.message_error("not connected");
-            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
-            bail!("not connected");
-        } else {
-            trace!("UI => {}", my_name);
+    fn set_display_mode(&mut self, new_value: DisplayMode) {
+        trace!("UI => set_display_mode({:?})", new_value);
+        let was_spectrum = self.display_mode == DisplayMode::Spectrum;
+        self.display_mode = new_value;
+        if !was_spectrum && new_value == DisplayMode::Spectrum {
+            self.reset_spectra();
         }
+    }
 
-        let scale = self.cfg.time_scale.as_ref().unwrap().clone();
-        debug!("UI => {}::{}", my_name, scale.my_to_string());
-
-        self.message_info(format!("setting time_scale={}", scale.my_to_string()));
-
-        self.tx.send(DevCommand::TimeScale(scale)).unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.message_info("time scale set");
-                Ok(())
-            }
-            Err(error) => {
-                self.message_error(error.clone());
-                bail!(error);
-            }
+    /// Adds the spectra of a new capture to the running average. Spectra are
+    /// only computed while they are shown, [`HantekState::set_display_mode`]
+    /// catches up when switching to them.
+    fn update_spectra(&mut self, waveform: &Waveform) {
+        if self.display_mode != DisplayMode::Spectrum {
+            return;
         }
+        let next = Spectra::compute(waveform, self.spectrum_window);
+        let spectra = match &self.spectra {
+            Some(spectra) => spectra.average(next, self.spectrum_averages),
+            None => next,
+        };
+        self.spectra = Some(Arc::new(spectra));
     }
 
-    fn get_time_scale(&self) -> TimeScale {
-        self.cfg
-            .time_scale
+    /// Restarts averaging from the capture on screen.
+    fn reset_spectra(&mut self) {
+        if self.display_mode != DisplayMode::Spectrum {
+            self.spectra = None;
+            return;
+        }
+        self.spectra = self
+            .capture
             .as_ref()
-            .unwrap_or(&TimeScale::ns5)
-            .clone()
+            .map(|waveform| Arc::new(Spectra::compute(waveform, self.spectrum_window)));
     }
 
-    fn set_time_scale(&mut self, new_value: TimeScale) {
-        self.cfg.time_scale = Some(new_value);
+    fn is_spectrum_disabled(&self) -> bool {
+        self.display_mode != DisplayMode::Spectrum
     }
 
-    fn is_time_scale_disabled(&self) -> bool {
-        !self.is_connected() || self.cfg.time_scale.is_none()
+    fn get_spectrum_window(&self) -> Window {
+        self.spectrum_window
     }
 
-    // ------------
+    fn set_spectrum_window(&mut self, new_value: Window) {
+        self.spectrum_window = new_value;
+        self.reset_spectra();
+    }
 
-    fn on_time_offset(&mut self) {
-        let _err = self.send_time_offset();
+    fn get_spectrum_scale(&self) -> SpectrumScale {
+        self.spectrum_scale
     }
 
-    fn send_time_offset(&mut self) -> anyhow::Result<()> {
-        let my_name = "send_time_offset()";
-        if self.initializing {
-            trace!("UI => {}, SKIPPED/INIT", my_name);
-            return Ok(());
-        } else if !self.is_connected() {
-            self.message_error("not connected");
-            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
-            bail!("not connected");
-        } else {
-            trace!("UI => {}", my_name);
-        }
+    fn set_spectrum_scale(&mut self, new_value: SpectrumScale) {
+        self.spectrum_scale = new_value;
+    }
 
-        let time_offset = self.cfg.time_offset.unwrap();
-        debug!("UI => {}::{}", my_name, time_offset);
+    fn get_spectrum_averages(&self) -> usize {
+        self.spectrum_averages
+    }
 
-        self.message_info(format!("setting time_offset={}", time_offset));
+    fn set_spectrum_averages(&mut self, new_value: usize) {
+        self.spectrum_averages = new_value.max(1);
+        self.reset_spectra();
+    }
 
-        self.tx.send(DevCommand::TimeOffset(time_offset)).unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.message_info("time offset set");
-                Ok(())
-            }
-            Err(error) => {
-                self.message_error(error.clone());
-                bail!(error);
-            }
+    // ------------
+
+    /// Keeps the last `xy_persistence` captures for the XY display.
+    fn update_xy_history(&mut self, waveform: Arc<Waveform>) {
+        self.xy_history.push_back(waveform);
+        while self.xy_history.len() > self.xy_persistence {
+            self.xy_history.pop_front();
         }
     }
 
-    fn get_time_offset(&self) -> f32 {
-        self.cfg.time_offset.unwrap_or(0.0)
+    fn is_xy_persistence_disabled(&self) -> bool {
+        self.display_mode != DisplayMode::Xy
     }
 
-    fn set_time_offset(&mut self, new_value: f32) {
-        self.cfg.time_offset = Some(new_value);
+    fn get_xy_persistence(&self) -> usize {
+        self.xy_persistence
     }
 
-    fn is_time_offset_disabled(&self) -> bool {
-        !self.is_connected() || self.cfg.time_offset.is_none()
+    fn set_xy_persistence(&mut self, new_value: usize) {
+        self.xy_persistence = new_value.clamp(1, MAX_XY_PERSISTENCE);
+        while self.xy_history.len() > self.xy_persistence {
+            self.xy_history.pop_front();
+        }
     }
 
     // ------------
 
-    fn on_trigger_source(&mut self) {
-        let _err = self.send_trigger_source();
+    /// Replaces the math trace of `waveform` with one computed from the
+    /// current math settings.
+    fn apply_math(&mut self, mut waveform: Waveform) -> Waveform {
+        waveform
+            .traces
+            .retain(|trace| trace.channel != MATH_CHANNEL);
+        self.math_error = None;
+        if !self.math.enabled {
+            return waveform;
+        }
+        match self.math.compute(&waveform) {
+            Ok(trace) => waveform.traces.push(trace),
+            Err(err) => self.math_error = Some(err.to_string()),
+        }
+        waveform
     }
 
-    fn send_trigger_source(&mut self) -> anyhow::Result<()> {
-        let my_name = "send_trigger_source()";
-        if self.initializing {
-            trace!("UI => {}, SKIPPED/INIT", my_name);
-            return Ok(());
-        } else if !self.is_connected() {
-            self.message_error("not connected");
-            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
-            bail!("not connected");
+    /// Recomputes the math trace of the capture on screen after a settings
+    /// change.
+    fn on_math(&mut self) {
+        trace!("UI => on_math({:?})", self.math);
+        self.reset_channel_measurements(MATH_CHANNEL);
+        if let Some(capture) = self.capture.take() {
+            let waveform = self.apply_math((*capture).clone());
+            self.update_decoded(&waveform);
+            if let Some(trace) = waveform.trace(MATH_CHANNEL) {
+                self.update_trace_measurements(trace, waveform.sample_interval);
+            }
+            self.capture = Some(Arc::new(waveform));
+            self.reset_spectra();
         } else {
-            trace!("UI => {}", my_name);
+            self.math_error = match self.math.operation {
+                MathOperation::Expression if self.math.enabled => {
+                    self.math.expression_error().map(|err| err.to_string())
+                }
+                _ => None,
+            };
         }
+    }
 
-        let source = self.cfg.trigger_source_channel.unwrap();
-        debug!("UI => {}::{}", my_name, source);
+    fn is_math_disabled(&self) -> bool {
+        !self.math.enabled
+    }
 
-        self.message_info(format!("setting trigger_source_channel={}", source));
+    fn is_math_source_b_disabled(&self) -> bool {
+        !self.math.enabled || !self.math.operation.uses_source_b()
+    }
 
-        self.tx.send(DevCommand::TriggerSource(source)).unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.message_info("trigger source set");
-                Ok(())
-            }
-            Err(error) => {
-                self.message_error(error.clone());
-                bail!(error);
-            }
+    fn is_math_expression_disabled(&self) -> bool {
+        !self.math.enabled || self.math.operation != MathOperation::Expression
+    }
+
+    fn get_math_status(&self) -> String {
+        match &self.math_error {
+            Some(err) => format!("Error: {}", err),
+            None => String::new(),
         }
     }
 
-    fn get_trigger_source(&self) -> usize {
-        self.cfg.trigger_source_channel.unwrap_or(1usize)
+    fn get_math_enabled(&self) -> bool {
+        self.math.enabled
     }
 
-    fn set_trigger_source(&mut self, new_value: usize) {
-        self.cfg.trigger_source_channel = Some(new_value);
+    fn set_math_enabled(&mut self, new_value: bool) {
+        self.math.enabled = new_value;
     }
 
-    fn is_trigger_source_disabled(&self) -> bool {
-        !self.is_connected() || self.cfg.trigger_source_channel.is_none()
+    fn get_math_operation(&self) -> MathOperation {
+        self.math.operation
     }
 
-    // ------------
+    fn set_math_operation(&mut self, new_value: MathOperation) {
+        self.math.operation = new_value;
+    }
 
-    fn on_trigger_mode(&mut self) {
-        let _err = self.send_trigger_mode();
+    fn get_math_source_a(&self) -> usize {
+        self.math.source_a
     }
 
-    fn send_trigger_mode(&mut self) -> anyhow::Result<()> {
-        let my_name = "send_trigger_mode()";
-        if self.initializing {
-            trace!("UI => {}, SKIPPED/INIT", my_name);
-            return Ok(());
-        } else if !self.is_connected() {
-            self.message_error("not connected");
-            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
-            bail!("not connected");
-        } else {
-            trace!("UI => {}", my_name);
-        }
+    fn set_math_source_a(&mut self, new_value: usize) {
+        self.math.source_a = new_value;
+    }
 
-        let mode = self.cfg.trigger_mode.as_ref().unwrap().clone();
-        debug!("UI => {}::{}", my_name, mode.my_to_string());
+    fn get_math_source_b(&self) -> usize {
+        self.math.source_b
+    }
 
-        self.message_info(format!("setting trigger_mode={}", mode.my_to_string()));
+    fn set_math_source_b(&mut self, new_value: usize) {
+        self.math.source_b = new_value;
+    }
 
-        self.tx.send(DevCommand::TriggerMode(mode)).unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.message_info("trigger mode set");
-                Ok(())
-            }
-            Err(error) => {
-                self.message_error(error.clone());
-                bail!(error);
-            }
-        }
+    fn get_math_expression(&self) -> String {
+        self.math.expression().to_string()
     }
 
-    fn get_trigger_mode(&self) -> TriggerMode {
-        self.cfg
-            .trigger_mode
-            .as_ref()
-            .unwrap_or(&TriggerMode::Auto)
-            .clone()
+    fn set_math_expression(&mut self, new_value: String) {
+        self.math.set_expression(new_value);
     }
 
-    fn set_trigger_mode(&mut self, new_value: TriggerMode) {
-        self.cfg.trigger_mode = Some(new_value);
+    fn get_math_scale(&self) -> f32 {
+        self.math.volts_per_div as f32
     }
 
-    fn is_trigger_mode_disabled(&self) -> bool {
-        !self.is_connected() || self.cfg.trigger_mode.is_none()
+    fn set_math_scale(&mut self, new_value: f32) {
+        if new_value > 0.0 {
+            self.math.volts_per_div = new_value as f64;
+        }
+    }
+
+    fn get_math_offset(&self) -> f32 {
+        self.math.offset as f32
+    }
+
+    fn set_math_offset(&mut self, new_value: f32) {
+        self.math.offset = new_value as f64;
     }
 
     // ------------
 
-    fn on_trigger_level(&mut self) {
-        let _err = self.send_trigger_level();
+    /// Runs the selected protocol decoder on `waveform`.
+    fn update_decoded(&mut self, waveform: &Waveform) {
+        let (decoded, error) = match self.decoder.decode(waveform) {
+            None => (None, None),
+            Some(Ok(decoded)) => (Some(Arc::new(decoded)), None),
+            Some(Err(err)) => (None, Some(err.to_string())),
+        };
+        self.decoded = decoded;
+        self.decode_error = error;
     }
 
-    fn send_trigger_level(&mut self) -> anyhow::Result<()> {
-        let my_name = "send_trigger_level()";
-        if self.initializing {
-            trace!("UI => {}, SKIPPED/INIT", my_name);
-            return Ok(());
-        } else if !self.is_connected() {
-            self.message_error("not connected");
-            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
-            bail!("not connected");
-        } else {
-            trace!("UI => {}", my_name);
+    /// Decodes the capture on screen again after a settings change.
+    fn on_decoder(&mut self) {
+        trace!("UI => on_decoder({:?})", self.decoder);
+        match self.capture.clone() {
+            Some(capture) => self.update_decoded(&capture),
+            None => {
+                self.decoded = None;
+                self.decode_error = None;
+            }
         }
+    }
 
-        let level = self.cfg.trigger_level.unwrap();
-        debug!("UI => {}::{}", my_name, level);
+    fn is_decoder_disabled(&self) -> bool {
+        self.decoder.protocol == Protocol::Off
+    }
 
-        self.message_info(format!("setting trigger_level={}", level));
+    fn is_uart_baud_rate_disabled(&self) -> bool {
+        self.is_decoder_disabled() || self.decoder.uart.auto_baud
+    }
 
-        self.tx.send(DevCommand::TriggerLevel(level)).unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.message_info("trigger level set");
-                Ok(())
-            }
-            Err(error) => {
-                self.message_error(error.clone());
-                bail!(error);
+    fn get_decoder_status(&self) -> String {
+        match (&self.decode_error, &self.decoded) {
+            (Some(err), _) => format!("Error: {}", err),
+            (None, Some(decoded)) => {
+                let errors = decoded
+                    .rows
+                    .iter()
+                    .filter(|row| row.error.is_some())
+                    .count();
+                let mut status = format!("{} decoded, {} with errors", decoded.rows.len(), errors);
+                if let Some(note) = &decoded.note {
+                    status.push_str(", ");
+                    status.push_str(note);
+                }
+                status
             }
+            (None, None) => String::new(),
         }
     }
 
-    fn get_trigger_level(&self) -> f32 {
-        self.cfg.trigger_level.unwrap_or(0.0)
+    /// Decoded items as a table with their start times.
+    fn get_decoder_text(&self) -> String {
+        let decoded = match &self.decoded {
+            Some(decoded) => decoded,
+            None => return String::new(),
+        };
+        let mut text = format!("{:>10}  {:<16}  {}", "time", "data", "error");
+        for row in &decoded.rows {
+            text.push('\n');
+            text.push_str(&format!(
+                "{:>10}  {:<16}  {}",
+                format_si(row.time, "s"),
+                row.text,
+                row.error.as_deref().unwrap_or("")
+            ));
+        }
+        text
     }
 
-    fn set_trigger_level(&mut self, new_value: f32) {
-        self.cfg.trigger_level = Some(new_value);
+    fn get_decoder_protocol(&self) -> Protocol {
+        self.decoder.protocol
     }
 
-    fn is_trigger_level_disabled(&self) -> bool {
-        !self.is_connected() || self.cfg.trigger_level.is_none()
+    fn set_decoder_protocol(&mut self, new_value: Protocol) {
+        self.decoder.protocol = new_value;
     }
 
-    // -----------
+    fn get_uart_channel(&self) -> usize {
+        self.decoder.uart.channel
+    }
 
-    fn on_num_captures(&mut self) {
-        let my_name = "on_num_captures()";
-        if self.initializing {
-            trace!("UI => {}, SKIPPED/INIT", my_name);
-            return;
-        } else {
-            trace!("UI => {}", my_name);
+    fn set_uart_channel(&mut self, new_value: usize) {
+        self.decoder.uart.channel = new_value;
+    }
+
+    fn get_uart_baud_rate(&self) -> usize {
+        self.decoder.uart.baud_rate
+    }
+
+    fn set_uart_baud_rate(&mut self, new_value: usize) {
+        if new_value > 0 {
+            self.decoder.uart.baud_rate = new_value;
         }
+    }
 
-        let num_captures = self.num_captures;
-        debug!("UI => {}::{}", my_name, num_captures);
+    fn get_uart_auto_baud(&self) -> bool {
+        self.decoder.uart.auto_baud
     }
 
-    fn get_num_captures(&self) -> usize {
-        self.num_captures
+    fn set_uart_auto_baud(&mut self, new_value: bool) {
+        self.decoder.uart.auto_baud = new_value;
     }
 
-    fn set_num_captures(&mut self, new_value: usize) {
-        self.num_captures = new_value;
+    fn get_uart_data_bits(&self) -> usize {
+        self.decoder.uart.data_bits
     }
 
-    fn is_num_captures_disabled(&self) -> bool {
-        !self.is_connected()
+    fn set_uart_data_bits(&mut self, new_value: usize) {
+        self.decoder.uart.data_bits = new_value;
     }
 
-    // ------------
+    fn get_uart_parity(&self) -> Parity {
+        self.decoder.uart.parity
+    }
 
-    fn on_awg_running(&mut self) {
-        let _err = self.send_awg_running();
+    fn set_uart_parity(&mut self, new_value: Parity) {
+        self.decoder.uart.parity = new_value;
     }
 
-    fn send_awg_running(&mut self) -> anyhow::Result<()> {
-        let my_name = "send_awg_running()";
-        if self.initializing {
-            trace!("UI => {}, SKIPPED/INIT", my_name);
-            return Ok(());
-        } else if !self.is_connected() {
-            self.message_error("not connected");
-            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
-            bail!("not connected");
-        } else {
-            trace!("UI => {}", my_name);
-        }
+    fn get_uart_stop_bits(&self) -> StopBits {
+        self.decoder.uart.stop_bits
+    }
 
-        let status = self.cfg.awg_running_status.as_ref().unwrap().clone();
-        debug!("UI => {}::{}", my_name, status.my_to_string());
+    fn set_uart_stop_bits(&mut self, new_value: StopBits) {
+        self.decoder.uart.stop_bits = new_value;
+    }
 
-        self.message_info(format!(
-            "setting awg_running_status={}",
-            status.my_to_string()
-        ));
+    fn get_uart_inverted(&self) -> bool {
+        self.decoder.uart.inverted
+    }
 
-        self.tx.send(DevCommand::AwgRunningStatus(status)).unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.message_info("awg running status set");
-                Ok(())
-            }
-            Err(error) => {
-                self.message_error(error.clone());
-                bail!(error);
-            }
-        }
+    fn set_uart_inverted(&mut self, new_value: bool) {
+        self.decoder.uart.inverted = new_value;
     }
 
-    fn get_awg_running(&self) -> RunningStatus {
-        self.cfg
-            .awg_running_status
-            .as_ref()
-            .unwrap_or(&RunningStatus::Stop)
-            .clone()
+    fn get_uart_threshold(&self) -> f32 {
+        self.decoder.uart.threshold as f32
     }
 
-    fn set_awg_running(&mut self, new_value: RunningStatus) {
-        self.cfg.awg_running_status = Some(new_value);
+    fn set_uart_threshold(&mut self, new_value: f32) {
+        self.decoder.uart.threshold = new_value as f64;
     }
 
-    fn is_awg_running_disabled(&self) -> bool {
-        !self.is_connected() || self.cfg.awg_running_status.is_none()
+    fn get_i2c_scl_channel(&self) -> usize {
+        self.decoder.i2c.scl_channel
     }
 
-    // ------------
+    fn set_i2c_scl_channel(&mut self, new_value: usize) {
+        self.decoder.i2c.scl_channel = new_value;
+    }
 
-    fn on_awg_frequency(&mut self) {
-        let _err = self.send_awg_frequency();
+    fn get_i2c_threshold(&self) -> f32 {
+        self.decoder.i2c.threshold as f32
     }
 
-    fn send_awg_frequency(&mut self) -> anyhow::Result<()> {
-        let my_name = "send_awg_frequency()";
-        if self.initializing {
-            trace!("UI => {}, SKIPPED/INIT", my_name);
-            return Ok(());
-        } else if !self.is_connected() {
-            self.message_error("not connected");
-            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
-            bail!("not connected");
-        } else {
-            trace!("UI => {}", my_name);
-        }
+    fn set_i2c_threshold(&mut self, new_value: f32) {
+        self.decoder.i2c.threshold = new_value as f64;
+    }
 
-        let frequency = self.cfg.awg_frequency.unwrap();
-        debug!("UI => {}::{}", my_name, frequency);
+    fn get_spi_sclk_channel(&self) -> usize {
+        self.decoder.spi.sclk_channel
+    }
 
-        self.message_info(format!("setting awg_running_frequency={}", frequency));
+    fn set_spi_sclk_channel(&mut self, new_value: usize) {
+        self.decoder.spi.sclk_channel = new_value;
+    }
 
-        self.tx.send(DevCommand::AwgFrequency(frequency)).unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.message_info("awg frequency set");
-                Ok(())
-            }
-            Err(error) => {
-                self.message_error(error.clone());
-                bail!(error);
-            }
-        }
+    fn get_spi_data_line(&self) -> DataLine {
+        self.decoder.spi.data_line
     }
 
-    fn get_awg_frequency(&self) -> f32 {
-        self.cfg.awg_frequency.unwrap_or(0.0)
+    fn set_spi_data_line(&mut self, new_value: DataLine) {
+        self.decoder.spi.data_line = new_value;
     }
 
-    fn set_awg_frequency(&mut self, new_value: f32) {
-        self.cfg.awg_frequency = Some(new_value);
+    fn get_spi_cpol(&self) -> bool {
+        self.decoder.spi.cpol
     }
 
-    fn is_awg_frequency_disabled(&self) -> bool {
-        !self.is_connected() || self.cfg.awg_frequency.is_none()
+    fn set_spi_cpol(&mut self, new_value: bool) {
+        self.decoder.spi.cpol = new_value;
     }
 
-    // ------------
+    fn get_spi_cpha(&self) -> bool {
+        self.decoder.spi.cpha
+    }
 
-    fn on_awg_amplitude(&mut self) {
-        let _err = self.send_awg_amplitude();
+    fn set_spi_cpha(&mut self, new_value: bool) {
+        self.decoder.spi.cpha = new_value;
     }
 
-    fn send_awg_amplitude(&mut self) -> anyhow::Result<()> {
-        let my_name = "send_awg_amplitude()";
-        if self.initializing {
-            trace!("UI => {}, SKIPPED/INIT", my_name);
-            return Ok(());
-        } else if !self.is_connected() {
-            self.message_error("not connected");
-            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
-            bail!("not connected");
-        } else {
-            trace!("UI => {}", my_name);
-        }
+    fn get_spi_bit_order(&self) -> BitOrder {
+        self.decoder.spi.bit_order
+    }
 
-        let amplitude = self.cfg.awg_amplitude.unwrap();
-        debug!("UI => {}::{}", my_name, amplitude);
+    fn set_spi_bit_order(&mut self, new_value: BitOrder) {
+        self.decoder.spi.bit_order = new_value;
+    }
 
-        self.message_info(format!("setting awg_running_amplitude={}", amplitude));
+    fn get_spi_word_size(&self) -> usize {
+        self.decoder.spi.word_size
+    }
 
-        self.tx.send(DevCommand::AwgAmplitude(amplitude)).unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.message_info("awg amplitude set");
-                Ok(())
-            }
-            Err(error) => {
-                self.message_error(error.clone());
-                bail!(error);
-            }
+    fn set_spi_word_size(&mut self, new_value: usize) {
+        if (1..=32).contains(&new_value) {
+            self.decoder.spi.word_size = new_value;
         }
     }
 
-    fn get_awg_amplitude(&self) -> f32 {
-        self.cfg.awg_amplitude.unwrap_or(0.0)
+    fn get_spi_cs_emulation(&self) -> bool {
+        self.decoder.spi.cs_emulation
     }
 
-    fn set_awg_amplitude(&mut self, new_value: f32) {
-        self.cfg.awg_amplitude = Some(new_value);
+    fn set_spi_cs_emulation(&mut self, new_value: bool) {
+        self.decoder.spi.cs_emulation = new_value;
     }
 
-    fn is_awg_amplitude_disabled(&self) -> bool {
-        !self.is_connected() || self.cfg.awg_amplitude.is_none()
+    fn is_spi_idle_gap_disabled(&self) -> bool {
+        !self.decoder.spi.cs_emulation
     }
 
-    // ------------
+    /// In microseconds.
+    fn get_spi_idle_gap(&self) -> f32 {
+        (self.decoder.spi.idle_gap * 1e6) as f32
+    }
 
-    fn on_awg_type(&mut self) {
-        let _err = self.send_awg_type();
+    fn set_spi_idle_gap(&mut self, new_value: f32) {
+        if new_value > 0.0 {
+            self.decoder.spi.idle_gap = new_value as f64 * 1e-6;
+        }
     }
 
-    fn send_awg_type(&mut self) -> anyhow::Result<()> {
-        let my_name = "send_awg_type()";
-        if self.initializing {
-            trace!("UI => {}, SKIPPED/INIT", my_name);
-            return Ok(());
-        } else if !self.is_connected() {
-            self.message_error("not connected");
-            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
-            bail!("not connected");
-        } else {
-            trace!("UI => {}", my_name);
+    fn get_spi_threshold(&self) -> f32 {
+        self.decoder.spi.threshold as f32
+    }
+
+    fn set_spi_threshold(&mut self, new_value: f32) {
+        self.decoder.spi.threshold = new_value as f64;
+    }
+
+    fn get_can_channel(&self) -> usize {
+        self.decoder.can.channel
+    }
+
+    fn set_can_channel(&mut self, new_value: usize) {
+        self.decoder.can.channel = new_value;
+    }
+
+    fn get_can_bitrate(&self) -> usize {
+        self.decoder.can.bitrate
+    }
+
+    fn set_can_bitrate(&mut self, new_value: usize) {
+        if new_value > 0 {
+            self.decoder.can.bitrate = new_value;
         }
+    }
 
-        let awg_type = self.cfg.awg_type.as_ref().unwrap().clone();
-        debug!("UI => {}::{}", my_name, awg_type.my_to_string());
+    /// In percent of the bit time.
+    fn get_can_sample_point(&self) -> f32 {
+        (self.decoder.can.sample_point * 100.0) as f32
+    }
 
-        self.message_info(format!(
-            "setting awg_running_type={}",
-            awg_type.my_to_string()
-        ));
+    fn set_can_sample_point(&mut self, new_value: f32) {
+        if (50.0..=95.0).contains(&new_value) {
+            self.decoder.can.sample_point = new_value as f64 / 100.0;
+        }
+    }
 
-        self.tx.send(DevCommand::AwgType(awg_type)).unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.message_info("awg type set");
-                Ok(())
-            }
-            Err(error) => {
-                self.message_error(error.clone());
-                bail!(error);
+    fn get_can_threshold(&self) -> f32 {
+        self.decoder.can.threshold as f32
+    }
+
+    fn set_can_threshold(&mut self, new_value: f32) {
+        self.decoder.can.threshold = new_value as f64;
+    }
+
+    fn export_decoded(&mut self, path: &Path) {
+        debug!("UI => export_decoded({})", path.display());
+        let decoded = match self.decoded.clone() {
+            Some(decoded) => decoded,
+            None => return,
+        };
+        match save_decoded(&decoded, path) {
+            Ok(()) => self.message_info(format!("decoded table exported to {}", path.display())),
+            Err(err) => self.message_error(format!("{:#}", err)),
+        }
+    }
+
+    fn is_export_decoded_disabled(&self) -> bool {
+        self.decoded.is_none()
+    }
+
+    // ------------
+
+    /// Loads the references saved in earlier sessions, hidden.
+    fn load_references(&mut self) {
+        for slot in 0..NUM_REFERENCES {
+            match Reference::load(slot) {
+                Ok(Some(reference)) => {
+                    let mut reference_slot = ReferenceSlot::new(reference, 1);
+                    reference_slot.shown = false;
+                    self.references[slot] = reference_slot;
+                }
+                Ok(None) => {}
+                Err(err) => self.message_error(format!(
+                    "failed to load {}: {:#}",
+                    reference_name(slot),
+                    err
+                )),
             }
         }
     }
 
-    fn get_awg_type(&self) -> AwgType {
-        self.cfg
-            .awg_type
-            .as_ref()
-            .unwrap_or(&AwgType::Square)
-            .clone()
+    /// Stores the capture on screen in `slot` and on disk.
+    fn save_reference(&mut self, slot: usize) {
+        let capture = match &self.capture {
+            Some(capture) => capture.clone(),
+            None => return,
+        };
+        let mut settings = CaptureSettings::from_config(&self.capture_cfg, &self.capture_channels);
+        if capture.trace(MATH_CHANNEL).is_some() {
+            settings.channels.push(ChannelSettings {
+                channel: MATH_CHANNEL,
+                coupling: String::new(),
+                probe: String::new(),
+                scale: String::new(),
+                volts_per_div: self.math.volts_per_div,
+                offset: self.math.offset,
+            });
+        }
+        let reference = Reference {
+            waveform: (*capture).clone(),
+            settings,
+        };
+
+        let name = reference_name(slot);
+        debug!("UI => save_reference({})", name);
+        match reference.save(slot) {
+            Ok(()) => self.message_info(format!("saved capture to {}", name)),
+            Err(err) => self.message_error(format!("failed to save {}: {:#}", name, err)),
+        }
+        self.references[slot] = ReferenceSlot::new(reference, self.references[slot].channel);
     }
 
-    fn set_awg_type(&mut self, new_value: AwgType) {
-        self.cfg.awg_type = Some(new_value);
+    fn clear_reference(&mut self, slot: usize) {
+        let name = reference_name(slot);
+        debug!("UI => clear_reference({})", name);
+        if let Err(err) = Reference::delete(slot) {
+            self.message_error(format!("failed to delete {}: {:#}", name, err));
+        }
+        self.references[slot] = ReferenceSlot::default();
     }
 
-    fn is_awg_type_disabled(&self) -> bool {
-        !self.is_connected() || self.cfg.awg_type.is_none()
+    fn is_save_reference_disabled(&self) -> bool {
+        self.capture.is_none()
+    }
+
+    fn is_reference_disabled(&self, slot: usize) -> bool {
+        self.references[slot].reference.is_none()
+    }
+
+    fn get_reference_shown(&self, slot: usize) -> bool {
+        self.references[slot].shown
+    }
+
+    fn set_reference_shown(&mut self, slot: usize, new_value: bool) {
+        self.references[slot].shown = new_value;
+    }
+
+    fn get_reference_channel(&self, slot: usize) -> usize {
+        self.references[slot].channel
+    }
+
+    fn set_reference_channel(&mut self, slot: usize, new_value: usize) {
+        self.references[slot].channel = new_value;
+    }
+
+    fn get_reference_scale(&self, slot: usize) -> f32 {
+        self.references[slot].volts_per_div as f32
+    }
+
+    fn set_reference_scale(&mut self, slot: usize, new_value: f32) {
+        if new_value > 0.0 {
+            self.references[slot].volts_per_div = new_value as f64;
+        }
+    }
+
+    fn get_reference_offset(&self, slot: usize) -> f32 {
+        self.references[slot].offset as f32
+    }
+
+    fn set_reference_offset(&mut self, slot: usize, new_value: f32) {
+        self.references[slot].offset = new_value as f64;
     }
 
     // ------------
 
-    fn on_awg_offset(&mut self) {
-        let _err = self.send_awg_offset();
+    fn exported_capture(&self) -> Option<Capture<'_>> {
+        Some(Capture {
+            waveform: self.capture.as_deref()?,
+            raw: self.capture_raw.as_deref()?,
+        })
     }
 
-    fn send_awg_offset(&mut self) -> anyhow::Result<()> {
-        let my_name = "send_awg_offset()";
-        if self.initializing {
-            trace!("UI => {}, SKIPPED/INIT", my_name);
-            return Ok(());
-        } else if !self.is_connected() {
-            self.message_error("not connected");
-            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
-            bail!("not connected");
-        } else {
-            trace!("UI => {}", my_name);
+    fn export_csv(&mut self, path: &Path) {
+        debug!("UI => export_csv({})", path.display());
+        let result = match self.exported_capture() {
+            Some(capture) => save_csv(&capture, self.export_raw, path),
+            None => return,
+        };
+        self.on_exported(path, result);
+    }
+
+    fn export_wav(&mut self, path: &Path) {
+        debug!("UI => export_wav({})", path.display());
+        let result = match self.exported_capture() {
+            Some(capture) => save_wav(&capture, self.wav_format, self.wav_normalized, path),
+            None => return,
+        };
+        self.on_exported(path, result);
+    }
+
+    fn export_sigrok(&mut self, path: &Path) {
+        debug!("UI => export_sigrok({})", path.display());
+        let result = match self.exported_capture() {
+            Some(capture) => save_sigrok(&capture, path),
+            None => return,
+        };
+        self.on_exported(path, result);
+    }
+
+    fn export_png(&mut self, path: &Path) {
+        debug!("UI => export_png({})", path.display());
+        let result = save_png(self, self.image_width, self.image_height, path);
+        self.on_exported(path, result);
+    }
+
+    fn export_svg(&mut self, path: &Path) {
+        debug!("UI => export_svg({})", path.display());
+        let result = save_svg(self, self.image_width, self.image_height, path);
+        self.on_exported(path, result);
+    }
+
+    fn on_exported(&mut self, path: &Path, result: anyhow::Result<()>) {
+        match result {
+            Ok(()) => self.message_info(format!("capture exported to {}", path.display())),
+            Err(err) => self.message_error(format!("{:#}", err)),
         }
+    }
 
-        let offset = self.cfg.awg_offset.unwrap();
-        debug!("UI => {}::{}", my_name, offset);
+    fn is_export_disabled(&self) -> bool {
+        self.capture_raw.is_none()
+    }
 
-        self.message_info(format!("setting awg_offset={}", offset));
+    /// Connects, captures once and saves the display to `path`, as SVG if
+    /// it ends in `.svg` and as PNG otherwise, then quits. Any failed
+    /// request on the way exits with an error.
+    fn save_image_on_launch(&mut self, path: PathBuf) {
+        self.save_image = Some(path);
+        self.connect();
+    }
 
-        self.tx.send(DevCommand::AwgOffset(offset)).unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.message_info("awg offset set");
-                Ok(())
+    fn continue_save_image(&mut self, failed: bool) {
+        let path = match &self.save_image {
+            Some(path) => path.clone(),
+            None => return,
+        };
+        if failed || !self.is_connected() {
+            error!("UI => not saving {}, see above", path.display());
+            std::process::exit(1);
+        }
+        if self.capture.is_none() {
+            if !self.is_capture_disabled() {
+                self.capture();
             }
-            Err(error) => {
-                self.message_error(error.clone());
-                bail!(error);
+            return;
+        }
+
+        self.save_image = None;
+        let is_svg = path.extension().and_then(|ext| ext.to_str()) == Some("svg");
+        let result = if is_svg {
+            save_svg(self, self.image_width, self.image_height, &path)
+        } else {
+            save_png(self, self.image_width, self.image_height, &path)
+        };
+        match result {
+            Ok(()) => {
+                info!("UI => display saved to {}", path.display());
+                Application::global().quit();
+            }
+            Err(err) => {
+                error!("UI => {:#}", err);
+                std::process::exit(1);
             }
         }
     }
 
-    fn get_awg_offset(&self) -> f32 {
-        self.cfg.awg_offset.unwrap_or(0.0)
+    fn get_export_raw(&self) -> bool {
+        self.export_raw
     }
 
-    fn set_awg_offset(&mut self, new_value: f32) {
-        self.cfg.awg_offset = Some(new_value);
+    fn set_export_raw(&mut self, new_value: bool) {
+        self.export_raw = new_value;
     }
 
-    fn is_awg_offset_disabled(&self) -> bool {
-        !self.is_connected() || self.cfg.awg_offset.is_none()
+    fn get_wav_format(&self) -> WavFormat {
+        self.wav_format
+    }
+
+    fn set_wav_format(&mut self, new_value: WavFormat) {
+        self.wav_format = new_value;
+    }
+
+    fn get_wav_normalized(&self) -> bool {
+        self.wav_normalized
+    }
+
+    fn set_wav_normalized(&mut self, new_value: bool) {
+        self.wav_normalized = new_value;
+    }
+
+    fn get_image_width(&self) -> usize {
+        self.image_width
+    }
+
+    fn set_image_width(&mut self, new_value: usize) {
+        self.image_width = new_value.clamp(MIN_IMAGE_SIZE, MAX_IMAGE_SIZE);
+    }
+
+    fn get_image_height(&self) -> usize {
+        self.image_height
+    }
+
+    fn set_image_height(&mut self, new_value: usize) {
+        self.image_height = new_value.clamp(MIN_IMAGE_SIZE, MAX_IMAGE_SIZE);
     }
 
     // ------------
 
-    fn on_awg_duty_square(&mut self) {
-        let _err = self.send_awg_duty_square();
+    fn on_device_function(&mut self) {
+        let _err = self.send_device_function();
     }
 
-    fn send_awg_duty_square(&mut self) -> anyhow::Result<()> {
-        let my_name = "send_awg_duty_square()";
+    fn send_device_function(&mut self) -> anyhow::Result<()> {
+        let my_name = "send_device_function()".to_string();
         if self.initializing {
             trace!("UI => {}, SKIPPED/INIT", my_name);
             return Ok(());
@@ -1253,44 +1846,45 @@ impl HantekState {
             trace!("UI => {}", my_name);
         }
 
-        let duty = self.cfg.awg_duty_square.unwrap();
-        debug!("UI => {}::{}", my_name, duty);
+        let device_function = self.cfg.device_function.as_ref().unwrap().clone();
+        debug!("UI => {}::{}", my_name, device_function.my_to_string());
 
-        self.message_info(format!("setting awg_duty_square={}", duty));
+        self.message_info(format!(
+            "setting device_function={}",
+            device_function.my_to_string(),
+        ));
 
-        self.tx.send(DevCommand::AwgDutySquare(duty)).unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.message_info("awg duty square set");
-                Ok(())
-            }
-            Err(error) => {
-                self.message_error(error.clone());
-                bail!(error);
-            }
-        }
+        self.send(
+            DevCommand::DeviceFunction(device_function),
+            "device function set",
+        );
+        Ok(())
     }
 
-    fn get_awg_duty_square(&self) -> f32 {
-        self.cfg.awg_duty_square.unwrap_or(0.0)
+    fn is_device_function_disabled(&self) -> bool {
+        !self.is_connected() || self.cfg.device_function.is_none()
     }
 
-    fn set_awg_duty_square(&mut self, new_value: f32) {
-        self.cfg.awg_duty_square = Some(new_value);
+    fn get_device_function(&self) -> DeviceFunction {
+        self.cfg
+            .device_function
+            .as_ref()
+            .unwrap_or(&DeviceFunction::Scope)
+            .clone()
     }
 
-    fn is_awg_duty_square_disabled(&self) -> bool {
-        !self.is_connected() || self.cfg.awg_duty_square.is_none()
+    fn set_device_function(&mut self, new_value: DeviceFunction) {
+        self.cfg.device_function = Some(new_value);
     }
 
     // ------------
 
-    fn on_awg_duty_ramp(&mut self) {
-        let _err = self.send_awg_duty_ramp();
+    fn on_running(&mut self) {
+        let _err = self.send_running();
     }
 
-    fn send_awg_duty_ramp(&mut self) -> anyhow::Result<()> {
-        let my_name = "send_awg_duty_ramp()";
+    fn send_running(&mut self) -> anyhow::Result<()> {
+        let my_name = "send_running()";
         if self.initializing {
             trace!("UI => {}, SKIPPED/INIT", my_name);
             return Ok(());
@@ -1298,48 +1892,51 @@ impl HantekState {
             self.message_error("not connected");
             error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
             bail!("not connected");
+        } else if self.is_running_disabled() {
+            trace!("UI => {}, SKIPPED/NO_STATE", my_name);
         } else {
             trace!("UI => {}", my_name);
         }
 
-        let duty = self.cfg.awg_duty_ramp.unwrap();
-        debug!("UI => {}::{}", my_name, duty);
-
-        self.message_info(format!("setting awg_duty_ramp={}", duty));
+        let new_status = self.cfg.running_status.as_ref().unwrap().clone();
+        debug!("UI => {}::{}", my_name, new_status.my_to_string());
 
-        self.tx.send(DevCommand::AwgDutyRamp(duty)).unwrap();
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.message_info("awg duty ramp set");
-                Ok(())
-            }
-            Err(error) => {
-                self.message_error(error.clone());
-                bail!(error);
-            }
-        }
+        let done = match new_status {
+            RunningStatus::Start => "running",
+            RunningStatus::Stop => "stopped",
+        };
+        self.send(DevCommand::ScopeRunning(new_status), done);
+        Ok(())
     }
 
-    fn get_awg_duty_ramp(&self) -> f32 {
-        self.cfg.awg_duty_ramp.unwrap_or(0.0)
+    fn get_running(&self) -> bool {
+        self.cfg
+            .running_status
+            .as_ref()
+            .unwrap_or(&RunningStatus::Stop)
+            .is_start()
     }
 
-    fn set_awg_duty_ramp(&mut self, new_value: f32) {
-        self.cfg.awg_duty_ramp = Some(new_value);
+    fn set_running(&mut self, new_value: bool) {
+        self.cfg.running_status = Some(match new_value {
+            true => RunningStatus::Start,
+            false => RunningStatus::Stop,
+        });
     }
 
-    fn is_awg_duty_ramp_disabled(&self) -> bool {
-        !self.is_connected() || self.cfg.awg_duty_ramp.is_none()
+    fn is_running_disabled(&self) -> bool {
+        !self.is_connected() || self.cfg.running_status.is_none()
     }
 
     // ------------
 
-    fn on_awg_duty_trap(&mut self) {
-        let _err = self.send_awg_duty_trap();
+    fn on_coupling(&mut self, channel: usize) {
+        self.reset_channel_measurements(channel);
+        let _err = self.send_coupling(channel);
     }
 
-    fn send_awg_duty_trap(&mut self) -> anyhow::Result<()> {
-        let my_name = "send_awg_duty_trap()";
+    fn send_coupling(&mut self, channel: usize) -> anyhow::Result<()> {
+        let my_name = format!("send_coupling(channel={})", channel);
         if self.initializing {
             trace!("UI => {}, SKIPPED/INIT", my_name);
             return Ok(());
@@ -1351,203 +1948,1630 @@ impl HantekState {
             trace!("UI => {}", my_name);
         }
 
-        let duty = self.cfg.awg_duty_trap.as_ref().unwrap().clone();
-        debug!("UI => {}::{}", my_name, duty);
+        let coupling = self.cfg.channel_coupling[&channel]
+            .as_ref()
+            .unwrap()
+            .clone();
+        debug!("UI => {}::{}", my_name, coupling.my_to_string());
 
         self.message_info(format!(
-            "setting awg_duty_trap={}/{}/{}",
-            duty.high, duty.low, duty.rise
+            "setting coupling={}, channel={}",
+            coupling.my_to_string(),
+            channel
         ));
-        self.tx
-            .send(DevCommand::AwgDutyTrap(duty.high, duty.low, duty.rise))
-            .unwrap();
 
-        match self.rx.recv().unwrap() {
-            Ok(_) => {
-                self.message_info("awg duty trap set");
-                Ok(())
-            }
-            Err(error) => {
-                self.message_error(error.clone());
-                bail!(error);
-            }
-        }
+        self.send(DevCommand::Coupling(channel, coupling), "coupling set");
+        Ok(())
     }
 
-    fn get_awg_duty_trap_high(&self) -> f32 {
-        self.cfg
-            .awg_duty_trap
-            .as_ref()
-            .unwrap_or(&TrapDuty::ZERO)
+    fn is_coupling_disabled(&self, channel: usize) -> bool {
+        !self.is_connected() || self.cfg.channel_coupling[&channel].is_none()
+    }
+
+    fn get_coupling(&self, channel: usize) -> Coupling {
+        self.cfg.channel_coupling[&channel]
+            .as_ref()
+            .unwrap_or(&Coupling::AC)
             .clone()
-            .high
     }
 
-    fn get_awg_duty_trap_low(&self) -> f32 {
-        self.cfg
-            .awg_duty_trap
+    fn set_coupling(&mut self, channel: usize, new_value: Coupling) {
+        self.cfg.channel_coupling.insert(channel, Some(new_value));
+    }
+
+    // ------------
+
+    fn on_probe(&mut self, channel: usize) {
+        self.reset_channel_measurements(channel);
+        let _err = self.send_probe(channel);
+    }
+
+    fn send_probe(&mut self, channel: usize) -> anyhow::Result<()> {
+        let my_name = format!("send_probe(channel={})", channel);
+        if self.initializing {
+            trace!("UI => {}, SKIPPED/INIT", my_name);
+            return Ok(());
+        } else if !self.is_connected() {
+            self.message_error("not connected");
+            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
+            bail!("not connected");
+        } else {
+            trace!("UI => {}", my_name);
+        }
+
+        let probe = self.cfg.channel_probe[&channel].as_ref().unwrap().clone();
+        debug!("UI => {}::{}", my_name, probe.my_to_string());
+
+        self.message_info(format!(
+            "setting probe={}, channel={}",
+            probe.my_to_string(),
+            channel
+        ));
+
+        self.send(DevCommand::Probe(channel, probe), "probe set");
+        Ok(())
+    }
+
+    fn is_probe_disabled(&self, channel: usize) -> bool {
+        !self.is_connected() || self.cfg.channel_probe[&channel].is_none()
+    }
+
+    fn get_probe(&self, channel: usize) -> Probe {
+        self.cfg.channel_probe[&channel]
             .as_ref()
-            .unwrap_or(&TrapDuty::ZERO)
+            .unwrap_or(&Probe::X1)
             .clone()
-            .low
     }
 
-    fn get_awg_duty_trap_rise(&self) -> f32 {
-        self.cfg
-            .awg_duty_trap
+    fn set_probe(&mut self, channel: usize, new_value: Probe) {
+        self.cfg.channel_probe.insert(channel, Some(new_value));
+    }
+
+    // ------------
+
+    fn on_scale(&mut self, channel: usize) {
+        self.reset_channel_measurements(channel);
+        let _err = self.send_scale(channel);
+    }
+
+    fn send_scale(&mut self, channel: usize) -> anyhow::Result<()> {
+        let my_name = format!("send_scale(channel={})", channel);
+        if self.initializing {
+            trace!("UI => {}, SKIPPED/INIT", my_name);
+            return Ok(());
+        } else if !self.is_connected() {
+            self.message_error("not connected");
+            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
+            bail!("not connected");
+        } else {
+            trace!("UI => {}", my_name);
+        }
+
+        let scale = self.cfg.channel_scale[&channel].as_ref().unwrap().clone();
+        debug!("UI => {}::{}", my_name, scale.my_to_string());
+
+        self.message_info(format!(
+            "setting scale={}, channel={}",
+            scale.my_to_string(),
+            channel
+        ));
+
+        self.send(DevCommand::Scale(channel, scale), "scale set");
+        Ok(())
+    }
+
+    fn is_scale_disabled(&self, channel: usize) -> bool {
+        !self.is_connected() || self.cfg.channel_scale[&channel].is_none()
+    }
+
+    fn get_scale(&self, channel: usize) -> Scale {
+        self.cfg.channel_scale[&channel]
             .as_ref()
-            .unwrap_or(&TrapDuty::ZERO)
+            .unwrap_or(&Scale::mv10)
             .clone()
-            .rise
     }
 
-    fn set_awg_duty_trap_high(&mut self, new_value: f32) {
-        let low = self.get_awg_duty_trap_low();
-        let rise = self.get_awg_duty_trap_rise();
-        let high = new_value;
-        self.cfg.awg_duty_trap = Some(TrapDuty { high, low, rise });
+    fn set_scale0(&mut self, channel: usize, new_value: Scale) {
+        self.cfg.channel_scale.insert(channel, Some(new_value));
     }
 
-    fn set_awg_duty_trap_low(&mut self, new_value: f32) {
-        let low = new_value;
-        let rise = self.get_awg_duty_trap_rise();
-        let high = self.get_awg_duty_trap_high();
-        self.cfg.awg_duty_trap = Some(TrapDuty { high, low, rise });
+    // ------------
+
+    fn on_offset(&mut self, channel: usize) {
+        let _err = self.send_offset(channel);
     }
 
-    fn set_awg_duty_trap_rise(&mut self, new_value: f32) {
-        let low = self.get_awg_duty_trap_low();
-        let rise = new_value;
-        let high = self.get_awg_duty_trap_high();
-        self.cfg.awg_duty_trap = Some(TrapDuty { high, low, rise });
+    fn send_offset(&mut self, channel: usize) -> anyhow::Result<()> {
+        let my_name = format!("send_offset(channel={})", channel);
+        if self.initializing {
+            trace!("UI => {}, SKIPPED/INIT", my_name);
+            return Ok(());
+        } else if !self.is_connected() {
+            self.message_error("not connected");
+            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
+            bail!("not connected");
+        } else {
+            trace!("UI => {}", my_name);
+        }
+
+        let offset = self.cfg.channel_offset[&channel].unwrap();
+        debug!("UI => {}::{}", my_name, offset);
+
+        self.message_info(format!("setting offset={}, channel={}", offset, channel));
+
+        self.send(DevCommand::Offset(channel, offset), "offset set");
+        Ok(())
     }
 
-    fn is_awg_duty_trap_disabled(&self) -> bool {
-        !self.is_connected() || self.cfg.awg_duty_trap.is_none()
+    fn is_offset_disabled(&self, channel: usize) -> bool {
+        !self.is_connected() || self.cfg.channel_offset[&channel].is_none()
     }
-}
 
-fn build_connect_panel() -> impl Widget<HantekState> {
-    let connect_button = Button::new("Connect")
-        .on_click(|_, state: &mut HantekState, _| state.connect())
-        .disabled_if(|state: &HantekState, _| state.is_connected());
+    fn get_offset(&self, channel: usize) -> f32 {
+        self.cfg.channel_offset[&channel].unwrap_or(0.0f32)
+    }
 
-    let disconnect_button = Button::new("Disconnect")
-        .on_click(|_, state: &mut HantekState, _| state.disconnect())
-        .disabled_if(|state: &HantekState, _| !state.is_connected());
+    fn set_offset(&mut self, channel: usize, new_value: f32) {
+        self.cfg.channel_offset.insert(channel, Some(new_value));
+    }
 
-    let device_function = DropdownSelect::new(Vector::from(DeviceFunction::my_options()))
-        .lens(lens_of(
-            move |state: &HantekState| state.get_device_function(),
-            move |state: &mut HantekState, new_value| state.set_device_function(new_value),
-        ))
-        .on_change(move |_, _, data_mut: &mut HantekState, _| data_mut.on_device_function())
-        .disabled_if(move |state: &HantekState, _| state.is_device_function_disabled());
+    // ------------
 
-    Flex::column()
-        .with_flex_child(
-            Flex::row()
-                .with_flex_child(connect_button, 1.0)
-                .with_flex_spacer(0.2)
-                .with_flex_child(disconnect_button, 1.0)
-                .with_flex_spacer(0.2),
-            1.0,
-        )
-        .with_flex_spacer(0.4)
-        .with_flex_child(device_function, 1.0)
-        .cross_axis_alignment(CrossAxisAlignment::Start)
-}
+    fn on_channel_enable(&mut self, channel: usize) {
+        self.reset_channel_measurements(channel);
+        let _err = self.send_channel_enable(channel);
+    }
 
-fn build_channel_panel(channel: usize) -> impl Widget<HantekState> {
-    let enabled_switch = Switch::new()
-        .lens(lens_of(
-            move |state: &HantekState| state.get_enabled_channel(channel),
-            move |state, new_value| state.set_enabled_channel(channel, new_value),
-        ))
-        .disabled_if(move |state: &HantekState, _| state.is_channel_enable_disabled(channel))
-        .on_change(move |_, _, data_mut: &mut HantekState, _| data_mut.on_channel_enable(channel));
-    let enabled = Flex::row()
-        .with_flex_child(label("Enabled"), 1.0)
-        .with_flex_child(enabled_switch, 1.0)
-        .align_horizontal(UnitPoint::CENTER)
-        .padding(5.0);
+    fn send_channel_enable(&mut self, channel: usize) -> anyhow::Result<()> {
+        let my_name = format!("send_channel_enable(channel={})", channel);
+        if self.initializing {
+            trace!("UI => {}, SKIPPED/INIT", my_name);
+            return Ok(());
+        } else if !self.is_connected() {
+            self.message_error("not connected");
+            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
+            bail!("not connected");
+        } else {
+            trace!("UI => {}", my_name);
+        }
+
+        let enabled = self.cfg.enabled_channels[&channel].unwrap();
+        debug!("UI => {}::{}", my_name, enabled);
+
+        self.message_info(format!(
+            "setting channel status, channel={}, status={}",
+            channel,
+            match enabled {
+                true => "enabled",
+                false => "disabled",
+            }
+        ));
+
+        self.send(
+            DevCommand::ChannelEnable(channel, enabled),
+            "channel status set",
+        );
+        Ok(())
+    }
+
+    fn is_channel_enable_disabled(&self, channel: usize) -> bool {
+        !self.is_connected() || self.cfg.enabled_channels[&channel].is_none()
+    }
+
+    fn get_enabled_channel(&self, channel: usize) -> bool {
+        self.cfg.enabled_channels[&channel].unwrap_or(false)
+    }
+
+    fn set_enabled_channel(&mut self, channel: usize, new_value: bool) {
+        self.cfg.enabled_channels.insert(channel, Some(new_value));
+    }
+
+    // ------------
+
+    fn on_bw_limit(&mut self, channel: usize) {
+        let _err = self.send_bw_limit(channel);
+    }
+
+    fn send_bw_limit(&mut self, channel: usize) -> anyhow::Result<()> {
+        let my_name = format!("send_bw_limit(channel={})", channel);
+        if self.initializing {
+            trace!("UI => {}, SKIPPED/INIT", my_name);
+            return Ok(());
+        } else if !self.is_connected() {
+            self.message_error("not connected");
+            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
+            bail!("not connected");
+        } else {
+            trace!("UI => {}", my_name);
+        }
+
+        let enabled = self.cfg.channel_bandwidth_limit[&channel].unwrap();
+        debug!("UI => {}::{}", my_name, enabled);
+
+        self.message_info(format!(
+            "setting channel bandwidth limit, channel={}, limit={}",
+            channel,
+            match enabled {
+                true => "enabled",
+                false => "disabled",
+            }
+        ));
+
+        self.send(
+            DevCommand::BwLimit(channel, enabled),
+            "channel bandwidth limit configuration set",
+        );
+        Ok(())
+    }
+
+    fn get_bw_limit(&self, channel: usize) -> bool {
+        self.cfg.channel_bandwidth_limit[&channel].unwrap_or(false)
+    }
+
+    fn set_bw_limit(&mut self, channel: usize, new_value: bool) {
+        self.cfg
+            .channel_bandwidth_limit
+            .insert(channel, Some(new_value));
+    }
+
+    fn is_bw_limit_enable_disabled(&self, channel: usize) -> bool {
+        !self.is_connected() || self.cfg.channel_bandwidth_limit[&channel].is_none()
+    }
+
+    // ------------
+
+    fn on_time_scale(&mut self) {
+        self.measurement_stats.clear();
+        let _err = self.send_time_scale();
+    }
+
+    fn send_time_scale(&mut self) -> anyhow::Result<()> {
+        let my_name = "send_time_scale()";
+        if self.initializing {
+            trace!("UI => {}, SKIPPED/INIT", my_name);
+            return Ok(());
+        } else if !self.is_connected() {
+            self.message_error("not connected");
+            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
+            bail!("not connected");
+        } else {
+            trace!("UI => {}", my_name);
+        }
+
+        let scale = self.cfg.time_scale.as_ref().unwrap().clone();
+        debug!("UI => {}::{}", my_name, scale.my_to_string());
+
+        self.message_info(format!("setting time_scale={}", scale.my_to_string()));
+
+        self.send(DevCommand::TimeScale(scale), "time scale set");
+        Ok(())
+    }
+
+    fn get_time_scale(&self) -> TimeScale {
+        self.cfg
+            .time_scale
+            .as_ref()
+            .unwrap_or(&TimeScale::ns5)
+            .clone()
+    }
+
+    fn set_time_scale(&mut self, new_value: TimeScale) {
+        self.cfg.time_scale = Some(new_value);
+    }
+
+    fn is_time_scale_disabled(&self) -> bool {
+        !self.is_connected() || self.cfg.time_scale.is_none()
+    }
+
+    // ------------
+
+    fn on_time_offset(&mut self) {
+        let _err = self.send_time_offset();
+    }
+
+    fn send_time_offset(&mut self) -> anyhow::Result<()> {
+        let my_name = "send_time_offset()";
+        if self.initializing {
+            trace!("UI => {}, SKIPPED/INIT", my_name);
+            return Ok(());
+        } else if !self.is_connected() {
+            self.message_error("not connected");
+            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
+            bail!("not connected");
+        } else {
+            trace!("UI => {}", my_name);
+        }
+
+        let time_offset = self.cfg.time_offset.unwrap();
+        debug!("UI => {}::{}", my_name, time_offset);
+
+        self.message_info(format!("setting time_offset={}", time_offset));
+
+        self.send(DevCommand::TimeOffset(time_offset), "time offset set");
+        Ok(())
+    }
+
+    fn get_time_offset(&self) -> f32 {
+        self.cfg.time_offset.unwrap_or(0.0)
+    }
+
+    fn set_time_offset(&mut self, new_value: f32) {
+        self.cfg.time_offset = Some(new_value);
+    }
+
+    fn is_time_offset_disabled(&self) -> bool {
+        !self.is_connected() || self.cfg.time_offset.is_none()
+    }
+
+    // ------------
+
+    fn on_trigger_source(&mut self) {
+        let _err = self.send_trigger_source();
+    }
+
+    fn send_trigger_source(&mut self) -> anyhow::Result<()> {
+        let my_name = "send_trigger_source()";
+        if self.initializing {
+            trace!("UI => {}, SKIPPED/INIT", my_name);
+            return Ok(());
+        } else if !self.is_connected() {
+            self.message_error("not connected");
+            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
+            bail!("not connected");
+        } else {
+            trace!("UI => {}", my_name);
+        }
+
+        let source = self.cfg.trigger_source_channel.unwrap();
+        debug!("UI => {}::{}", my_name, source);
+
+        self.message_info(format!("setting trigger_source_channel={}", source));
+
+        self.send(DevCommand::TriggerSource(source), "trigger source set");
+        Ok(())
+    }
+
+    fn get_trigger_source(&self) -> usize {
+        self.cfg.trigger_source_channel.unwrap_or(1usize)
+    }
+
+    fn set_trigger_source(&mut self, new_value: usize) {
+        self.cfg.trigger_source_channel = Some(new_value);
+    }
+
+    fn is_trigger_source_disabled(&self) -> bool {
+        !self.is_connected() || self.cfg.trigger_source_channel.is_none()
+    }
+
+    // ------------
+
+    fn on_trigger_mode(&mut self) {
+        let _err = self.send_trigger_mode();
+    }
+
+    fn send_trigger_mode(&mut self) -> anyhow::Result<()> {
+        let my_name = "send_trigger_mode()";
+        if self.initializing {
+            trace!("UI => {}, SKIPPED/INIT", my_name);
+            return Ok(());
+        } else if !self.is_connected() {
+            self.message_error("not connected");
+            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
+            bail!("not connected");
+        } else {
+            trace!("UI => {}", my_name);
+        }
+
+        let mode = self.cfg.trigger_mode.as_ref().unwrap().clone();
+        debug!("UI => {}::{}", my_name, mode.my_to_string());
+
+        self.message_info(format!("setting trigger_mode={}", mode.my_to_string()));
+
+        self.send(DevCommand::TriggerMode(mode), "trigger mode set");
+        Ok(())
+    }
+
+    fn get_trigger_mode(&self) -> TriggerMode {
+        self.cfg
+            .trigger_mode
+            .as_ref()
+            .unwrap_or(&TriggerMode::Auto)
+            .clone()
+    }
+
+    fn set_trigger_mode(&mut self, new_value: TriggerMode) {
+        self.cfg.trigger_mode = Some(new_value);
+    }
+
+    fn is_trigger_mode_disabled(&self) -> bool {
+        !self.is_connected() || self.cfg.trigger_mode.is_none()
+    }
+
+    // ------------
+
+    fn on_trigger_level(&mut self) {
+        let _err = self.send_trigger_level();
+    }
+
+    fn send_trigger_level(&mut self) -> anyhow::Result<()> {
+        let my_name = "send_trigger_level()";
+        if self.initializing {
+            trace!("UI => {}, SKIPPED/INIT", my_name);
+            return Ok(());
+        } else if !self.is_connected() {
+            self.message_error("not connected");
+            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
+            bail!("not connected");
+        } else {
+            trace!("UI => {}", my_name);
+        }
+
+        let level = self.cfg.trigger_level.unwrap();
+        debug!("UI => {}::{}", my_name, level);
+
+        self.message_info(format!("setting trigger_level={}", level));
+
+        self.send(DevCommand::TriggerLevel(level), "trigger level set");
+        Ok(())
+    }
+
+    fn get_trigger_level(&self) -> f32 {
+        self.cfg.trigger_level.unwrap_or(0.0)
+    }
+
+    fn set_trigger_level(&mut self, new_value: f32) {
+        self.cfg.trigger_level = Some(new_value);
+    }
+
+    fn is_trigger_level_disabled(&self) -> bool {
+        !self.is_connected() || self.cfg.trigger_level.is_none()
+    }
+
+    // -----------
+
+    fn on_num_captures(&mut self) {
+        let my_name = "on_num_captures()";
+        if self.initializing {
+            trace!("UI => {}, SKIPPED/INIT", my_name);
+            return;
+        } else {
+            trace!("UI => {}", my_name);
+        }
+
+        let num_captures = self.num_captures;
+        debug!("UI => {}::{}", my_name, num_captures);
+    }
+
+    fn get_num_captures(&self) -> usize {
+        self.num_captures
+    }
+
+    fn set_num_captures(&mut self, new_value: usize) {
+        self.num_captures = new_value;
+    }
+
+    fn is_num_captures_disabled(&self) -> bool {
+        !self.is_connected()
+    }
+
+    // ------------
+
+    fn on_awg_running(&mut self) {
+        let _err = self.send_awg_running();
+    }
+
+    fn send_awg_running(&mut self) -> anyhow::Result<()> {
+        let my_name = "send_awg_running()";
+        if self.initializing {
+            trace!("UI => {}, SKIPPED/INIT", my_name);
+            return Ok(());
+        } else if !self.is_connected() {
+            self.message_error("not connected");
+            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
+            bail!("not connected");
+        } else {
+            trace!("UI => {}", my_name);
+        }
+
+        let status = self.cfg.awg_running_status.as_ref().unwrap().clone();
+        debug!("UI => {}::{}", my_name, status.my_to_string());
+
+        self.message_info(format!(
+            "setting awg_running_status={}",
+            status.my_to_string()
+        ));
+
+        self.send(
+            DevCommand::AwgRunningStatus(status),
+            "awg running status set",
+        );
+        Ok(())
+    }
+
+    fn get_awg_running(&self) -> RunningStatus {
+        self.cfg
+            .awg_running_status
+            .as_ref()
+            .unwrap_or(&RunningStatus::Stop)
+            .clone()
+    }
+
+    fn set_awg_running(&mut self, new_value: RunningStatus) {
+        self.cfg.awg_running_status = Some(new_value);
+    }
+
+    fn is_awg_running_disabled(&self) -> bool {
+        !self.is_connected() || self.cfg.awg_running_status.is_none()
+    }
+
+    // ------------
+
+    fn on_awg_frequency(&mut self) {
+        let _err = self.send_awg_frequency();
+    }
+
+    fn send_awg_frequency(&mut self) -> anyhow::Result<()> {
+        let my_name = "send_awg_frequency()";
+        if self.initializing {
+            trace!("UI => {}, SKIPPED/INIT", my_name);
+            return Ok(());
+        } else if !self.is_connected() {
+            self.message_error("not connected");
+            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
+            bail!("not connected");
+        } else {
+            trace!("UI => {}", my_name);
+        }
+
+        let frequency = self.cfg.awg_frequency.unwrap();
+        debug!("UI => {}::{}", my_name, frequency);
+
+        self.message_info(format!("setting awg_running_frequency={}", frequency));
+
+        self.send(DevCommand::AwgFrequency(frequency), "awg frequency set");
+        Ok(())
+    }
+
+    fn get_awg_frequency(&self) -> f32 {
+        self.cfg.awg_frequency.unwrap_or(0.0)
+    }
+
+    fn set_awg_frequency(&mut self, new_value: f32) {
+        self.cfg.awg_frequency = Some(new_value);
+    }
+
+    fn is_awg_frequency_disabled(&self) -> bool {
+        !self.is_connected() || self.cfg.awg_frequency.is_none()
+    }
+
+    // ------------
+
+    fn on_awg_amplitude(&mut self) {
+        let _err = self.send_awg_amplitude();
+    }
+
+    fn send_awg_amplitude(&mut self) -> anyhow::Result<()> {
+        let my_name = "send_awg_amplitude()";
+        if self.initializing {
+            trace!("UI => {}, SKIPPED/INIT", my_name);
+            return Ok(());
+        } else if !self.is_connected() {
+            self.message_error("not connected");
+            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
+            bail!("not connected");
+        } else {
+            trace!("UI => {}", my_name);
+        }
+
+        let amplitude = self.cfg.awg_amplitude.unwrap();
+        debug!("UI => {}::{}", my_name, amplitude);
+
+        self.message_info(format!("setting awg_running_amplitude={}", amplitude));
+
+        self.send(DevCommand::AwgAmplitude(amplitude), "awg amplitude set");
+        Ok(())
+    }
+
+    fn get_awg_amplitude(&self) -> f32 {
+        self.cfg.awg_amplitude.unwrap_or(0.0)
+    }
+
+    fn set_awg_amplitude(&mut self, new_value: f32) {
+        self.cfg.awg_amplitude = Some(new_value);
+    }
+
+    fn is_awg_amplitude_disabled(&self) -> bool {
+        !self.is_connected() || self.cfg.awg_amplitude.is_none()
+    }
+
+    // ------------
+
+    fn on_awg_type(&mut self) {
+        let _err = self.send_awg_type();
+    }
+
+    fn send_awg_type(&mut self) -> anyhow::Result<()> {
+        let my_name = "send_awg_type()";
+        if self.initializing {
+            trace!("UI => {}, SKIPPED/INIT", my_name);
+            return Ok(());
+        } else if !self.is_connected() {
+            self.message_error("not connected");
+            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
+            bail!("not connected");
+        } else {
+            trace!("UI => {}", my_name);
+        }
+
+        let awg_type = self.cfg.awg_type.as_ref().unwrap().clone();
+        debug!("UI => {}::{}", my_name, awg_type.my_to_string());
+
+        self.message_info(format!(
+            "setting awg_running_type={}",
+            awg_type.my_to_string()
+        ));
+
+        self.send(DevCommand::AwgType(awg_type), "awg type set");
+        Ok(())
+    }
+
+    fn get_awg_type(&self) -> AwgType {
+        self.cfg
+            .awg_type
+            .as_ref()
+            .unwrap_or(&AwgType::Square)
+            .clone()
+    }
+
+    fn set_awg_type(&mut self, new_value: AwgType) {
+        self.cfg.awg_type = Some(new_value);
+    }
+
+    fn is_awg_type_disabled(&self) -> bool {
+        !self.is_connected() || self.cfg.awg_type.is_none()
+    }
+
+    // ------------
+
+    fn on_awg_offset(&mut self) {
+        let _err = self.send_awg_offset();
+    }
+
+    fn send_awg_offset(&mut self) -> anyhow::Result<()> {
+        let my_name = "send_awg_offset()";
+        if self.initializing {
+            trace!("UI => {}, SKIPPED/INIT", my_name);
+            return Ok(());
+        } else if !self.is_connected() {
+            self.message_error("not connected");
+            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
+            bail!("not connected");
+        } else {
+            trace!("UI => {}", my_name);
+        }
+
+        let offset = self.cfg.awg_offset.unwrap();
+        debug!("UI => {}::{}", my_name, offset);
+
+        self.message_info(format!("setting awg_offset={}", offset));
+
+        self.send(DevCommand::AwgOffset(offset), "awg offset set");
+        Ok(())
+    }
+
+    fn get_awg_offset(&self) -> f32 {
+        self.cfg.awg_offset.unwrap_or(0.0)
+    }
+
+    fn set_awg_offset(&mut self, new_value: f32) {
+        self.cfg.awg_offset = Some(new_value);
+    }
+
+    fn is_awg_offset_disabled(&self) -> bool {
+        !self.is_connected() || self.cfg.awg_offset.is_none()
+    }
+
+    // ------------
+
+    fn on_awg_duty_square(&mut self) {
+        let _err = self.send_awg_duty_square();
+    }
+
+    fn send_awg_duty_square(&mut self) -> anyhow::Result<()> {
+        let my_name = "send_awg_duty_square()";
+        if self.initializing {
+            trace!("UI => {}, SKIPPED/INIT", my_name);
+            return Ok(());
+        } else if !self.is_connected() {
+            self.message_error("not connected");
+            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
+            bail!("not connected");
+        } else {
+            trace!("UI => {}", my_name);
+        }
+
+        let duty = self.cfg.awg_duty_square.unwrap();
+        debug!("UI => {}::{}", my_name, duty);
+
+        self.message_info(format!("setting awg_duty_square={}", duty));
+
+        self.send(DevCommand::AwgDutySquare(duty), "awg duty square set");
+        Ok(())
+    }
+
+    fn get_awg_duty_square(&self) -> f32 {
+        self.cfg.awg_duty_square.unwrap_or(0.0)
+    }
+
+    fn set_awg_duty_square(&mut self, new_value: f32) {
+        self.cfg.awg_duty_square = Some(new_value);
+    }
+
+    fn is_awg_duty_square_disabled(&self) -> bool {
+        !self.is_connected() || self.cfg.awg_duty_square.is_none()
+    }
+
+    // ------------
+
+    fn on_awg_duty_ramp(&mut self) {
+        let _err = self.send_awg_duty_ramp();
+    }
+
+    fn send_awg_duty_ramp(&mut self) -> anyhow::Result<()> {
+        let my_name = "send_awg_duty_ramp()";
+        if self.initializing {
+            trace!("UI => {}, SKIPPED/INIT", my_name);
+            return Ok(());
+        } else if !self.is_connected() {
+            self.message_error("not connected");
+            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
+            bail!("not connected");
+        } else {
+            trace!("UI => {}", my_name);
+        }
+
+        let duty = self.cfg.awg_duty_ramp.unwrap();
+        debug!("UI => {}::{}", my_name, duty);
+
+        self.message_info(format!("setting awg_duty_ramp={}", duty));
+
+        self.send(DevCommand::AwgDutyRamp(duty), "awg duty ramp set");
+        Ok(())
+    }
+
+    fn get_awg_duty_ramp(&self) -> f32 {
+        self.cfg.awg_duty_ramp.unwrap_or(0.0)
+    }
+
+    fn set_awg_duty_ramp(&mut self, new_value: f32) {
+        self.cfg.awg_duty_ramp = Some(new_value);
+    }
+
+    fn is_awg_duty_ramp_disabled(&self) -> bool {
+        !self.is_connected() || self.cfg.awg_duty_ramp.is_none()
+    }
+
+    // ------------
+
+    fn on_awg_duty_trap(&mut self) {
+        let _err = self.send_awg_duty_trap();
+    }
+
+    fn send_awg_duty_trap(&mut self) -> anyhow::Result<()> {
+        let my_name = "send_awg_duty_trap()";
+        if self.initializing {
+            trace!("UI => {}, SKIPPED/INIT", my_name);
+            return Ok(());
+        } else if !self.is_connected() {
+            self.message_error("not connected");
+            error!("UI => {}, SKIPPED/NOT_CONNECTED", my_name);
+            bail!("not connected");
+        } else {
+            trace!("UI => {}", my_name);
+        }
+
+        let duty = self.cfg.awg_duty_trap.as_ref().unwrap().clone();
+        debug!("UI => {}::{}", my_name, duty);
+
+        self.message_info(format!(
+            "setting awg_duty_trap={}/{}/{}",
+            duty.high, duty.low, duty.rise
+        ));
+        self.send(
+            DevCommand::AwgDutyTrap(duty.high, duty.low, duty.rise),
+            "awg duty trap set",
+        );
+        Ok(())
+    }
+
+    fn get_awg_duty_trap_high(&self) -> f32 {
+        self.cfg
+            .awg_duty_trap
+            .as_ref()
+            .unwrap_or(&TrapDuty::ZERO)
+            .clone()
+            .high
+    }
+
+    fn get_awg_duty_trap_low(&self) -> f32 {
+        self.cfg
+            .awg_duty_trap
+            .as_ref()
+            .unwrap_or(&TrapDuty::ZERO)
+            .clone()
+            .low
+    }
+
+    fn get_awg_duty_trap_rise(&self) -> f32 {
+        self.cfg
+            .awg_duty_trap
+            .as_ref()
+            .unwrap_or(&TrapDuty::ZERO)
+            .clone()
+            .rise
+    }
+
+    fn set_awg_duty_trap_high(&mut self, new_value: f32) {
+        let low = self.get_awg_duty_trap_low();
+        let rise = self.get_awg_duty_trap_rise();
+        let high = new_value;
+        self.cfg.awg_duty_trap = Some(TrapDuty { high, low, rise });
+    }
+
+    fn set_awg_duty_trap_low(&mut self, new_value: f32) {
+        let low = new_value;
+        let rise = self.get_awg_duty_trap_rise();
+        let high = self.get_awg_duty_trap_high();
+        self.cfg.awg_duty_trap = Some(TrapDuty { high, low, rise });
+    }
+
+    fn set_awg_duty_trap_rise(&mut self, new_value: f32) {
+        let low = self.get_awg_duty_trap_low();
+        let rise = new_value;
+        let high = self.get_awg_duty_trap_high();
+        self.cfg.awg_duty_trap = Some(TrapDuty { high, low, rise });
+    }
+
+    fn is_awg_duty_trap_disabled(&self) -> bool {
+        !self.is_connected() || self.cfg.awg_duty_trap.is_none()
+    }
+}
+
+fn build_connect_panel() -> impl Widget<HantekState> {
+    let connect_button = Button::new("Connect")
+        .on_click(|_, state: &mut HantekState, _| state.connect())
+        .disabled_if(|state: &HantekState, _| state.is_connect_disabled());
+
+    let disconnect_button = Button::new("Disconnect")
+        .on_click(|_, state: &mut HantekState, _| state.disconnect())
+        .disabled_if(|state: &HantekState, _| state.is_disconnect_disabled());
+
+    let pending = Label::dynamic(|state: &HantekState, _| match state.num_pending() {
+        0 => "".to_string(),
+        n => format!("{} pending", n),
+    });
+
+    let save_setup_button = Button::new("Save Setup...").on_click(|ctx, _, _| {
+        let options = FileDialogOptions::new()
+            .allowed_types(vec![SETUP_FILE])
+            .default_type(SETUP_FILE)
+            .default_name("setup.json")
+            .accept_command(SAVE_SETUP);
+        ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+    });
+
+    let load_setup_button = Button::new("Load Setup...")
+        .on_click(|ctx, _, _| {
+            let options = FileDialogOptions::new()
+                .allowed_types(vec![SETUP_FILE])
+                .default_type(SETUP_FILE)
+                .accept_command(LOAD_SETUP);
+            ctx.submit_command(commands::SHOW_OPEN_PANEL.with(options));
+        })
+        .disabled_if(|state: &HantekState, _| state.is_load_setup_disabled());
+
+    let factory_defaults = Checkbox::new("Factory defaults on connect")
+        .lens(lens_of(
+            |state: &HantekState| state.get_factory_defaults(),
+            |state: &mut HantekState, new_value| state.set_factory_defaults(new_value),
+        ))
+        .disabled_if(|state: &HantekState, _| state.is_connected());
+
+    let device_function = DropdownSelect::new(Vector::from(DeviceFunction::my_options()))
+        .lens(lens_of(
+            move |state: &HantekState| state.get_device_function(),
+            move |state: &mut HantekState, new_value| state.set_device_function(new_value),
+        ))
+        .on_change(move |_, _, data_mut: &mut HantekState, _| data_mut.on_device_function())
+        .disabled_if(move |state: &HantekState, _| state.is_device_function_disabled());
+
+    Flex::column()
+        .with_flex_child(
+            Flex::row()
+                .with_flex_child(connect_button, 1.0)
+                .with_flex_spacer(0.2)
+                .with_flex_child(disconnect_button, 1.0)
+                .with_flex_spacer(0.2)
+                .with_flex_child(pending, 1.0),
+            1.0,
+        )
+        .with_flex_spacer(0.4)
+        .with_flex_child(factory_defaults, 1.0)
+        .with_flex_spacer(0.4)
+        .with_flex_child(device_function, 1.0)
+        .with_flex_spacer(0.4)
+        .with_flex_child(
+            Flex::row()
+                .with_flex_child(save_setup_button, 1.0)
+                .with_flex_spacer(0.2)
+                .with_flex_child(load_setup_button, 1.0),
+            1.0,
+        )
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+}
+
+fn build_channel_panel(channel: usize) -> impl Widget<HantekState> {
+    let enabled_switch = Switch::new()
+        .lens(lens_of(
+            move |state: &HantekState| state.get_enabled_channel(channel),
+            move |state, new_value| state.set_enabled_channel(channel, new_value),
+        ))
+        .disabled_if(move |state: &HantekState, _| state.is_channel_enable_disabled(channel))
+        .on_change(move |_, _, data_mut: &mut HantekState, _| data_mut.on_channel_enable(channel));
+    let enabled = Flex::row()
+        .with_flex_child(
+            label_pending("Enabled", Control::ChannelEnable(channel)),
+            1.0,
+        )
+        .with_flex_child(enabled_switch, 1.0)
+        .align_horizontal(UnitPoint::CENTER)
+        .padding(5.0);
+
+    let coupling_options = DropdownSelect::new(Vector::from(Coupling::my_options()))
+        .lens(lens_of(
+            move |state: &HantekState| state.get_coupling(channel),
+            move |state: &mut HantekState, new_value| state.set_coupling(channel, new_value),
+        ))
+        .on_change(move |_, _, data_mut: &mut HantekState, _| data_mut.on_coupling(channel))
+        .disabled_if(move |state: &HantekState, _| state.is_coupling_disabled(channel));
+    let coupling = Flex::row()
+        .with_flex_child(label_pending("Coupling", Control::Coupling(channel)), 1.0)
+        .with_flex_child(coupling_options, 1.0);
+
+    let probe_options = DropdownSelect::new(Vector::from(Probe::my_options()))
+        .lens(lens_of(
+            move |state: &HantekState| state.get_probe(channel),
+            move |state, new_value| state.set_probe(channel, new_value),
+        ))
+        .on_change(move |_, _, data_mut: &mut HantekState, _| data_mut.on_probe(channel))
+        .disabled_if(move |state: &HantekState, _| state.is_probe_disabled(channel));
+    let probe = Flex::row()
+        .with_flex_child(label_pending("Probe", Control::Probe(channel)), 1.0)
+        .with_flex_child(probe_options, 1.0);
+
+    let scale_options = DropdownSelect::new(Vector::from(Scale::my_options()))
+        .lens(lens_of(
+            move |state: &HantekState| state.get_scale(channel),
+            move |state, new_value| state.set_scale0(channel, new_value),
+        ))
+        .on_change(move |_, _, data_mut: &mut HantekState, _| data_mut.on_scale(channel))
+        .disabled_if(move |state: &HantekState, _| state.is_scale_disabled(channel));
+    let scale = Flex::row()
+        .with_flex_child(label_pending("Scale", Control::Scale(channel)), 1.0)
+        .with_flex_child(scale_options, 1.0);
+
+    let offset_input = float_text_unrestricted()
+        .lens(lens_of(
+            move |state: &HantekState| state.get_offset(channel),
+            move |state: &mut HantekState, new_value| state.set_offset(channel, new_value),
+        ))
+        .on_change(move |_, _, data_mut: &mut HantekState, _| data_mut.on_offset(channel))
+        .disabled_if(move |state: &HantekState, _| state.is_offset_disabled(channel));
+    let offset = Flex::row()
+        .with_flex_child(label_pending("Offset", Control::Offset(channel)), 1.0)
+        .with_flex_child(offset_input, 1.0);
+
+    let bw_limit_switch = Switch::new()
+        .lens(lens_of(
+            move |state: &HantekState| state.get_bw_limit(channel),
+            move |state: &mut HantekState, new_value| state.set_bw_limit(channel, new_value),
+        ))
+        .disabled_if(move |state: &HantekState, _| state.is_bw_limit_enable_disabled(channel))
+        .on_change(move |_, _, data_mut: &mut HantekState, _| data_mut.on_bw_limit(channel));
+    let bandwidth_limit = Flex::row()
+        .with_flex_child(
+            label_pending("Bandwidth Limit", Control::BwLimit(channel)),
+            1.0,
+        )
+        .with_flex_child(bw_limit_switch, 1.0)
+        .align_horizontal(UnitPoint::CENTER)
+        .padding(5.0);
+
+    Flex::column()
+        .with_flex_child(label_ct(format!("Channel {}", channel)), 1.0)
+        .with_flex_child(enabled, 1.0)
+        .with_flex_spacer(0.1)
+        .with_flex_child(coupling, 1.0)
+        .with_flex_spacer(0.1)
+        .with_flex_child(probe, 1.0)
+        .with_flex_spacer(0.1)
+        .with_flex_child(scale, 1.0)
+        .with_flex_spacer(0.1)
+        .with_flex_child(offset, 1.0)
+        .with_flex_spacer(0.1)
+        .with_flex_child(bandwidth_limit, 1.0)
+        .with_flex_spacer(0.1)
+}
+
+fn build_math_panel() -> impl Widget<HantekState> {
+    let enabled = Flex::row()
+        .with_flex_child(label("Enabled"), 1.0)
+        .with_flex_child(
+            Switch::new()
+                .lens(lens_of(
+                    |state: &HantekState| state.get_math_enabled(),
+                    |state: &mut HantekState, new_value| state.set_math_enabled(new_value),
+                ))
+                .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_math()),
+            1.0,
+        )
+        .align_horizontal(UnitPoint::CENTER)
+        .padding(5.0);
+
+    let operation = Flex::row()
+        .with_flex_child(label("Operation"), 1.0)
+        .with_flex_child(
+            DropdownSelect::new(Vector::from(MathOperation::my_options()))
+                .lens(lens_of(
+                    |state: &HantekState| state.get_math_operation(),
+                    |state: &mut HantekState, new_value| state.set_math_operation(new_value),
+                ))
+                .disabled_if(|state: &HantekState, _| state.is_math_disabled())
+                .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_math()),
+            1.0,
+        );
+
+    let sources = Flex::row()
+        .with_flex_child(label("Sources A, B"), 1.0)
+        .with_flex_child(
+            Flex::row()
+                .with_flex_child(
+                    DropdownSelect::new(Vector::from(vec![("CH1", 1), ("CH2", 2)]))
+                        .lens(lens_of(
+                            |state: &HantekState| state.get_math_source_a(),
+                            |state: &mut HantekState, new_value| state.set_math_source_a(new_value),
+                        ))
+                        .disabled_if(|state: &HantekState, _| state.is_math_disabled())
+                        .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_math()),
+                    1.0,
+                )
+                .with_flex_child(
+                    DropdownSelect::new(Vector::from(vec![("CH1", 1), ("CH2", 2)]))
+                        .lens(lens_of(
+                            |state: &HantekState| state.get_math_source_b(),
+                            |state: &mut HantekState, new_value| state.set_math_source_b(new_value),
+                        ))
+                        .disabled_if(|state: &HantekState, _| state.is_math_source_b_disabled())
+                        .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_math()),
+                    1.0,
+                ),
+            1.0,
+        );
+
+    let expression = Flex::row()
+        .with_flex_child(label("Expression"), 1.0)
+        .with_flex_child(
+            TextBox::new()
+                .lens(lens_of(
+                    |state: &HantekState| state.get_math_expression(),
+                    |state: &mut HantekState, new_value| state.set_math_expression(new_value),
+                ))
+                .disabled_if(|state: &HantekState, _| state.is_math_expression_disabled())
+                .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_math())
+                .expand_width(),
+            1.0,
+        );
+
+    let scale = Flex::row()
+        .with_flex_child(label("Scale (V/div)"), 1.0)
+        .with_flex_child(
+            float_text_unrestricted()
+                .lens(lens_of(
+                    |state: &HantekState| state.get_math_scale(),
+                    |state: &mut HantekState, new_value| state.set_math_scale(new_value),
+                ))
+                .disabled_if(|state: &HantekState, _| state.is_math_disabled()),
+            1.0,
+        );
+
+    let offset = Flex::row()
+        .with_flex_child(label("Offset"), 1.0)
+        .with_flex_child(
+            float_text_unrestricted()
+                .lens(lens_of(
+                    |state: &HantekState| state.get_math_offset(),
+                    |state: &mut HantekState, new_value| state.set_math_offset(new_value),
+                ))
+                .disabled_if(|state: &HantekState, _| state.is_math_disabled()),
+            1.0,
+        );
+
+    let status = Label::dynamic(|state: &HantekState, _| state.get_math_status())
+        .with_text_color(Color::rgb8(255, 80, 80))
+        .align_horizontal(UnitPoint::LEFT);
+
+    Flex::column()
+        .with_flex_child(label_c("Math"), 1.0)
+        .with_flex_child(enabled, 1.0)
+        .with_flex_spacer(0.1)
+        .with_flex_child(operation, 1.0)
+        .with_flex_spacer(0.1)
+        .with_flex_child(sources, 1.0)
+        .with_flex_spacer(0.1)
+        .with_flex_child(expression, 1.0)
+        .with_flex_spacer(0.1)
+        .with_flex_child(scale, 1.0)
+        .with_flex_spacer(0.1)
+        .with_flex_child(offset, 1.0)
+        .with_flex_spacer(0.1)
+        .with_flex_child(status, 1.0)
+}
+
+fn build_uart_panel() -> impl Widget<HantekState> {
+    let channel = Flex::row()
+        .with_flex_child(label("Channel"), 1.0)
+        .with_flex_child(
+            DropdownSelect::new(Vector::from(vec![
+                ("CH1", 1),
+                ("CH2", 2),
+                ("MATH", MATH_CHANNEL),
+            ]))
+            .lens(lens_of(
+                |state: &HantekState| state.get_uart_channel(),
+                |state: &mut HantekState, new_value| state.set_uart_channel(new_value),
+            ))
+            .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+            1.0,
+        );
+
+    let threshold = Flex::row()
+        .with_flex_child(label("Threshold (V)"), 1.0)
+        .with_flex_child(
+            float_text_unrestricted()
+                .lens(lens_of(
+                    |state: &HantekState| state.get_uart_threshold(),
+                    |state: &mut HantekState, new_value| state.set_uart_threshold(new_value),
+                ))
+                .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+            1.0,
+        );
+
+    let baud_rate = Flex::row()
+        .with_flex_child(label("Baud rate"), 1.0)
+        .with_flex_child(
+            Flex::row()
+                .with_flex_child(
+                    usize_text_unrestricted()
+                        .lens(lens_of(
+                            |state: &HantekState| state.get_uart_baud_rate(),
+                            |state: &mut HantekState, new_value| {
+                                state.set_uart_baud_rate(new_value)
+                            },
+                        ))
+                        .disabled_if(|state: &HantekState, _| state.is_uart_baud_rate_disabled())
+                        .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+                    1.0,
+                )
+                .with_child(
+                    Checkbox::new("Auto")
+                        .lens(lens_of(
+                            |state: &HantekState| state.get_uart_auto_baud(),
+                            |state: &mut HantekState, new_value| {
+                                state.set_uart_auto_baud(new_value)
+                            },
+                        ))
+                        .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+                ),
+            1.0,
+        );
+
+    let data_bits = Flex::row()
+        .with_flex_child(label("Data bits"), 1.0)
+        .with_flex_child(
+            DropdownSelect::new(Vector::from(vec![
+                ("5", 5),
+                ("6", 6),
+                ("7", 7),
+                ("8", 8),
+                ("9", 9),
+            ]))
+            .lens(lens_of(
+                |state: &HantekState| state.get_uart_data_bits(),
+                |state: &mut HantekState, new_value| state.set_uart_data_bits(new_value),
+            ))
+            .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+            1.0,
+        );
+
+    let parity = Flex::row()
+        .with_flex_child(label("Parity"), 1.0)
+        .with_flex_child(
+            DropdownSelect::new(Vector::from(Parity::my_options()))
+                .lens(lens_of(
+                    |state: &HantekState| state.get_uart_parity(),
+                    |state: &mut HantekState, new_value| state.set_uart_parity(new_value),
+                ))
+                .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+            1.0,
+        );
+
+    let stop_bits = Flex::row()
+        .with_flex_child(label("Stop bits"), 1.0)
+        .with_flex_child(
+            DropdownSelect::new(Vector::from(StopBits::my_options()))
+                .lens(lens_of(
+                    |state: &HantekState| state.get_uart_stop_bits(),
+                    |state: &mut HantekState, new_value| state.set_uart_stop_bits(new_value),
+                ))
+                .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+            1.0,
+        );
+
+    let inverted = Flex::row()
+        .with_flex_child(label("Inverted"), 1.0)
+        .with_flex_child(
+            Checkbox::new("")
+                .lens(lens_of(
+                    |state: &HantekState| state.get_uart_inverted(),
+                    |state: &mut HantekState, new_value| state.set_uart_inverted(new_value),
+                ))
+                .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+            1.0,
+        );
+
+    Flex::column()
+        .with_child(
+            Flex::row()
+                .with_flex_child(channel, 1.0)
+                .with_spacer(10.0)
+                .with_flex_child(threshold, 1.0),
+        )
+        .with_spacer(5.0)
+        .with_child(
+            Flex::row()
+                .with_flex_child(baud_rate, 1.0)
+                .with_spacer(10.0)
+                .with_flex_child(data_bits, 1.0),
+        )
+        .with_spacer(5.0)
+        .with_child(
+            Flex::row()
+                .with_flex_child(parity, 1.0)
+                .with_spacer(10.0)
+                .with_flex_child(stop_bits, 1.0),
+        )
+        .with_spacer(5.0)
+        .with_child(
+            Flex::row()
+                .with_flex_child(inverted, 1.0)
+                .with_spacer(10.0)
+                .with_flex_spacer(1.0),
+        )
+}
+
+fn build_i2c_panel() -> impl Widget<HantekState> {
+    let channels = Flex::row()
+        .with_flex_child(label("SCL, SDA"), 1.0)
+        .with_flex_child(
+            DropdownSelect::new(Vector::from(vec![("CH1, CH2", 1), ("CH2, CH1", 2)]))
+                .lens(lens_of(
+                    |state: &HantekState| state.get_i2c_scl_channel(),
+                    |state: &mut HantekState, new_value| state.set_i2c_scl_channel(new_value),
+                ))
+                .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+            1.0,
+        );
+
+    let threshold = Flex::row()
+        .with_flex_child(label("Threshold (V)"), 1.0)
+        .with_flex_child(
+            float_text_unrestricted()
+                .lens(lens_of(
+                    |state: &HantekState| state.get_i2c_threshold(),
+                    |state: &mut HantekState, new_value| state.set_i2c_threshold(new_value),
+                ))
+                .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+            1.0,
+        );
+
+    Flex::row()
+        .with_flex_child(channels, 1.0)
+        .with_spacer(10.0)
+        .with_flex_child(threshold, 1.0)
+}
+
+fn build_spi_panel() -> impl Widget<HantekState> {
+    let channels = Flex::row()
+        .with_flex_child(label("SCLK, data"), 1.0)
+        .with_flex_child(
+            DropdownSelect::new(Vector::from(vec![("CH1, CH2", 1), ("CH2, CH1", 2)]))
+                .lens(lens_of(
+                    |state: &HantekState| state.get_spi_sclk_channel(),
+                    |state: &mut HantekState, new_value| state.set_spi_sclk_channel(new_value),
+                ))
+                .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+            1.0,
+        );
+
+    let data_line = Flex::row()
+        .with_flex_child(label("Data line"), 1.0)
+        .with_flex_child(
+            DropdownSelect::new(Vector::from(DataLine::my_options()))
+                .lens(lens_of(
+                    |state: &HantekState| state.get_spi_data_line(),
+                    |state: &mut HantekState, new_value| state.set_spi_data_line(new_value),
+                ))
+                .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+            1.0,
+        );
+
+    let mode = Flex::row()
+        .with_flex_child(label("Mode"), 1.0)
+        .with_flex_child(
+            Flex::row()
+                .with_child(
+                    Checkbox::new("CPOL")
+                        .lens(lens_of(
+                            |state: &HantekState| state.get_spi_cpol(),
+                            |state: &mut HantekState, new_value| state.set_spi_cpol(new_value),
+                        ))
+                        .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+                )
+                .with_spacer(5.0)
+                .with_child(
+                    Checkbox::new("CPHA")
+                        .lens(lens_of(
+                            |state: &HantekState| state.get_spi_cpha(),
+                            |state: &mut HantekState, new_value| state.set_spi_cpha(new_value),
+                        ))
+                        .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+                ),
+            1.0,
+        );
+
+    let bit_order = Flex::row()
+        .with_flex_child(label("Bit order"), 1.0)
+        .with_flex_child(
+            DropdownSelect::new(Vector::from(BitOrder::my_options()))
+                .lens(lens_of(
+                    |state: &HantekState| state.get_spi_bit_order(),
+                    |state: &mut HantekState, new_value| state.set_spi_bit_order(new_value),
+                ))
+                .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+            1.0,
+        );
+
+    let word_size = Flex::row()
+        .with_flex_child(label("Word size"), 1.0)
+        .with_flex_child(
+            usize_text_unrestricted()
+                .lens(lens_of(
+                    |state: &HantekState| state.get_spi_word_size(),
+                    |state: &mut HantekState, new_value| state.set_spi_word_size(new_value),
+                ))
+                .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+            1.0,
+        );
+
+    let threshold = Flex::row()
+        .with_flex_child(label("Threshold (V)"), 1.0)
+        .with_flex_child(
+            float_text_unrestricted()
+                .lens(lens_of(
+                    |state: &HantekState| state.get_spi_threshold(),
+                    |state: &mut HantekState, new_value| state.set_spi_threshold(new_value),
+                ))
+                .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+            1.0,
+        );
+
+    let cs_emulation = Flex::row()
+        .with_flex_child(label("CS from idle"), 1.0)
+        .with_flex_child(
+            Checkbox::new("")
+                .lens(lens_of(
+                    |state: &HantekState| state.get_spi_cs_emulation(),
+                    |state: &mut HantekState, new_value| state.set_spi_cs_emulation(new_value),
+                ))
+                .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+            1.0,
+        );
+
+    let idle_gap = Flex::row()
+        .with_flex_child(label("Idle gap (µs)"), 1.0)
+        .with_flex_child(
+            float_text_unrestricted()
+                .lens(lens_of(
+                    |state: &HantekState| state.get_spi_idle_gap(),
+                    |state: &mut HantekState, new_value| state.set_spi_idle_gap(new_value),
+                ))
+                .disabled_if(|state: &HantekState, _| state.is_spi_idle_gap_disabled())
+                .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+            1.0,
+        );
+
+    Flex::column()
+        .with_child(
+            Flex::row()
+                .with_flex_child(channels, 1.0)
+                .with_spacer(10.0)
+                .with_flex_child(data_line, 1.0),
+        )
+        .with_spacer(5.0)
+        .with_child(
+            Flex::row()
+                .with_flex_child(mode, 1.0)
+                .with_spacer(10.0)
+                .with_flex_child(bit_order, 1.0),
+        )
+        .with_spacer(5.0)
+        .with_child(
+            Flex::row()
+                .with_flex_child(word_size, 1.0)
+                .with_spacer(10.0)
+                .with_flex_child(threshold, 1.0),
+        )
+        .with_spacer(5.0)
+        .with_child(
+            Flex::row()
+                .with_flex_child(cs_emulation, 1.0)
+                .with_spacer(10.0)
+                .with_flex_child(idle_gap, 1.0),
+        )
+}
+
+fn build_can_panel() -> impl Widget<HantekState> {
+    // Differential probing uses the math trace set up as CH1 - CH2.
+    let channel = Flex::row()
+        .with_flex_child(label("Channel"), 1.0)
+        .with_flex_child(
+            DropdownSelect::new(Vector::from(vec![
+                ("CH1", 1),
+                ("CH2", 2),
+                ("MATH", MATH_CHANNEL),
+            ]))
+            .lens(lens_of(
+                |state: &HantekState| state.get_can_channel(),
+                |state: &mut HantekState, new_value| state.set_can_channel(new_value),
+            ))
+            .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+            1.0,
+        );
+
+    let threshold = Flex::row()
+        .with_flex_child(label("Threshold (V)"), 1.0)
+        .with_flex_child(
+            float_text_unrestricted()
+                .lens(lens_of(
+                    |state: &HantekState| state.get_can_threshold(),
+                    |state: &mut HantekState, new_value| state.set_can_threshold(new_value),
+                ))
+                .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+            1.0,
+        );
 
-    let coupling_options = DropdownSelect::new(Vector::from(Coupling::my_options()))
-        .lens(lens_of(
-            move |state: &HantekState| state.get_coupling(channel),
-            move |state: &mut HantekState, new_value| state.set_coupling(channel, new_value),
-        ))
-        .on_change(move |_, _, data_mut: &mut HantekState, _| data_mut.on_coupling(channel))
-        .disabled_if(move |state: &HantekState, _| state.is_coupling_disabled(channel));
-    let coupling = Flex::row()
-        .with_flex_child(label("Coupling"), 1.0)
-        .with_flex_child(coupling_options, 1.0);
+    let bitrate = Flex::row()
+        .with_flex_child(label("Bitrate"), 1.0)
+        .with_flex_child(
+            usize_text_unrestricted()
+                .lens(lens_of(
+                    |state: &HantekState| state.get_can_bitrate(),
+                    |state: &mut HantekState, new_value| state.set_can_bitrate(new_value),
+                ))
+                .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+            1.0,
+        );
 
-    let probe_options = DropdownSelect::new(Vector::from(Probe::my_options()))
-        .lens(lens_of(
-            move |state: &HantekState| state.get_probe(channel),
-            move |state, new_value| state.set_probe(channel, new_value),
-        ))
-        .on_change(move |_, _, data_mut: &mut HantekState, _| data_mut.on_probe(channel))
-        .disabled_if(move |state: &HantekState, _| state.is_probe_disabled(channel));
-    let probe = Flex::row()
-        .with_flex_child(label("Probe"), 1.0)
-        .with_flex_child(probe_options, 1.0);
+    let sample_point = Flex::row()
+        .with_flex_child(label("Sample point (%)"), 1.0)
+        .with_flex_child(
+            float_text_unrestricted()
+                .lens(lens_of(
+                    |state: &HantekState| state.get_can_sample_point(),
+                    |state: &mut HantekState, new_value| state.set_can_sample_point(new_value),
+                ))
+                .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+            1.0,
+        );
 
-    let scale_options = DropdownSelect::new(Vector::from(Scale::my_options()))
-        .lens(lens_of(
-            move |state: &HantekState| state.get_scale(channel),
-            move |state, new_value| state.set_scale0(channel, new_value),
-        ))
-        .on_change(move |_, _, data_mut: &mut HantekState, _| data_mut.on_scale(channel))
-        .disabled_if(move |state: &HantekState, _| state.is_scale_disabled(channel));
-    let scale = Flex::row()
-        .with_flex_child(label("Scale"), 1.0)
-        .with_flex_child(scale_options, 1.0);
+    Flex::column()
+        .with_child(
+            Flex::row()
+                .with_flex_child(channel, 1.0)
+                .with_spacer(10.0)
+                .with_flex_child(threshold, 1.0),
+        )
+        .with_spacer(5.0)
+        .with_child(
+            Flex::row()
+                .with_flex_child(bitrate, 1.0)
+                .with_spacer(10.0)
+                .with_flex_child(sample_point, 1.0),
+        )
+}
 
-    let offset_input = float_text_unrestricted()
-        .lens(lens_of(
-            move |state: &HantekState| state.get_offset(channel),
-            move |state: &mut HantekState, new_value| state.set_offset(channel, new_value),
-        ))
-        .on_change(move |_, _, data_mut: &mut HantekState, _| data_mut.on_offset(channel))
-        .disabled_if(move |state: &HantekState, _| state.is_offset_disabled(channel));
-    let offset = Flex::row()
-        .with_flex_child(label("Offset"), 1.0)
-        .with_flex_child(offset_input, 1.0);
+fn build_decoder_panel() -> impl Widget<HantekState> {
+    let protocol = Flex::row()
+        .with_flex_child(label("Protocol"), 1.0)
+        .with_flex_child(
+            DropdownSelect::new(Vector::from(Protocol::my_options()))
+                .lens(lens_of(
+                    |state: &HantekState| state.get_decoder_protocol(),
+                    |state: &mut HantekState, new_value| state.set_decoder_protocol(new_value),
+                ))
+                .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_decoder()),
+            1.0,
+        );
 
-    let bw_limit_switch = Switch::new()
+    let settings = ViewSwitcher::new(
+        |state: &HantekState, _| state.get_decoder_protocol(),
+        |protocol, _, _| match protocol {
+            Protocol::Off => Flex::column().boxed(),
+            Protocol::Uart => build_uart_panel().boxed(),
+            Protocol::I2c => build_i2c_panel().boxed(),
+            Protocol::Spi => build_spi_panel().boxed(),
+            Protocol::Can => build_can_panel().boxed(),
+        },
+    );
+
+    let status = Label::dynamic(|state: &HantekState, _| state.get_decoder_status())
+        .with_line_break_mode(LineBreaking::WordWrap)
+        .align_horizontal(UnitPoint::LEFT);
+
+    let table = Label::new(|data: &String, _env: &_| data.clone())
+        .with_font(FontDescriptor::new(FontFamily::MONOSPACE))
         .lens(lens_of(
-            move |state: &HantekState| state.get_bw_limit(channel),
-            move |state: &mut HantekState, new_value| state.set_bw_limit(channel, new_value),
+            |state: &HantekState| state.get_decoder_text(),
+            |_, _| {},
         ))
-        .disabled_if(move |state: &HantekState, _| state.is_bw_limit_enable_disabled(channel))
-        .on_change(move |_, _, data_mut: &mut HantekState, _| data_mut.on_bw_limit(channel));
-    let bandwidth_limit = Flex::row()
-        .with_flex_child(label("Bandwidth Limit"), 1.0)
-        .with_flex_child(bw_limit_switch, 1.0)
-        .align_horizontal(UnitPoint::CENTER)
-        .padding(5.0);
-
-    Flex::column()
-        .with_flex_child(label_ct(format!("Channel {}", channel)), 1.0)
-        .with_flex_child(enabled, 1.0)
-        .with_flex_spacer(0.1)
-        .with_flex_child(coupling, 1.0)
-        .with_flex_spacer(0.1)
-        .with_flex_child(probe, 1.0)
-        .with_flex_spacer(0.1)
-        .with_flex_child(scale, 1.0)
-        .with_flex_spacer(0.1)
-        .with_flex_child(offset, 1.0)
-        .with_flex_spacer(0.1)
-        .with_flex_child(bandwidth_limit, 1.0)
-        .with_flex_spacer(0.1)
+        .padding(5.0)
+        .scroll()
+        .expand();
+
+    let export_button = Button::new("Export table...")
+        .on_click(|ctx, _, _| {
+            let options = FileDialogOptions::new()
+                .allowed_types(vec![CSV_FILE])
+                .default_type(CSV_FILE)
+                .default_name("decoded.csv")
+                .accept_command(EXPORT_DECODED);
+            ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+        })
+        .disabled_if(|state: &HantekState, _| state.is_export_decoded_disabled());
+
+    Flex::row()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_flex_child(
+            Flex::column()
+                .with_child(label_c("Decoder"))
+                .with_spacer(5.0)
+                .with_child(protocol)
+                .with_spacer(5.0)
+                .with_child(settings)
+                .with_spacer(5.0)
+                .with_child(status)
+                .with_spacer(5.0)
+                .with_child(export_button),
+            1.5,
+        )
+        .with_spacer(10.0)
+        .with_flex_child(table, 1.0)
+        .padding(5.0)
 }
 
 fn build_scope_panel() -> impl Widget<HantekState> {
     let enabled = Flex::row()
-        .with_flex_child(label("Running"), 1.0)
+        .with_flex_child(label_pending("Running", Control::Running), 1.0)
         .with_flex_child(
             Switch::new()
                 .lens(lens_of(
@@ -1569,11 +3593,11 @@ fn build_scope_panel() -> impl Widget<HantekState> {
         .disabled_if(|state: &HantekState, _| state.is_time_scale_disabled())
         .on_change(|_, _, data_mut: &mut HantekState, _| data_mut.on_time_scale());
     let time_scale = Flex::row()
-        .with_flex_child(label("Time Scale"), 1.0)
+        .with_flex_child(label_pending("Time Scale", Control::TimeScale), 1.0)
         .with_flex_child(time_scale_options, 1.0);
 
     let time_offset = Flex::row()
-        .with_flex_child(label("Time Offset"), 1.0)
+        .with_flex_child(label_pending("Time Offset", Control::TimeOffset), 1.0)
         .with_flex_child(
             float_text_unrestricted()
                 .lens(lens_of(
@@ -1585,7 +3609,7 @@ fn build_scope_panel() -> impl Widget<HantekState> {
             1.0,
         );
     let trigger_source = Flex::row()
-        .with_flex_child(label("Trigger Source"), 1.0)
+        .with_flex_child(label_pending("Trigger Source", Control::TriggerSource), 1.0)
         .with_flex_child(
             DropdownSelect::new(Vector::from(vec![("Channel 1", 1), ("Channel 2", 2)]))
                 .lens(lens_of(
@@ -1597,7 +3621,7 @@ fn build_scope_panel() -> impl Widget<HantekState> {
             1.0,
         );
     let trigger_mode = Flex::row()
-        .with_flex_child(label("Trigger Mode"), 1.0)
+        .with_flex_child(label_pending("Trigger Mode", Control::TriggerMode), 1.0)
         .with_flex_child(
             DropdownSelect::new(Vector::from(TriggerMode::my_options()))
                 .lens(lens_of(
@@ -1610,7 +3634,7 @@ fn build_scope_panel() -> impl Widget<HantekState> {
         );
 
     let trigger_level = Flex::row()
-        .with_flex_child(label("Trigger Level"), 1.0)
+        .with_flex_child(label_pending("Trigger Level", Control::TriggerLevel), 1.0)
         .with_flex_child(
             float_text_unrestricted()
                 .lens(lens_of(
@@ -1635,12 +3659,58 @@ fn build_scope_panel() -> impl Widget<HantekState> {
                 }),
             1.0,
         );
+    let acquisition_interval = Flex::row()
+        .with_flex_child(label("Interval (ms)"), 1.0)
+        .with_flex_child(
+            usize_text_unrestricted()
+                .lens(lens_of(
+                    |state: &HantekState| state.get_acquisition_interval(),
+                    |state: &mut HantekState, new_value| state.set_acquisition_interval(new_value),
+                ))
+                .disabled_if(|state: &HantekState, _| state.is_acquisition_disabled()),
+            1.0,
+        );
     let action_panel = Flex::row()
-        .with_flex_child(label(" "), 1.0)
+        .with_flex_child(
+            Button::dynamic(|state: &HantekState, _| match state.acquiring {
+                true => "Stop".to_string(),
+                false => "Run".to_string(),
+            })
+            .on_click(|_, state: &mut HantekState, _| state.toggle_acquisition())
+            .disabled_if(|state: &HantekState, _| state.is_acquisition_disabled()),
+            1.0,
+        )
         .with_flex_child(
             Button::new("Capture")
                 .on_click(|_, state: &mut HantekState, _| state.capture())
-                .disabled_if(|state: &HantekState, _| !state.is_connected()),
+                .disabled_if(|state: &HantekState, _| state.is_capture_disabled()),
+            1.0,
+        );
+    let single_timeout = Flex::row()
+        .with_flex_child(label("Single Timeout (s)"), 1.0)
+        .with_flex_child(
+            float_text_unrestricted()
+                .lens(lens_of(
+                    |state: &HantekState| state.get_single_timeout(),
+                    |state: &mut HantekState, new_value| state.set_single_timeout(new_value),
+                ))
+                .disabled_if(|state: &HantekState, _| state.is_single_disabled()),
+            1.0,
+        );
+    let single_panel = Flex::row()
+        .with_flex_child(
+            Button::dynamic(|state: &HantekState, _| match state.is_single_armed() {
+                true => "Armed …".to_string(),
+                false => "Single".to_string(),
+            })
+            .on_click(|_, state: &mut HantekState, _| state.arm_single())
+            .disabled_if(|state: &HantekState, _| state.is_single_disabled()),
+            1.0,
+        )
+        .with_flex_child(
+            Button::new("Cancel")
+                .on_click(|_, state: &mut HantekState, _| state.cancel_single())
+                .disabled_if(|state: &HantekState, _| state.is_cancel_single_disabled()),
             1.0,
         );
 
@@ -1660,13 +3730,143 @@ fn build_scope_panel() -> impl Widget<HantekState> {
         .with_flex_spacer(0.1)
         .with_flex_child(num_captures, 1.0)
         .with_flex_spacer(0.1)
+        .with_flex_child(acquisition_interval, 1.0)
+        .with_flex_spacer(0.1)
         .with_flex_child(action_panel, 1.0)
         .with_flex_spacer(0.1)
+        .with_flex_child(single_timeout, 1.0)
+        .with_flex_spacer(0.1)
+        .with_flex_child(single_panel, 1.0)
+        .with_flex_spacer(0.1)
+}
+
+fn build_display_panel() -> impl Widget<HantekState> {
+    let display_mode = Flex::row()
+        .with_flex_child(label("Display"), 1.0)
+        .with_flex_child(
+            DropdownSelect::new(Vector::from(DisplayMode::my_options())).lens(lens_of(
+                |state: &HantekState| state.get_display_mode(),
+                |state: &mut HantekState, new_value| state.set_display_mode(new_value),
+            )),
+            1.0,
+        );
+
+    let view_panel = Flex::row()
+        .with_flex_child(label("View"), 1.0)
+        .with_flex_child(
+            Button::new("Reset View")
+                .on_click(|_, state: &mut HantekState, _| state.reset_view())
+                .disabled_if(|state: &HantekState, _| state.is_reset_view_disabled()),
+            1.0,
+        );
+
+    let time_cursors = Flex::row()
+        .with_flex_child(label("Time Cursors"), 1.0)
+        .with_flex_child(
+            Switch::new().lens(lens_of(
+                |state: &HantekState| state.get_time_cursors(),
+                |state: &mut HantekState, new_value| state.set_time_cursors(new_value),
+            )),
+            1.0,
+        );
+    let volt_cursors = Flex::row()
+        .with_flex_child(label("Volt Cursors"), 1.0)
+        .with_flex_child(
+            Flex::row()
+                .with_flex_child(
+                    Switch::new().lens(lens_of(
+                        |state: &HantekState| state.get_volt_cursors(),
+                        |state: &mut HantekState, new_value| state.set_volt_cursors(new_value),
+                    )),
+                    1.0,
+                )
+                .with_flex_child(
+                    DropdownSelect::new(Vector::from(vec![
+                        ("CH1", 1),
+                        ("CH2", 2),
+                        ("MATH", MATH_CHANNEL),
+                    ]))
+                    .lens(lens_of(
+                        |state: &HantekState| state.get_volt_cursor_channel(),
+                        |state: &mut HantekState, new_value| {
+                            state.set_volt_cursor_channel(new_value)
+                        },
+                    ))
+                    .disabled_if(|state: &HantekState, _| state.is_volt_cursor_channel_disabled()),
+                    1.0,
+                ),
+            1.0,
+        );
+
+    let spectrum_window = Flex::row()
+        .with_flex_child(label("FFT Window"), 1.0)
+        .with_flex_child(
+            DropdownSelect::new(Vector::from(Window::my_options()))
+                .lens(lens_of(
+                    |state: &HantekState| state.get_spectrum_window(),
+                    |state: &mut HantekState, new_value| state.set_spectrum_window(new_value),
+                ))
+                .disabled_if(|state: &HantekState, _| state.is_spectrum_disabled()),
+            1.0,
+        );
+    let spectrum_scale = Flex::row()
+        .with_flex_child(label("FFT Scale"), 1.0)
+        .with_flex_child(
+            DropdownSelect::new(Vector::from(SpectrumScale::my_options()))
+                .lens(lens_of(
+                    |state: &HantekState| state.get_spectrum_scale(),
+                    |state: &mut HantekState, new_value| state.set_spectrum_scale(new_value),
+                ))
+                .disabled_if(|state: &HantekState, _| state.is_spectrum_disabled()),
+            1.0,
+        );
+    let spectrum_averages = Flex::row()
+        .with_flex_child(label("FFT Averages"), 1.0)
+        .with_flex_child(
+            usize_text_unrestricted()
+                .lens(lens_of(
+                    |state: &HantekState| state.get_spectrum_averages(),
+                    |state: &mut HantekState, new_value| state.set_spectrum_averages(new_value),
+                ))
+                .disabled_if(|state: &HantekState, _| state.is_spectrum_disabled()),
+            1.0,
+        );
+
+    let xy_persistence = Flex::row()
+        .with_flex_child(label("XY Persistence"), 1.0)
+        .with_flex_child(
+            usize_text_unrestricted()
+                .lens(lens_of(
+                    |state: &HantekState| state.get_xy_persistence(),
+                    |state: &mut HantekState, new_value| state.set_xy_persistence(new_value),
+                ))
+                .disabled_if(|state: &HantekState, _| state.is_xy_persistence_disabled()),
+            1.0,
+        );
+
+    Flex::column()
+        .with_flex_child(label_c("Display"), 1.0)
+        .with_flex_child(display_mode, 1.0)
+        .with_flex_spacer(0.1)
+        .with_flex_child(view_panel, 1.0)
+        .with_flex_spacer(0.1)
+        .with_flex_child(time_cursors, 1.0)
+        .with_flex_spacer(0.1)
+        .with_flex_child(volt_cursors, 1.0)
+        .with_flex_spacer(0.1)
+        .with_flex_child(spectrum_window, 1.0)
+        .with_flex_spacer(0.1)
+        .with_flex_child(spectrum_scale, 1.0)
+        .with_flex_spacer(0.1)
+        .with_flex_child(spectrum_averages, 1.0)
+        .with_flex_spacer(0.1)
+        .with_flex_child(xy_persistence, 1.0)
+        .with_flex_spacer(0.1)
 }
 
 fn build_awg_panel() -> impl Widget<HantekState> {
     let awg_running = Flex::row()
-        .with_flex_child(label("Running"), 1.0)
+        .with_flex_child(label_pending("Running", Control::AwgRunning), 1.0)
         .with_flex_child(
             Switch::new()
                 .lens(lens_of(
@@ -1686,7 +3886,7 @@ fn build_awg_panel() -> impl Widget<HantekState> {
         .padding(5.0);
 
     let awg_type = Flex::row()
-        .with_flex_child(label("AWG Type"), 1.0)
+        .with_flex_child(label_pending("AWG Type", Control::AwgType), 1.0)
         .with_flex_child(
             DropdownSelect::new(Vector::from(AwgType::my_options()))
                 .lens(lens_of(
@@ -1699,7 +3899,7 @@ fn build_awg_panel() -> impl Widget<HantekState> {
         );
 
     let frequency = Flex::row()
-        .with_flex_child(label("Frequency"), 1.0)
+        .with_flex_child(label_pending("Frequency", Control::AwgFrequency), 1.0)
         .with_flex_child(
             float_text_unrestricted()
                 .lens(lens_of(
@@ -1712,7 +3912,7 @@ fn build_awg_panel() -> impl Widget<HantekState> {
         );
 
     let amplitude = Flex::row()
-        .with_flex_child(label("Amplitude"), 1.0)
+        .with_flex_child(label_pending("Amplitude", Control::AwgAmplitude), 1.0)
         .with_flex_child(
             float_text_unrestricted()
                 .lens(lens_of(
@@ -1725,7 +3925,7 @@ fn build_awg_panel() -> impl Widget<HantekState> {
         );
 
     let offset = Flex::row()
-        .with_flex_child(label("Offset"), 1.0)
+        .with_flex_child(label_pending("Offset", Control::AwgOffset), 1.0)
         .with_flex_child(
             float_text_unrestricted()
                 .lens(lens_of(
@@ -1738,7 +3938,7 @@ fn build_awg_panel() -> impl Widget<HantekState> {
         );
 
     let duty_square = Flex::row()
-        .with_flex_child(label("Duty / Square"), 1.0)
+        .with_flex_child(label_pending("Duty / Square", Control::AwgDutySquare), 1.0)
         .with_flex_child(
             float_text_unrestricted()
                 .lens(lens_of(
@@ -1753,7 +3953,7 @@ fn build_awg_panel() -> impl Widget<HantekState> {
         );
 
     let duty_ramp = Flex::row()
-        .with_flex_child(label("Duty / Ramp"), 1.0)
+        .with_flex_child(label_pending("Duty / Ramp", Control::AwgDutyRamp), 1.0)
         .with_flex_child(
             float_text_unrestricted()
                 .lens(lens_of(
@@ -1766,7 +3966,10 @@ fn build_awg_panel() -> impl Widget<HantekState> {
         );
 
     let duty_trap_high = Flex::row()
-        .with_flex_child(label("Duty / Trap::High"), 1.0)
+        .with_flex_child(
+            label_pending("Duty / Trap::High", Control::AwgDutyTrap),
+            1.0,
+        )
         .with_flex_child(
             float_text_unrestricted()
                 .lens(lens_of(
@@ -1781,7 +3984,7 @@ fn build_awg_panel() -> impl Widget<HantekState> {
         );
 
     let duty_trap_low = Flex::row()
-        .with_flex_child(label("Duty / Trap::Low"), 1.0)
+        .with_flex_child(label_pending("Duty / Trap::Low", Control::AwgDutyTrap), 1.0)
         .with_flex_child(
             float_text_unrestricted()
                 .lens(lens_of(
@@ -1796,7 +3999,10 @@ fn build_awg_panel() -> impl Widget<HantekState> {
         );
 
     let duty_trap_rise = Flex::row()
-        .with_flex_child(label("Duty / Trap::Rise"), 1.0)
+        .with_flex_child(
+            label_pending("Duty / Trap::Rise", Control::AwgDutyTrap),
+            1.0,
+        )
         .with_flex_child(
             float_text_unrestricted()
                 .lens(lens_of(
@@ -1840,7 +4046,8 @@ fn build_control_panel() -> impl Widget<HantekState> {
         .with_flex_child(
             Flex::row()
                 .with_flex_child(build_channel_panel(1), 1.0)
-                .with_flex_child(build_channel_panel(2), 1.0),
+                .with_flex_child(build_channel_panel(2), 1.0)
+                .with_flex_child(build_math_panel(), 1.0),
             1.0,
         )
         .with_flex_child(
@@ -1857,8 +4064,243 @@ fn build_control_panel() -> impl Widget<HantekState> {
         )
 }
 
+fn build_reference_panel(slot: usize) -> impl Widget<HantekState> {
+    let controls = Flex::row()
+        .with_flex_child(label_ct(reference_name(slot)), 0.5)
+        .with_flex_child(
+            Button::new("Save")
+                .on_click(move |_, state: &mut HantekState, _| state.save_reference(slot))
+                .disabled_if(|state: &HantekState, _| state.is_save_reference_disabled()),
+            1.0,
+        )
+        .with_flex_child(
+            Button::new("Clear")
+                .on_click(move |_, state: &mut HantekState, _| state.clear_reference(slot))
+                .disabled_if(move |state: &HantekState, _| state.is_reference_disabled(slot)),
+            1.0,
+        )
+        .with_flex_child(
+            Switch::new()
+                .lens(lens_of(
+                    move |state: &HantekState| state.get_reference_shown(slot),
+                    move |state: &mut HantekState, new_value| {
+                        state.set_reference_shown(slot, new_value)
+                    },
+                ))
+                .disabled_if(move |state: &HantekState, _| state.is_reference_disabled(slot)),
+            1.0,
+        )
+        .with_flex_child(
+            DropdownSelect::new(Vector::from(vec![
+                ("CH1", 1),
+                ("CH2", 2),
+                ("MATH", MATH_CHANNEL),
+            ]))
+            .lens(lens_of(
+                move |state: &HantekState| state.get_reference_channel(slot),
+                move |state: &mut HantekState, new_value| {
+                    state.set_reference_channel(slot, new_value)
+                },
+            )),
+            1.0,
+        );
+
+    let position = Flex::row()
+        .with_flex_child(label("V/div, Offset"), 1.0)
+        .with_flex_child(
+            float_text_unrestricted()
+                .lens(lens_of(
+                    move |state: &HantekState| state.get_reference_scale(slot),
+                    move |state: &mut HantekState, new_value| {
+                        state.set_reference_scale(slot, new_value)
+                    },
+                ))
+                .disabled_if(move |state: &HantekState, _| state.is_reference_disabled(slot)),
+            1.0,
+        )
+        .with_flex_child(
+            float_text_unrestricted()
+                .lens(lens_of(
+                    move |state: &HantekState| state.get_reference_offset(slot),
+                    move |state: &mut HantekState, new_value| {
+                        state.set_reference_offset(slot, new_value)
+                    },
+                ))
+                .disabled_if(move |state: &HantekState, _| state.is_reference_disabled(slot)),
+            1.0,
+        );
+
+    Flex::column()
+        .with_flex_child(controls, 1.0)
+        .with_flex_child(position, 1.0)
+}
+
+fn build_references_panel() -> impl Widget<HantekState> {
+    let mut panel = Flex::column().with_child(label_c("References"));
+    for slot in 0..NUM_REFERENCES {
+        panel.add_spacer(5.0);
+        panel.add_flex_child(build_reference_panel(slot), 1.0);
+    }
+    panel
+}
+
+fn build_export_panel() -> impl Widget<HantekState> {
+    let csv_button = Button::new("Export CSV...")
+        .on_click(|ctx, _, _| {
+            let options = FileDialogOptions::new()
+                .allowed_types(vec![CSV_FILE])
+                .default_type(CSV_FILE)
+                .default_name("capture.csv")
+                .accept_command(EXPORT_CSV);
+            ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+        })
+        .disabled_if(|state: &HantekState, _| state.is_export_disabled());
+
+    let raw = Checkbox::new("Raw ADC column").lens(lens_of(
+        |state: &HantekState| state.get_export_raw(),
+        |state: &mut HantekState, new_value| state.set_export_raw(new_value),
+    ));
+
+    let wav_button = Button::new("Export WAV...")
+        .on_click(|ctx, _, _| {
+            let options = FileDialogOptions::new()
+                .allowed_types(vec![WAV_FILE])
+                .default_type(WAV_FILE)
+                .default_name("capture.wav")
+                .accept_command(EXPORT_WAV);
+            ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+        })
+        .disabled_if(|state: &HantekState, _| state.is_export_disabled());
+
+    let sigrok_button = Button::new("Export sigrok...")
+        .on_click(|ctx, _, _| {
+            let options = FileDialogOptions::new()
+                .allowed_types(vec![SIGROK_FILE])
+                .default_type(SIGROK_FILE)
+                .default_name("capture.sr")
+                .accept_command(EXPORT_SIGROK);
+            ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+        })
+        .disabled_if(|state: &HantekState, _| state.is_export_disabled());
+
+    let png_button = Button::new("Save PNG...").on_click(|ctx, _, _| {
+        let options = FileDialogOptions::new()
+            .allowed_types(vec![PNG_FILE])
+            .default_type(PNG_FILE)
+            .default_name("scope.png")
+            .accept_command(EXPORT_PNG);
+        ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+    });
+
+    let svg_button = Button::new("Save SVG...").on_click(|ctx, _, _| {
+        let options = FileDialogOptions::new()
+            .allowed_types(vec![SVG_FILE])
+            .default_type(SVG_FILE)
+            .default_name("scope.svg")
+            .accept_command(EXPORT_SVG);
+        ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+    });
+
+    let image_width = usize_text_unrestricted().lens(lens_of(
+        |state: &HantekState| state.get_image_width(),
+        |state: &mut HantekState, new_value| state.set_image_width(new_value),
+    ));
+
+    let image_height = usize_text_unrestricted().lens(lens_of(
+        |state: &HantekState| state.get_image_height(),
+        |state: &mut HantekState, new_value| state.set_image_height(new_value),
+    ));
+
+    let wav_format = DropdownSelect::new(Vector::from(WavFormat::my_options())).lens(lens_of(
+        |state: &HantekState| state.get_wav_format(),
+        |state: &mut HantekState, new_value| state.set_wav_format(new_value),
+    ));
+
+    let wav_normalized = Checkbox::new("Normalized").lens(lens_of(
+        |state: &HantekState| state.get_wav_normalized(),
+        |state: &mut HantekState, new_value| state.set_wav_normalized(new_value),
+    ));
+
+    Flex::column()
+        .with_child(label_c("Export"))
+        .with_spacer(5.0)
+        .with_child(
+            Flex::row()
+                .with_flex_child(csv_button, 1.0)
+                .with_flex_child(raw, 1.0)
+                .with_flex_child(sigrok_button, 1.0),
+        )
+        .with_spacer(5.0)
+        .with_child(
+            Flex::row()
+                .with_flex_child(wav_button, 1.0)
+                .with_flex_child(wav_format, 1.0)
+                .with_flex_child(wav_normalized, 1.0),
+        )
+        .with_spacer(5.0)
+        .with_child(
+            Flex::row()
+                .with_flex_child(png_button, 1.0)
+                .with_flex_child(svg_button, 1.0)
+                .with_flex_child(image_width, 0.6)
+                .with_child(Label::new("x"))
+                .with_flex_child(image_height, 0.6),
+        )
+}
+
+fn build_measurements_panel() -> impl Widget<HantekState> {
+    let mut selection = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+    for row in Measurement::ALL.chunks(3) {
+        let mut flex = Flex::row();
+        for measurement in row.iter().copied() {
+            flex.add_flex_child(
+                Checkbox::new(measurement.name())
+                    .lens(lens_of(
+                        move |state: &HantekState| state.is_measurement_selected(measurement),
+                        move |state: &mut HantekState, new_value| {
+                            state.set_measurement_selected(measurement, new_value)
+                        },
+                    ))
+                    .align_horizontal(UnitPoint::LEFT),
+                1.0,
+            );
+        }
+        selection.add_child(flex.padding(2.0));
+    }
+
+    let table = Label::new(|data: &String, _env: &_| data.clone())
+        .with_font(FontDescriptor::new(FontFamily::MONOSPACE))
+        .lens(lens_of(
+            |state: &HantekState| state.get_measurements_text(),
+            |_, _| {},
+        ))
+        .padding(5.0)
+        .scroll()
+        .expand();
+
+    Flex::column()
+        .with_child(label_c("Measurements"))
+        .with_spacer(5.0)
+        .with_child(selection)
+        .with_spacer(5.0)
+        .with_flex_child(table, 1.0)
+        .with_child(
+            Button::new("Reset Statistics")
+                .on_click(|_, state: &mut HantekState, _| state.reset_measurements())
+                .disabled_if(|state: &HantekState, _| state.is_reset_measurements_disabled()),
+        )
+}
+
 fn build_scope_graph() -> impl Widget<HantekState> {
-    ScopeGraph
+    ViewSwitcher::new(
+        |state: &HantekState, _| state.get_display_mode(),
+        |mode, _, _| match mode {
+            DisplayMode::Time => ScopeGraph::new().boxed(),
+            DisplayMode::Spectrum => SpectrumGraph.boxed(),
+            DisplayMode::Xy => XyGraph.boxed(),
+        },
+    )
+    .controller(Acquisition::new())
 }
 
 fn build_ui() -> impl Widget<HantekState> {
@@ -1881,9 +4323,27 @@ fn build_ui() -> impl Widget<HantekState> {
         .cross_axis_alignment(CrossAxisAlignment::Start)
         .with_flex_child(
             Flex::row()
-                .with_flex_child(build_scope_graph(), 2.5)
+                .with_flex_child(
+                    Flex::column()
+                        .with_flex_child(build_scope_graph(), 3.0)
+                        .with_spacer(5.0)
+                        .with_flex_child(build_decoder_panel(), 1.0),
+                    2.5,
+                )
                 .with_flex_spacer(0.1)
-                .with_flex_child(build_control_panel(), 1.0),
+                .with_flex_child(build_control_panel(), 1.0)
+                .with_flex_spacer(0.1)
+                .with_flex_child(
+                    Flex::column()
+                        .with_flex_child(build_display_panel(), 1.0)
+                        .with_flex_spacer(0.1)
+                        .with_flex_child(build_references_panel(), 1.0)
+                        .with_flex_spacer(0.1)
+                        .with_child(build_export_panel())
+                        .with_flex_spacer(0.1)
+                        .with_flex_child(build_measurements_panel(), 1.5),
+                    0.8,
+                ),
             1.0,
         )
 }
@@ -1893,8 +4353,22 @@ pub fn main() -> anyhow::Result<()> {
     builder.parse_filters("TRACE");
     builder.init();
 
-    info!("running handler thread");
-    let (rx, tx) = handler_thread();
+    let front_panel_edits = std::env::args().any(|arg| arg == "--simulate-front-panel")
+        || std::env::var_os("HANTEKER_SIMULATE_FRONT_PANEL").is_some();
+    let backend = if std::env::args().any(|arg| arg == "--simulate")
+        || std::env::var_os("HANTEKER_SIMULATE").is_some()
+        || front_panel_edits
+    {
+        Backend::Simulated { front_panel_edits }
+    } else {
+        Backend::Usb
+    };
+    let mut args = std::env::args().skip_while(|arg| arg != "--save-image");
+    let save_image = match (args.next(), args.next()) {
+        (Some(_), Some(path)) => Some(PathBuf::from(path)),
+        (Some(_), None) => bail!("--save-image needs a file name"),
+        _ => None,
+    };
 
     debug!("creating UI window");
     let window = WindowDesc::new(
@@ -1905,12 +4379,22 @@ pub fn main() -> anyhow::Result<()> {
     .window_size((1724., 740.))
     .title(tt("window-title", "Hantek"));
 
-    let state = HantekState::new(rx, Arc::new(tx));
+    let launcher = AppLauncher::with_window(window)
+        .delegate(Delegate)
+        .log_to_console();
+
+    info!("running handler thread, backend={:?}", backend);
+    let tx = handler_thread(backend, launcher.get_external_handle());
+
+    let mut state = HantekState::new(tx);
+    state.load_session();
+    state.load_references();
+    if let Some(path) = save_image {
+        state.save_image_on_launch(path);
+    }
 
     info!("launching UI");
-    AppLauncher::with_window(window)
-        .log_to_console()
-        .launch(state)?;
+    launcher.launch(state)?;
 
     Ok(())
 }
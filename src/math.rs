@@ -0,0 +1,420 @@
+use druid::Data;
+use thiserror::Error;
+
+use crate::waveform::{Trace, Waveform};
+
+/// Channel number of the math trace, after the two device channels.
+pub(crate) const MATH_CHANNEL: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum MathOperation {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Invert,
+    Integrate,
+    Differentiate,
+    Expression,
+}
+
+impl MathOperation {
+    pub(crate) fn my_options() -> Vec<(&'static str, MathOperation)> {
+        vec![
+            ("A + B", MathOperation::Add),
+            ("A − B", MathOperation::Subtract),
+            ("A × B", MathOperation::Multiply),
+            ("A / B", MathOperation::Divide),
+            ("−A", MathOperation::Invert),
+            ("∫A dt", MathOperation::Integrate),
+            ("dA/dt", MathOperation::Differentiate),
+            ("Expression", MathOperation::Expression),
+        ]
+    }
+
+    pub(crate) fn uses_source_b(&self) -> bool {
+        matches!(
+            self,
+            MathOperation::Add
+                | MathOperation::Subtract
+                | MathOperation::Multiply
+                | MathOperation::Divide
+        )
+    }
+}
+
+/// Settings of the math trace. Its values are shown in volts whatever the
+/// operation, with a scale and offset of its own.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MathConfig {
+    pub(crate) enabled: bool,
+    pub(crate) operation: MathOperation,
+    pub(crate) source_a: usize,
+    pub(crate) source_b: usize,
+    /// Used by [`MathOperation::Expression`], e.g. `(ch1 - ch2) * 10`.
+    expression: String,
+    /// `expression` parsed once when it is set, rather than per capture.
+    parsed: Result<Expression, MathError>,
+    pub(crate) volts_per_div: f64,
+    pub(crate) offset: f64,
+}
+
+impl Default for MathConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            operation: MathOperation::Subtract,
+            source_a: 1,
+            source_b: 2,
+            expression: "ch1 - ch2".to_string(),
+            parsed: Expression::parse("ch1 - ch2"),
+            volts_per_div: 1.0,
+            offset: 0.0,
+        }
+    }
+}
+
+impl MathConfig {
+    pub(crate) fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    pub(crate) fn set_expression(&mut self, expression: String) {
+        self.parsed = Expression::parse(&expression);
+        self.expression = expression;
+    }
+
+    /// Reports a problem with the expression without needing a capture.
+    pub(crate) fn expression_error(&self) -> Option<&MathError> {
+        self.parsed.as_ref().err()
+    }
+
+    /// Derives the math trace from the device channels of `waveform`.
+    /// Undefined samples, such as a division by zero, come out as zero.
+    pub(crate) fn compute(&self, waveform: &Waveform) -> Result<Trace, MathError> {
+        let a = || source(waveform, self.source_a);
+        let b = || source(waveform, self.source_b);
+        let dt = waveform.sample_interval;
+
+        let volts: Vec<f64> = match self.operation {
+            MathOperation::Add => zip(a()?, b()?, |a, b| a + b),
+            MathOperation::Subtract => zip(a()?, b()?, |a, b| a - b),
+            MathOperation::Multiply => zip(a()?, b()?, |a, b| a * b),
+            MathOperation::Divide => zip(a()?, b()?, |a, b| a / b),
+            MathOperation::Invert => a()?.iter().map(|a| -a).collect(),
+            MathOperation::Integrate => integrate(a()?, dt),
+            MathOperation::Differentiate => differentiate(a()?, dt),
+            MathOperation::Expression => {
+                let expression = self.parsed.as_ref().map_err(Clone::clone)?;
+                (0..waveform.num_samples)
+                    .map(|i| expression.evaluate(waveform, i))
+                    .collect::<Result<_, _>>()?
+            }
+        };
+
+        Ok(Trace {
+            channel: MATH_CHANNEL,
+            volts: volts
+                .into_iter()
+                .map(|v| if v.is_finite() { v } else { 0.0 })
+                .collect(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub(crate) enum MathError {
+    #[error("CH{0} is not in the capture")]
+    MissingChannel(usize),
+    #[error("unexpected '{0}' at {1}")]
+    UnexpectedCharacter(char, usize),
+    #[error("unknown name '{0}', use ch1 or ch2")]
+    UnknownName(String),
+    #[error("unexpected end of expression")]
+    UnexpectedEnd,
+}
+
+fn source(waveform: &Waveform, channel: usize) -> Result<&[f64], MathError> {
+    waveform
+        .trace(channel)
+        .map(|trace| trace.volts.as_slice())
+        .ok_or(MathError::MissingChannel(channel))
+}
+
+fn zip(a: &[f64], b: &[f64], op: impl Fn(f64, f64) -> f64) -> Vec<f64> {
+    a.iter().zip(b).map(|(a, b)| op(*a, *b)).collect()
+}
+
+/// Running integral by the trapezoidal rule, starting at zero.
+fn integrate(volts: &[f64], dt: f64) -> Vec<f64> {
+    let mut sum = 0.0;
+    let mut previous = volts.first().copied().unwrap_or(0.0);
+    volts
+        .iter()
+        .map(|v| {
+            sum += (previous + v) / 2.0 * dt;
+            previous = *v;
+            sum
+        })
+        .collect()
+}
+
+/// Central differences, one-sided at both ends.
+fn differentiate(volts: &[f64], dt: f64) -> Vec<f64> {
+    let last = volts.len().saturating_sub(1);
+    (0..volts.len())
+        .map(|i| {
+            let (before, after) = (i.saturating_sub(1), (i + 1).min(last));
+            match after - before {
+                0 => 0.0,
+                steps => (volts[after] - volts[before]) / (steps as f64 * dt),
+            }
+        })
+        .collect()
+}
+
+/// Arithmetic on `ch1`, `ch2` and numbers with `+ - * /`, unary minus and
+/// parentheses.
+#[derive(Debug, Clone, PartialEq)]
+enum Expression {
+    Number(f64),
+    Channel(usize),
+    Negate(Box<Expression>),
+    Binary(char, Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    fn parse(text: &str) -> Result<Self, MathError> {
+        let mut parser = Parser {
+            chars: text.char_indices().collect(),
+            position: 0,
+        };
+        let expression = parser.sum()?;
+        match parser.peek() {
+            Some((index, c)) => Err(MathError::UnexpectedCharacter(c, index)),
+            None => Ok(expression),
+        }
+    }
+
+    fn evaluate(&self, waveform: &Waveform, index: usize) -> Result<f64, MathError> {
+        Ok(match self {
+            Expression::Number(value) => *value,
+            Expression::Channel(channel) => source(waveform, *channel)?
+                .get(index)
+                .copied()
+                .unwrap_or(0.0),
+            Expression::Negate(inner) => -inner.evaluate(waveform, index)?,
+            Expression::Binary(op, left, right) => {
+                let left = left.evaluate(waveform, index)?;
+                let right = right.evaluate(waveform, index)?;
+                match op {
+                    '+' => left + right,
+                    '-' => left - right,
+                    '*' => left * right,
+                    _ => left / right,
+                }
+            }
+        })
+    }
+}
+
+struct Parser {
+    chars: Vec<(usize, char)>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&mut self) -> Option<(usize, char)> {
+        while let Some((_, c)) = self.chars.get(self.position) {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.position += 1;
+        }
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Result<(usize, char), MathError> {
+        let next = self.peek().ok_or(MathError::UnexpectedEnd)?;
+        self.position += 1;
+        Ok(next)
+    }
+
+    fn sum(&mut self) -> Result<Expression, MathError> {
+        let mut left = self.product()?;
+        while let Some((_, op @ ('+' | '-'))) = self.peek() {
+            self.position += 1;
+            left = Expression::Binary(op, Box::new(left), Box::new(self.product()?));
+        }
+        Ok(left)
+    }
+
+    fn product(&mut self) -> Result<Expression, MathError> {
+        let mut left = self.unary()?;
+        while let Some((_, op @ ('*' | '/'))) = self.peek() {
+            self.position += 1;
+            left = Expression::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expression, MathError> {
+        match self.peek() {
+            Some((_, '-')) => {
+                self.position += 1;
+                Ok(Expression::Negate(Box::new(self.unary()?)))
+            }
+            Some((_, '+')) => {
+                self.position += 1;
+                self.unary()
+            }
+            _ => self.atom(),
+        }
+    }
+
+    fn atom(&mut self) -> Result<Expression, MathError> {
+        match self.next()? {
+            (_, '(') => {
+                let inner = self.sum()?;
+                match self.next()? {
+                    (_, ')') => Ok(inner),
+                    (index, c) => Err(MathError::UnexpectedCharacter(c, index)),
+                }
+            }
+            (start, c) if c.is_ascii_digit() || c == '.' => {
+                let mut text = c.to_string();
+                while let Some((_, c)) = self.chars.get(self.position) {
+                    let exponent_sign = (*c == '-' || *c == '+') && text.ends_with(['e', 'E']);
+                    if !(c.is_ascii_digit() || *c == '.' || *c == 'e' || *c == 'E' || exponent_sign)
+                    {
+                        break;
+                    }
+                    text.push(*c);
+                    self.position += 1;
+                }
+                text.parse()
+                    .map(Expression::Number)
+                    .map_err(|_| MathError::UnexpectedCharacter(c, start))
+            }
+            (_, c) if c.is_ascii_alphabetic() => {
+                let mut name = c.to_string();
+                while let Some((_, c)) = self.chars.get(self.position) {
+                    if !c.is_ascii_alphanumeric() {
+                        break;
+                    }
+                    name.push(*c);
+                    self.position += 1;
+                }
+                match name.to_ascii_lowercase().as_str() {
+                    "ch1" => Ok(Expression::Channel(1)),
+                    "ch2" => Ok(Expression::Channel(2)),
+                    _ => Err(MathError::UnknownName(name)),
+                }
+            }
+            (index, c) => Err(MathError::UnexpectedCharacter(c, index)),
+        }
+    }
+}
+
+/// Display name of a device channel or the math trace.
+pub(crate) fn channel_name(channel: usize) -> String {
+    match channel {
+        MATH_CHANNEL => "MATH".to_string(),
+        _ => format!("CH{}", channel),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waveform() -> Waveform {
+        Waveform {
+            traces: vec![
+                Trace {
+                    channel: 1,
+                    volts: vec![2.0, -1.0],
+                },
+                Trace {
+                    channel: 2,
+                    volts: vec![0.5, 4.0],
+                },
+            ],
+            num_samples: 2,
+            start_time: 0.0,
+            sample_interval: 1e-3,
+        }
+    }
+
+    fn evaluate(text: &str) -> f64 {
+        Expression::parse(text)
+            .unwrap()
+            .evaluate(&waveform(), 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(evaluate("1 + 2 * 3"), 7.0);
+        assert_eq!(evaluate("2 * 3 - 4 / 2"), 4.0);
+        assert_eq!(evaluate("(1 + 2) * 3"), 9.0);
+        assert_eq!(evaluate("8 - 2 - 1"), 5.0);
+        assert_eq!(evaluate("8 / 2 / 2"), 2.0);
+        assert_eq!(evaluate("ch1 - ch2 * 2"), 1.0);
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(evaluate("-ch1"), -2.0);
+        assert_eq!(evaluate("--2"), 2.0);
+        assert_eq!(evaluate("2 * -3"), -6.0);
+        assert_eq!(evaluate("1 - -1"), 2.0);
+        assert_eq!(evaluate("-(ch1 + ch2)"), -2.5);
+        assert_eq!(evaluate("+ch2"), 0.5);
+    }
+
+    #[test]
+    fn exponent_literals() {
+        assert_eq!(evaluate("1e-3"), 1e-3);
+        assert_eq!(evaluate("2.5E+2"), 250.0);
+        assert_eq!(evaluate("1e3 * ch2"), 500.0);
+        assert_eq!(evaluate("ch1-1e-3"), 2.0 - 1e-3);
+        assert_eq!(evaluate(".5"), 0.5);
+    }
+
+    #[test]
+    fn error_positions() {
+        let error = |text: &str| Expression::parse(text).unwrap_err();
+        assert_eq!(error("ch1 + * 2"), MathError::UnexpectedCharacter('*', 6));
+        assert_eq!(error("ch1 ch2"), MathError::UnexpectedCharacter('c', 4));
+        assert_eq!(error("ch1 - 2)"), MathError::UnexpectedCharacter(')', 7));
+        assert_eq!(
+            error("(ch1 - ch2 ch1"),
+            MathError::UnexpectedCharacter('c', 11)
+        );
+        assert_eq!(error("1 + 2e"), MathError::UnexpectedCharacter('2', 4));
+        assert_eq!(error("(ch1 - ch2"), MathError::UnexpectedEnd);
+        assert_eq!(error(""), MathError::UnexpectedEnd);
+        assert_eq!(error("ch3"), MathError::UnknownName("ch3".to_string()));
+    }
+
+    #[test]
+    fn expression_is_parsed_when_set() {
+        let mut config = MathConfig {
+            operation: MathOperation::Expression,
+            ..MathConfig::default()
+        };
+        config.set_expression("(ch1 + ch2) * 2".to_string());
+        assert_eq!(config.expression_error(), None);
+        let trace = config.compute(&waveform()).unwrap();
+        assert_eq!(trace.channel, MATH_CHANNEL);
+        assert_eq!(trace.volts, [5.0, 6.0]);
+
+        config.set_expression("ch1 +".to_string());
+        assert_eq!(config.expression_error(), Some(&MathError::UnexpectedEnd));
+        assert_eq!(
+            config.compute(&waveform()).err(),
+            Some(MathError::UnexpectedEnd)
+        );
+    }
+}
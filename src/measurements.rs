@@ -0,0 +1,425 @@
+use crate::units::format_si;
+use crate::waveform::Trace;
+
+/// Fraction of the amplitude a signal has to move past the mid level before
+/// a crossing counts as an edge, so noise around the mid level is ignored.
+const HYSTERESIS: f64 = 0.1;
+const HISTOGRAM_BINS: usize = 100;
+/// Amplitudes below this are taken for a flat line without edges.
+const MIN_AMPLITUDE: f64 = 1e-9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum Measurement {
+    Vpp,
+    Vmax,
+    Vmin,
+    Vavg,
+    Vrms,
+    Frequency,
+    Period,
+    DutyCycle,
+    RiseTime,
+    FallTime,
+    PositiveWidth,
+    NegativeWidth,
+    Overshoot,
+}
+
+impl Measurement {
+    pub(crate) const ALL: [Measurement; 13] = [
+        Measurement::Vpp,
+        Measurement::Vmax,
+        Measurement::Vmin,
+        Measurement::Vavg,
+        Measurement::Vrms,
+        Measurement::Frequency,
+        Measurement::Period,
+        Measurement::DutyCycle,
+        Measurement::RiseTime,
+        Measurement::FallTime,
+        Measurement::PositiveWidth,
+        Measurement::NegativeWidth,
+        Measurement::Overshoot,
+    ];
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Measurement::Vpp => "Vpp",
+            Measurement::Vmax => "Vmax",
+            Measurement::Vmin => "Vmin",
+            Measurement::Vavg => "Vavg",
+            Measurement::Vrms => "Vrms",
+            Measurement::Frequency => "Freq",
+            Measurement::Period => "Period",
+            Measurement::DutyCycle => "Duty",
+            Measurement::RiseTime => "Rise",
+            Measurement::FallTime => "Fall",
+            Measurement::PositiveWidth => "+Width",
+            Measurement::NegativeWidth => "-Width",
+            Measurement::Overshoot => "Overshoot",
+        }
+    }
+
+    pub(crate) fn format(&self, value: f64) -> String {
+        match self {
+            Measurement::Vpp
+            | Measurement::Vmax
+            | Measurement::Vmin
+            | Measurement::Vavg
+            | Measurement::Vrms => format_si(value, "V"),
+            Measurement::Frequency => format_si(value, "Hz"),
+            Measurement::Period
+            | Measurement::RiseTime
+            | Measurement::FallTime
+            | Measurement::PositiveWidth
+            | Measurement::NegativeWidth => format_si(value, "s"),
+            Measurement::DutyCycle | Measurement::Overshoot => format!("{:.1}%", value),
+        }
+    }
+}
+
+/// Running statistics of one measurement across captures.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Statistics {
+    pub(crate) count: usize,
+    pub(crate) last: f64,
+    pub(crate) min: f64,
+    pub(crate) max: f64,
+    pub(crate) mean: f64,
+    /// Sum of squared differences from the mean, see [`Statistics::std`].
+    m2: f64,
+}
+
+impl Statistics {
+    pub(crate) fn new(value: f64) -> Self {
+        Self {
+            count: 1,
+            last: value,
+            min: value,
+            max: value,
+            mean: value,
+            m2: 0.0,
+        }
+    }
+
+    /// Adds `value` using Welford's online algorithm.
+    pub(crate) fn add(&mut self, value: f64) {
+        self.count += 1;
+        self.last = value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    /// Sample standard deviation.
+    pub(crate) fn std(&self) -> f64 {
+        match self.count {
+            0 | 1 => 0.0,
+            count => (self.m2 / (count - 1) as f64).sqrt(),
+        }
+    }
+}
+
+/// Measures every [`Measurement`] on one trace. Measurements that need edges
+/// are missing when the trace doesn't have enough of them.
+pub(crate) fn measure(trace: &Trace, sample_interval: f64) -> Vec<(Measurement, f64)> {
+    let volts = &trace.volts;
+    if volts.is_empty() {
+        return Vec::new();
+    }
+
+    let max = volts.iter().copied().fold(f64::MIN, f64::max);
+    let min = volts.iter().copied().fold(f64::MAX, f64::min);
+    let avg = volts.iter().sum::<f64>() / volts.len() as f64;
+    let rms = (volts.iter().map(|v| v * v).sum::<f64>() / volts.len() as f64).sqrt();
+
+    let mut results = vec![
+        (Measurement::Vpp, max - min),
+        (Measurement::Vmax, max),
+        (Measurement::Vmin, min),
+        (Measurement::Vavg, avg),
+        (Measurement::Vrms, rms),
+    ];
+
+    let (base, top) = levels(volts, min, max);
+    let amplitude = top - base;
+    if amplitude < MIN_AMPLITUDE {
+        return results;
+    }
+    results.push((Measurement::Overshoot, (max - top) / amplitude * 100.0));
+
+    let dt = sample_interval;
+    let edges = find_edges(volts, base, top);
+    let rising: Vec<f64> = edges.iter().filter(|e| e.rising).map(|e| e.index).collect();
+    let falling: Vec<f64> = edges
+        .iter()
+        .filter(|e| !e.rising)
+        .map(|e| e.index)
+        .collect();
+
+    let period = mean_interval(&rising).or_else(|| mean_interval(&falling));
+    if let Some(period) = period {
+        results.push((Measurement::Period, period * dt));
+        results.push((Measurement::Frequency, 1.0 / (period * dt)));
+    }
+
+    let positive = mean_width(&edges, true);
+    let negative = mean_width(&edges, false);
+    if let Some(width) = positive {
+        results.push((Measurement::PositiveWidth, width * dt));
+    }
+    if let Some(width) = negative {
+        results.push((Measurement::NegativeWidth, width * dt));
+    }
+    if let (Some(positive), Some(negative)) = (positive, negative) {
+        results.push((
+            Measurement::DutyCycle,
+            positive / (positive + negative) * 100.0,
+        ));
+    }
+
+    let low = base + amplitude * 0.1;
+    let high = base + amplitude * 0.9;
+    if let Some(rise) = mean_transition(volts, &rising, low, high) {
+        results.push((Measurement::RiseTime, rise * dt));
+    }
+    if let Some(fall) = mean_transition(volts, &falling, high, low) {
+        results.push((Measurement::FallTime, fall * dt));
+    }
+
+    results
+}
+
+/// Base and top levels: the most common values in the lower and upper half
+/// of the range, which for pulses ignores overshoot and ringing.
+fn levels(volts: &[f64], min: f64, max: f64) -> (f64, f64) {
+    let span = max - min;
+    if span <= 0.0 {
+        return (min, max);
+    }
+
+    let mut histogram = [0usize; HISTOGRAM_BINS];
+    for v in volts {
+        let bin = ((v - min) / span * (HISTOGRAM_BINS - 1) as f64).round() as usize;
+        histogram[bin] += 1;
+    }
+    let mode = |bins: std::ops::Range<usize>| {
+        let bin = bins.max_by_key(|bin| histogram[*bin]).unwrap();
+        min + bin as f64 / (HISTOGRAM_BINS - 1) as f64 * span
+    };
+    (
+        mode(0..HISTOGRAM_BINS / 2),
+        mode(HISTOGRAM_BINS / 2..HISTOGRAM_BINS),
+    )
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Edge {
+    /// Fractional sample index of the mid level crossing.
+    index: f64,
+    rising: bool,
+}
+
+/// Mid level crossings, alternating between rising and falling.
+fn find_edges(volts: &[f64], base: f64, top: f64) -> Vec<Edge> {
+    let mid = (base + top) / 2.0;
+    let band = (top - base) * HYSTERESIS;
+    let mut high = match volts.iter().find(|v| (**v - mid).abs() > band) {
+        Some(v) => *v > mid,
+        None => return Vec::new(),
+    };
+
+    let mut edges = Vec::new();
+    let mut crossing = None;
+    for (i, pair) in volts.windows(2).enumerate() {
+        if (pair[0] - mid) * (pair[1] - mid) <= 0.0 && pair[0] != pair[1] {
+            crossing = Some(i as f64 + (mid - pair[0]) / (pair[1] - pair[0]));
+        }
+        let v = pair[1];
+        if (!high && v > mid + band) || (high && v < mid - band) {
+            high = !high;
+            if let Some(index) = crossing.take() {
+                edges.push(Edge {
+                    index,
+                    rising: high,
+                });
+            }
+        }
+    }
+    edges
+}
+
+fn mean_interval(indices: &[f64]) -> Option<f64> {
+    match indices.len() {
+        0 | 1 => None,
+        n => Some((indices[n - 1] - indices[0]) / (n - 1) as f64),
+    }
+}
+
+/// Mean time from an edge of the given direction to the following edge.
+fn mean_width(edges: &[Edge], rising: bool) -> Option<f64> {
+    let widths: Vec<f64> = edges
+        .windows(2)
+        .filter(|pair| pair[0].rising == rising)
+        .map(|pair| pair[1].index - pair[0].index)
+        .collect();
+    match widths.len() {
+        0 => None,
+        n => Some(widths.iter().sum::<f64>() / n as f64),
+    }
+}
+
+/// Mean time, in samples, the signal takes to go from `from` to `to` around
+/// each edge.
+fn mean_transition(volts: &[f64], edges: &[f64], from: f64, to: f64) -> Option<f64> {
+    let rising = to > from;
+    let past = |v: f64, level: f64| match rising {
+        true => v >= level,
+        false => v <= level,
+    };
+
+    let durations: Vec<f64> = edges
+        .iter()
+        .filter_map(|edge| {
+            let center = edge.floor() as usize;
+            let start = (0..=center).rev().find(|i| !past(volts[*i], from))?;
+            let end = (center..volts.len()).find(|i| past(volts[*i], to))?;
+            let start = interpolate(volts, start, from);
+            let end = interpolate(volts, end.checked_sub(1)?, to);
+            Some(end - start)
+        })
+        .collect();
+    match durations.len() {
+        0 => None,
+        n => Some(durations.iter().sum::<f64>() / n as f64),
+    }
+}
+
+/// Fractional index at which the segment from sample `i` to `i + 1` crosses
+/// `level`.
+fn interpolate(volts: &[f64], i: usize, level: f64) -> f64 {
+    match volts.get(i + 1) {
+        Some(next) if *next != volts[i] => i as f64 + (level - volts[i]) / (next - volts[i]),
+        _ => i as f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_INTERVAL: f64 = 1e-6;
+
+    fn trace(volts: Vec<f64>) -> Trace {
+        Trace { channel: 1, volts }
+    }
+
+    fn get(results: &[(Measurement, f64)], measurement: Measurement) -> f64 {
+        results
+            .iter()
+            .find(|(m, _)| *m == measurement)
+            .map(|(_, value)| *value)
+            .unwrap_or_else(|| panic!("{:?} is missing", measurement))
+    }
+
+    fn assert_close(value: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (value - expected).abs() <= tolerance,
+            "{} != {} ± {}",
+            value,
+            expected,
+            tolerance
+        );
+    }
+
+    /// Ten periods of a 1 kHz, 0 to 2 V pulse at 25% duty, with 10 µs
+    /// linear edges.
+    fn square() -> Vec<f64> {
+        (0..10_000)
+            .map(|i| match i % 1000 {
+                i if i < 10 => 0.2 * i as f64,
+                i if i < 250 => 2.0,
+                i if i < 260 => 2.0 - 0.2 * (i - 250) as f64,
+                _ => 0.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn square_wave() {
+        let results = measure(&trace(square()), SAMPLE_INTERVAL);
+        assert_close(get(&results, Measurement::Vpp), 2.0, 1e-9);
+        assert_close(get(&results, Measurement::Vmin), 0.0, 1e-9);
+        assert_close(get(&results, Measurement::Frequency), 1000.0, 0.1);
+        assert_close(get(&results, Measurement::Period), 1e-3, 1e-7);
+        assert_close(get(&results, Measurement::DutyCycle), 25.0, 0.1);
+        assert_close(get(&results, Measurement::PositiveWidth), 250e-6, 1e-7);
+        // 10% to 90% of a 10 µs linear edge.
+        assert_close(get(&results, Measurement::RiseTime), 8e-6, 1e-7);
+        assert_close(get(&results, Measurement::FallTime), 8e-6, 1e-7);
+        assert_close(get(&results, Measurement::Overshoot), 0.0, 1e-9);
+    }
+
+    #[test]
+    fn sine_wave() {
+        let volts = (0..10_000)
+            .map(|i| (2.0 * std::f64::consts::PI * i as f64 / 1000.0).sin())
+            .collect();
+        let results = measure(&trace(volts), SAMPLE_INTERVAL);
+        assert_close(get(&results, Measurement::Vpp), 2.0, 1e-4);
+        assert_close(get(&results, Measurement::Vavg), 0.0, 1e-9);
+        assert_close(get(&results, Measurement::Vrms), 1.0 / 2f64.sqrt(), 1e-4);
+        assert_close(get(&results, Measurement::Frequency), 1000.0, 0.1);
+        assert_close(get(&results, Measurement::DutyCycle), 50.0, 0.1);
+    }
+
+    #[test]
+    fn flat_line_has_no_edge_measurements() {
+        let results = measure(&trace(vec![0.5; 100]), SAMPLE_INTERVAL);
+        assert_close(get(&results, Measurement::Vavg), 0.5, 1e-9);
+        assert!(results.iter().all(|(m, _)| *m != Measurement::Frequency));
+    }
+
+    #[test]
+    fn levels_ignore_overshoot() {
+        let mut volts = square();
+        for i in (10..10_000).step_by(1000) {
+            volts[i] = 2.6;
+        }
+        let (base, top) = levels(&volts, 0.0, 2.6);
+        assert_close(base, 0.0, 2.6 / 99.0);
+        assert_close(top, 2.0, 2.6 / 99.0);
+
+        let results = measure(&trace(volts), SAMPLE_INTERVAL);
+        assert_close(get(&results, Measurement::Overshoot), 30.0, 2.0);
+    }
+
+    #[test]
+    fn noise_around_mid_level_is_one_edge() {
+        let volts = [0.0, 0.0, 0.49, 0.51, 0.49, 0.51, 1.0, 1.0, 0.0];
+        let edges = find_edges(&volts, 0.0, 1.0);
+        assert_eq!(edges.len(), 2);
+        assert!(edges[0].rising);
+        assert_close(edges[0].index, 4.5, 1e-9);
+        assert!(!edges[1].rising);
+        assert_close(edges[1].index, 7.5, 1e-9);
+    }
+
+    #[test]
+    fn statistics_accumulate() {
+        let values = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        let mut stats = Statistics::new(values[0]);
+        for value in &values[1..] {
+            stats.add(*value);
+        }
+        assert_eq!(stats.count, 8);
+        assert_eq!(stats.last, 9.0);
+        assert_eq!(stats.min, 2.0);
+        assert_eq!(stats.max, 9.0);
+        assert_close(stats.mean, 5.0, 1e-12);
+        assert_close(stats.std(), (32.0f64 / 7.0).sqrt(), 1e-12);
+        assert_eq!(Statistics::new(1.0).std(), 0.0);
+    }
+}
@@ -0,0 +1,187 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context};
+use druid::Data;
+use hanteker_lib::device::cfg::HantekConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::units::{probe_factor, volts_per_div};
+use crate::waveform::Waveform;
+
+/// Number of reference slots, R1 to R4.
+pub(crate) const NUM_REFERENCES: usize = 4;
+
+/// Device settings of one captured channel.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ChannelSettings {
+    pub(crate) channel: usize,
+    pub(crate) coupling: String,
+    pub(crate) probe: String,
+    pub(crate) scale: String,
+    /// Volts per division at the probe tip.
+    pub(crate) volts_per_div: f64,
+    pub(crate) offset: f64,
+}
+
+/// Device settings a reference was captured with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct CaptureSettings {
+    pub(crate) time_scale: String,
+    pub(crate) time_offset: f64,
+    pub(crate) channels: Vec<ChannelSettings>,
+}
+
+impl CaptureSettings {
+    pub(crate) fn from_config(cfg: &HantekConfig, channels: &[usize]) -> Self {
+        let channels = channels
+            .iter()
+            .map(|channel| {
+                let coupling = cfg.channel_coupling.get(channel).cloned().flatten();
+                let probe = cfg.channel_probe.get(channel).cloned().flatten();
+                let scale = cfg.channel_scale.get(channel).cloned().flatten();
+                let offset = cfg.channel_offset.get(channel).copied().flatten();
+                ChannelSettings {
+                    channel: *channel,
+                    coupling: coupling
+                        .map(|c| c.my_to_string().to_string())
+                        .unwrap_or_default(),
+                    probe: probe
+                        .as_ref()
+                        .map(|p| p.my_to_string().to_string())
+                        .unwrap_or_default(),
+                    scale: scale
+                        .as_ref()
+                        .map(|s| s.my_to_string().to_string())
+                        .unwrap_or_default(),
+                    volts_per_div: match (&scale, &probe) {
+                        (Some(scale), Some(probe)) => volts_per_div(scale) * probe_factor(probe),
+                        _ => 1.0,
+                    },
+                    offset: offset.unwrap_or(0.0) as f64,
+                }
+            })
+            .collect();
+
+        Self {
+            time_scale: cfg
+                .time_scale
+                .as_ref()
+                .map(|t| t.my_to_string().to_string())
+                .unwrap_or_default(),
+            time_offset: cfg.time_offset.unwrap_or(0.0) as f64,
+            channels,
+        }
+    }
+
+    pub(crate) fn channel(&self, channel: usize) -> Option<&ChannelSettings> {
+        self.channels.iter().find(|c| c.channel == channel)
+    }
+}
+
+/// A stored capture, including the math trace if it was shown.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Reference {
+    pub(crate) waveform: Waveform,
+    pub(crate) settings: CaptureSettings,
+}
+
+impl Reference {
+    /// Reads the reference stored in `slot`, if there is one.
+    pub(crate) fn load(slot: usize) -> anyhow::Result<Option<Reference>> {
+        let path = path(slot)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let reference = serde_json::from_str(&text)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        Ok(Some(reference))
+    }
+
+    pub(crate) fn save(&self, slot: usize) -> anyhow::Result<()> {
+        let path = path(slot)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        let text = serde_json::to_string(self)?;
+        fs::write(&path, text).with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    pub(crate) fn delete(slot: usize) -> anyhow::Result<()> {
+        let path = path(slot)?;
+        if path.exists() {
+            fs::remove_file(&path)
+                .with_context(|| format!("failed to delete {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+/// File of `slot` in the per-user data directory.
+fn path(slot: usize) -> anyhow::Result<PathBuf> {
+    let dir = dirs::data_dir().ok_or_else(|| anyhow!("no data directory for this user"))?;
+    Ok(dir
+        .join("hanteker")
+        .join("references")
+        .join(format!("{}.json", reference_name(slot))))
+}
+
+/// A reference slot and how its trace is overlaid, independently of the live
+/// channels.
+#[derive(Debug, Clone, Data)]
+pub(crate) struct ReferenceSlot {
+    pub(crate) reference: Option<Arc<Reference>>,
+    pub(crate) shown: bool,
+    /// Trace of the stored capture that is drawn.
+    pub(crate) channel: usize,
+    pub(crate) volts_per_div: f64,
+    pub(crate) offset: f64,
+}
+
+impl Default for ReferenceSlot {
+    fn default() -> Self {
+        Self {
+            reference: None,
+            shown: false,
+            channel: 1,
+            volts_per_div: 1.0,
+            offset: 0.0,
+        }
+    }
+}
+
+impl ReferenceSlot {
+    /// Slot holding `reference`, shown with the scale and offset `channel`
+    /// was captured with.
+    pub(crate) fn new(reference: Reference, channel: usize) -> Self {
+        let channel = match reference.waveform.trace(channel) {
+            Some(_) => channel,
+            None => reference
+                .waveform
+                .traces
+                .first()
+                .map(|trace| trace.channel)
+                .unwrap_or(channel),
+        };
+        let (volts_per_div, offset) = match reference.settings.channel(channel) {
+            Some(settings) => (settings.volts_per_div, settings.offset),
+            None => (1.0, 0.0),
+        };
+        Self {
+            reference: Some(Arc::new(reference)),
+            shown: true,
+            channel,
+            volts_per_div,
+            offset,
+        }
+    }
+}
+
+/// Display name of reference `slot`, counting from zero.
+pub(crate) fn reference_name(slot: usize) -> String {
+    format!("R{}", slot + 1)
+}
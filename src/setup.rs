@@ -0,0 +1,307 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+use druid::FileSpec;
+use hanteker_lib::device::cfg::*;
+use serde::{Deserialize, Serialize};
+
+/// Setup files are JSON, readable and editable by hand.
+pub(crate) const SETUP_FILE: FileSpec = FileSpec::new("Hanteker setup", &["json"]);
+
+/// Settings enums stored by name, the one `my_to_string()` returns.
+trait Named: Sized {
+    fn options() -> Vec<(String, Self)>;
+    fn name(&self) -> String;
+}
+
+macro_rules! named {
+    ($($t:ty),*) => {
+        $(impl Named for $t {
+            fn options() -> Vec<(String, Self)> {
+                <$t>::my_options()
+            }
+
+            fn name(&self) -> String {
+                self.my_to_string().to_string()
+            }
+        })*
+    };
+}
+
+named!(
+    Coupling,
+    Probe,
+    Scale,
+    TimeScale,
+    TriggerMode,
+    AwgType,
+    DeviceFunction,
+    RunningStatus
+);
+
+fn to_name<T: Named>(value: &Option<T>) -> Option<String> {
+    value.as_ref().map(Named::name)
+}
+
+fn from_name<T: Named>(name: &Option<String>, what: &str) -> anyhow::Result<Option<T>> {
+    match name {
+        None => Ok(None),
+        Some(name) => T::options()
+            .into_iter()
+            .map(|(_, value)| value)
+            .find(|value| value.name() == *name)
+            .map(Some)
+            .ok_or_else(|| anyhow!("unknown {} '{}'", what, name)),
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct ChannelSetup {
+    pub(crate) channel: usize,
+    pub(crate) enabled: Option<bool>,
+    pub(crate) coupling: Option<String>,
+    pub(crate) probe: Option<String>,
+    pub(crate) scale: Option<String>,
+    pub(crate) offset: Option<f32>,
+    pub(crate) bandwidth_limit: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct AwgSetup {
+    pub(crate) running: Option<String>,
+    pub(crate) waveform: Option<String>,
+    pub(crate) frequency: Option<f32>,
+    pub(crate) amplitude: Option<f32>,
+    pub(crate) offset: Option<f32>,
+    pub(crate) duty_square: Option<f32>,
+    pub(crate) duty_ramp: Option<f32>,
+    pub(crate) duty_trap_high: Option<f32>,
+    pub(crate) duty_trap_low: Option<f32>,
+    pub(crate) duty_trap_rise: Option<f32>,
+}
+
+/// Settings that only live in the GUI and are never sent to the device.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct GuiSetup {
+    pub(crate) num_captures: usize,
+    pub(crate) acquisition_interval: usize,
+    pub(crate) single_timeout: f32,
+}
+
+/// What the GUI starts with.
+impl Default for GuiSetup {
+    fn default() -> Self {
+        Self {
+            num_captures: 1024,
+            acquisition_interval: 100,
+            single_timeout: 10.0,
+        }
+    }
+}
+
+/// Everything in [`HantekConfig`] plus the GUI-only settings. Settings the
+/// GUI doesn't know yet, like before the first connect, are left out and
+/// keep their current value on load.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Setup {
+    pub(crate) device_function: Option<String>,
+    pub(crate) running: Option<String>,
+    #[serde(default)]
+    pub(crate) channels: Vec<ChannelSetup>,
+    pub(crate) time_scale: Option<String>,
+    pub(crate) time_offset: Option<f32>,
+    pub(crate) trigger_source: Option<usize>,
+    pub(crate) trigger_mode: Option<String>,
+    pub(crate) trigger_level: Option<f32>,
+    #[serde(default)]
+    pub(crate) awg: AwgSetup,
+    pub(crate) gui: Option<GuiSetup>,
+}
+
+impl Setup {
+    pub(crate) fn new(cfg: &HantekConfig, gui: GuiSetup) -> Self {
+        let mut channel_numbers: Vec<usize> = cfg.enabled_channels.keys().copied().collect();
+        channel_numbers.sort_unstable();
+        let channels = channel_numbers
+            .into_iter()
+            .map(|channel| ChannelSetup {
+                channel,
+                enabled: cfg.enabled_channels.get(&channel).copied().flatten(),
+                coupling: to_name(&cfg.channel_coupling.get(&channel).cloned().flatten()),
+                probe: to_name(&cfg.channel_probe.get(&channel).cloned().flatten()),
+                scale: to_name(&cfg.channel_scale.get(&channel).cloned().flatten()),
+                offset: cfg.channel_offset.get(&channel).copied().flatten(),
+                bandwidth_limit: cfg.channel_bandwidth_limit.get(&channel).copied().flatten(),
+            })
+            .collect();
+
+        let trap = cfg.awg_duty_trap.as_ref();
+        Self {
+            device_function: to_name(&cfg.device_function),
+            running: to_name(&cfg.running_status),
+            channels,
+            time_scale: to_name(&cfg.time_scale),
+            time_offset: cfg.time_offset,
+            trigger_source: cfg.trigger_source_channel,
+            trigger_mode: to_name(&cfg.trigger_mode),
+            trigger_level: cfg.trigger_level,
+            awg: AwgSetup {
+                running: to_name(&cfg.awg_running_status),
+                waveform: to_name(&cfg.awg_type),
+                frequency: cfg.awg_frequency,
+                amplitude: cfg.awg_amplitude,
+                offset: cfg.awg_offset,
+                duty_square: cfg.awg_duty_square,
+                duty_ramp: cfg.awg_duty_ramp,
+                duty_trap_high: trap.map(|trap| trap.high),
+                duty_trap_low: trap.map(|trap| trap.low),
+                duty_trap_rise: trap.map(|trap| trap.rise),
+            },
+            gui: Some(gui),
+        }
+    }
+
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("failed to parse {}", path.display()))
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let text = serde_json::to_string_pretty(self)?;
+        fs::write(path, text).with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    /// Reads the setup of the last session, if there is one.
+    pub(crate) fn load_session() -> anyhow::Result<Option<Self>> {
+        let path = session_path()?;
+        match path.exists() {
+            true => Self::load(&path).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    pub(crate) fn save_session(&self) -> anyhow::Result<()> {
+        let path = session_path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        self.save(&path)
+    }
+
+    /// Returns `cfg` with the settings of this setup, or an error naming the
+    /// first setting that can't be applied.
+    pub(crate) fn apply(&self, cfg: &HantekConfig) -> anyhow::Result<HantekConfig> {
+        let mut cfg = cfg.clone();
+
+        if let Some(value) = from_name(&self.device_function, "device function")? {
+            cfg.device_function = Some(value);
+        }
+        if let Some(value) = from_name(&self.running, "running status")? {
+            cfg.running_status = Some(value);
+        }
+
+        for setup in &self.channels {
+            let channel = setup.channel;
+            if !cfg.enabled_channels.contains_key(&channel) {
+                return Err(anyhow!("unknown channel {}", channel));
+            }
+            if let Some(value) = setup.enabled {
+                cfg.enabled_channels.insert(channel, Some(value));
+            }
+            if let Some(value) = from_name(&setup.coupling, "coupling")? {
+                cfg.channel_coupling.insert(channel, Some(value));
+            }
+            if let Some(value) = from_name(&setup.probe, "probe")? {
+                cfg.channel_probe.insert(channel, Some(value));
+            }
+            if let Some(value) = from_name(&setup.scale, "scale")? {
+                cfg.channel_scale.insert(channel, Some(value));
+            }
+            if let Some(value) = setup.offset {
+                cfg.channel_offset.insert(channel, Some(value));
+            }
+            if let Some(value) = setup.bandwidth_limit {
+                cfg.channel_bandwidth_limit.insert(channel, Some(value));
+            }
+        }
+
+        if let Some(value) = from_name(&self.time_scale, "time scale")? {
+            cfg.time_scale = Some(value);
+        }
+        if let Some(value) = self.time_offset {
+            cfg.time_offset = Some(value);
+        }
+        if let Some(value) = self.trigger_source {
+            if !cfg.enabled_channels.contains_key(&value) {
+                return Err(anyhow!("unknown trigger source channel {}", value));
+            }
+            cfg.trigger_source_channel = Some(value);
+        }
+        if let Some(value) = from_name(&self.trigger_mode, "trigger mode")? {
+            cfg.trigger_mode = Some(value);
+        }
+        if let Some(value) = self.trigger_level {
+            cfg.trigger_level = Some(value);
+        }
+
+        let awg = &self.awg;
+        if let Some(value) = from_name(&awg.running, "AWG running status")? {
+            cfg.awg_running_status = Some(value);
+        }
+        if let Some(value) = from_name(&awg.waveform, "AWG waveform")? {
+            cfg.awg_type = Some(value);
+        }
+        if let Some(value) = awg.frequency {
+            cfg.awg_frequency = Some(value);
+        }
+        if let Some(value) = awg.amplitude {
+            cfg.awg_amplitude = Some(value);
+        }
+        if let Some(value) = awg.offset {
+            cfg.awg_offset = Some(value);
+        }
+        if let Some(value) = awg.duty_square {
+            cfg.awg_duty_square = Some(value);
+        }
+        if let Some(value) = awg.duty_ramp {
+            cfg.awg_duty_ramp = Some(value);
+        }
+        if awg.duty_trap_high.is_some()
+            || awg.duty_trap_low.is_some()
+            || awg.duty_trap_rise.is_some()
+        {
+            let mut trap = cfg.awg_duty_trap.clone().unwrap_or(TrapDuty::ZERO);
+            trap.high = awg.duty_trap_high.unwrap_or(trap.high);
+            trap.low = awg.duty_trap_low.unwrap_or(trap.low);
+            trap.rise = awg.duty_trap_rise.unwrap_or(trap.rise);
+            cfg.awg_duty_trap = Some(trap);
+        }
+
+        Ok(cfg)
+    }
+}
+
+/// Setup of the last session in the per-user config directory.
+fn session_path() -> anyhow::Result<PathBuf> {
+    let dir = dirs::config_dir().ok_or_else(|| anyhow!("no config directory for this user"))?;
+    Ok(dir.join("hanteker").join("session.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gui_setup_missing_fields_keep_defaults() {
+        let gui: GuiSetup = serde_json::from_str(r#"{"num_captures": 512}"#).unwrap();
+        assert_eq!(gui.num_captures, 512);
+        assert_eq!(gui.acquisition_interval, 100);
+        assert_eq!(gui.single_timeout, 10.0);
+    }
+}
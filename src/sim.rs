@@ -0,0 +1,523 @@
+use std::f64::consts::PI;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use hanteker_lib::device::cfg::{
+    AwgType, Coupling, DeviceFunction, HantekConfig, Probe, RunningStatus, Scale, TimeScale,
+    TrapDuty, TriggerMode,
+};
+
+use crate::device::{DeviceOpener, DeviceResult, ScopeDevice};
+use crate::units::{
+    probe_factor, seconds_per_div, volts_per_div, ADC_CENTER, ADC_COUNTS_PER_DIV, DIVS_X,
+};
+
+/// Noise added to every sample, in ADC counts.
+const NOISE_COUNTS: f64 = 1.5;
+
+/// Upper bound on how long a simulated capture pretends to take.
+const MAX_CAPTURE_DELAY: Duration = Duration::from_millis(100);
+
+/// How often the simulated user at the front panel changes something.
+const FRONT_PANEL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A virtual 2D42 whose channel 1 is wired to its own AWG output and whose
+/// channel 2 sees the same signal through an RC network (half the amplitude,
+/// lagging by a quarter period).
+pub(crate) struct SimulatedHantek {
+    cfg: HantekConfig,
+    started: Instant,
+    noise_state: u64,
+    last_capture: Option<Vec<u8>>,
+    /// When the front panel was last touched, `None` if it never is.
+    front_panel_edited: Option<Instant>,
+}
+
+pub(crate) struct SimulatorOpener {
+    pub(crate) front_panel_edits: bool,
+}
+
+impl DeviceOpener for SimulatorOpener {
+    fn open(&self) -> DeviceResult<Box<dyn ScopeDevice + '_>> {
+        let mut simulator = SimulatedHantek::new();
+        if self.front_panel_edits {
+            simulator.front_panel_edited = Some(Instant::now());
+        }
+        Ok(Box::new(simulator))
+    }
+}
+
+impl ScopeDevice for SimulatedHantek {
+    fn claim(&mut self) -> DeviceResult<()> {
+        Ok(())
+    }
+
+    fn release(&mut self) -> DeviceResult<()> {
+        Ok(())
+    }
+
+    fn set_device_function(&mut self, device_function: DeviceFunction) -> DeviceResult<()> {
+        self.cfg.device_function = Some(device_function);
+        Ok(())
+    }
+
+    fn set_running(&mut self, status: RunningStatus) -> DeviceResult<()> {
+        self.cfg.running_status = Some(status);
+        Ok(())
+    }
+
+    fn set_channel_enabled(&mut self, channel: usize, enabled: bool) -> DeviceResult<()> {
+        self.cfg.enabled_channels.insert(channel, Some(enabled));
+        Ok(())
+    }
+
+    fn set_channel_coupling(&mut self, channel: usize, coupling: Coupling) -> DeviceResult<()> {
+        self.cfg.channel_coupling.insert(channel, Some(coupling));
+        Ok(())
+    }
+
+    fn set_channel_probe(&mut self, channel: usize, probe: Probe) -> DeviceResult<()> {
+        self.cfg.channel_probe.insert(channel, Some(probe));
+        Ok(())
+    }
+
+    fn set_channel_scale(&mut self, channel: usize, scale: Scale) -> DeviceResult<()> {
+        self.cfg.channel_scale.insert(channel, Some(scale));
+        Ok(())
+    }
+
+    fn set_channel_offset(&mut self, channel: usize, offset: f32) -> DeviceResult<()> {
+        self.cfg.channel_offset.insert(channel, Some(offset));
+        Ok(())
+    }
+
+    fn set_bandwidth_limit(&mut self, channel: usize, enabled: bool) -> DeviceResult<()> {
+        self.cfg
+            .channel_bandwidth_limit
+            .insert(channel, Some(enabled));
+        Ok(())
+    }
+
+    fn set_time_scale(&mut self, time_scale: TimeScale) -> DeviceResult<()> {
+        self.cfg.time_scale = Some(time_scale);
+        Ok(())
+    }
+
+    fn set_time_offset(&mut self, offset: f32) -> DeviceResult<()> {
+        self.cfg.time_offset = Some(offset);
+        Ok(())
+    }
+
+    fn set_trigger_source(&mut self, channel: usize) -> DeviceResult<()> {
+        self.cfg.trigger_source_channel = Some(channel);
+        Ok(())
+    }
+
+    fn set_trigger_mode(&mut self, mode: TriggerMode) -> DeviceResult<()> {
+        self.cfg.trigger_mode = Some(mode);
+        Ok(())
+    }
+
+    fn set_trigger_level(&mut self, level: f32) -> DeviceResult<()> {
+        self.cfg.trigger_level = Some(level);
+        Ok(())
+    }
+
+    fn set_awg_running(&mut self, status: RunningStatus) -> DeviceResult<()> {
+        self.cfg.awg_running_status = Some(status);
+        Ok(())
+    }
+
+    fn set_awg_frequency(&mut self, frequency: f32) -> DeviceResult<()> {
+        self.cfg.awg_frequency = Some(frequency);
+        Ok(())
+    }
+
+    fn set_awg_amplitude(&mut self, amplitude: f32) -> DeviceResult<()> {
+        self.cfg.awg_amplitude = Some(amplitude);
+        Ok(())
+    }
+
+    fn set_awg_type(&mut self, awg_type: AwgType) -> DeviceResult<()> {
+        self.cfg.awg_type = Some(awg_type);
+        Ok(())
+    }
+
+    fn set_awg_offset(&mut self, offset: f32) -> DeviceResult<()> {
+        self.cfg.awg_offset = Some(offset);
+        Ok(())
+    }
+
+    fn set_awg_duty_square(&mut self, duty: f32) -> DeviceResult<()> {
+        self.cfg.awg_duty_square = Some(duty);
+        Ok(())
+    }
+
+    fn set_awg_duty_ramp(&mut self, duty: f32) -> DeviceResult<()> {
+        self.cfg.awg_duty_ramp = Some(duty);
+        Ok(())
+    }
+
+    fn set_awg_duty_trap(&mut self, high: f32, low: f32, rise: f32) -> DeviceResult<()> {
+        self.cfg.awg_duty_trap = Some(TrapDuty { high, low, rise });
+        Ok(())
+    }
+
+    fn capture(&mut self, channels: Vec<usize>, num_samples: usize) -> DeviceResult<Vec<u8>> {
+        let seconds_per_div =
+            seconds_per_div(self.cfg.time_scale.as_ref().unwrap_or(&TimeScale::ms1));
+        let span = seconds_per_div * DIVS_X as f64;
+        thread::sleep(Duration::from_secs_f64(span).min(MAX_CAPTURE_DELAY));
+
+        let expected_len = channels.len() * num_samples;
+        let trigger_time = self.trigger_time();
+        let single = matches!(self.cfg.trigger_mode, Some(TriggerMode::Single));
+        let normal = matches!(self.cfg.trigger_mode, Some(TriggerMode::Normal));
+        let stopped = matches!(self.cfg.running_status, Some(RunningStatus::Stop));
+        // Without a trigger, normal and single mode keep the last capture.
+        let held = stopped || (trigger_time.is_none() && (single || normal));
+        if let Some(last_capture) = &self.last_capture {
+            if held && last_capture.len() == expected_len {
+                return Ok(last_capture.clone());
+            }
+        }
+        // After its trigger, single mode stops.
+        if single && trigger_time.is_some() {
+            self.cfg.running_status = Some(RunningStatus::Stop);
+        }
+
+        let dt = span / num_samples as f64;
+        let t_base = trigger_time.unwrap_or_else(|| self.started.elapsed().as_secs_f64())
+            + self.cfg.time_offset.unwrap_or(0.0) as f64
+            - span / 2.0;
+
+        let mut capture = Vec::with_capacity(expected_len);
+        for i in 0..num_samples {
+            let t = t_base + i as f64 * dt;
+            for &channel in &channels {
+                let sample = self.sample(channel, t);
+                capture.push(sample);
+            }
+        }
+
+        self.last_capture = Some(capture.clone());
+        Ok(capture)
+    }
+
+    fn read_settings(&mut self) -> DeviceResult<Option<HantekConfig>> {
+        if let Some(edited) = self.front_panel_edited {
+            if edited.elapsed() >= FRONT_PANEL_INTERVAL {
+                self.front_panel_edited = Some(Instant::now());
+                self.front_panel(toggle_coupling);
+            }
+        }
+        Ok(Some(self.cfg.clone()))
+    }
+}
+
+impl SimulatedHantek {
+    fn new() -> Self {
+        Self {
+            cfg: HantekConfig::new(2),
+            started: Instant::now(),
+            noise_state: 0x2545_f491_4f6c_dd1d,
+            last_capture: None,
+            front_panel_edited: None,
+        }
+    }
+
+    /// Changes settings the way the front panel would, behind the back of
+    /// whoever sent them.
+    fn front_panel(&mut self, edit: impl FnOnce(&mut HantekConfig)) {
+        edit(&mut self.cfg);
+    }
+
+    fn sample(&mut self, channel: usize, t: f64) -> u8 {
+        let scale = self
+            .cfg
+            .channel_scale
+            .get(&channel)
+            .cloned()
+            .flatten()
+            .unwrap_or(Scale::v10);
+        let probe = self
+            .cfg
+            .channel_probe
+            .get(&channel)
+            .cloned()
+            .flatten()
+            .unwrap_or(Probe::X1);
+        let offset = self
+            .cfg
+            .channel_offset
+            .get(&channel)
+            .cloned()
+            .flatten()
+            .unwrap_or(0.0) as f64;
+        let bw_limit = self
+            .cfg
+            .channel_bandwidth_limit
+            .get(&channel)
+            .cloned()
+            .flatten()
+            .unwrap_or(false);
+
+        let volts_per_div = volts_per_div(&scale) * probe_factor(&probe);
+        let noise = match bw_limit {
+            true => NOISE_COUNTS / 3.0,
+            false => NOISE_COUNTS,
+        } * self.next_noise();
+
+        let counts =
+            (self.input_voltage(channel, t) + offset) / volts_per_div * ADC_COUNTS_PER_DIV + noise;
+        (ADC_CENTER + counts).round().clamp(0.0, 255.0) as u8
+    }
+
+    /// Voltage at the probe tip of `channel`, after the coupling stage.
+    fn input_voltage(&self, channel: usize, t: f64) -> f64 {
+        let ac = matches!(
+            self.cfg.channel_coupling.get(&channel).cloned().flatten(),
+            Some(Coupling::AC)
+        );
+        let awg_offset = self.cfg.awg_offset.unwrap_or(0.0) as f64;
+
+        let frequency = self.awg_frequency();
+        let (gain, lag) = match channel {
+            1 => (1.0, 0.0),
+            _ if frequency > 0.0 => (0.5, 0.25 / frequency),
+            _ => (0.5, 0.0),
+        };
+
+        let mut voltage = gain * self.awg_voltage(t - lag);
+        if ac && self.is_awg_running() {
+            voltage -= gain * awg_offset;
+        }
+        voltage
+    }
+
+    /// Time of the first rising crossing of the trigger level after now,
+    /// `None` when the signal never reaches the level.
+    fn trigger_time(&self) -> Option<f64> {
+        let now = self.started.elapsed().as_secs_f64();
+        let frequency = self.awg_frequency();
+        if !self.is_awg_running() || frequency <= 0.0 {
+            return None;
+        }
+
+        let period = 1.0 / frequency;
+        let start = (now / period).floor() * period;
+        let source = self.cfg.trigger_source_channel.unwrap_or(1);
+        let level = self.cfg.trigger_level.unwrap_or(0.0) as f64;
+
+        const STEPS: usize = 1000;
+        let step = period / STEPS as f64;
+        let mut previous = self.input_voltage(source, start);
+        for i in 1..=STEPS {
+            let t = start + i as f64 * step;
+            let current = self.input_voltage(source, t);
+            if previous < level && current >= level {
+                return Some(t);
+            }
+            previous = current;
+        }
+
+        None
+    }
+
+    fn is_awg_running(&self) -> bool {
+        matches!(self.cfg.awg_running_status, Some(RunningStatus::Start))
+    }
+
+    fn awg_frequency(&self) -> f64 {
+        self.cfg.awg_frequency.unwrap_or(0.0) as f64
+    }
+
+    fn awg_voltage(&self, t: f64) -> f64 {
+        if !self.is_awg_running() {
+            return 0.0;
+        }
+
+        let amplitude = self.cfg.awg_amplitude.unwrap_or(0.0) as f64;
+        let offset = self.cfg.awg_offset.unwrap_or(0.0) as f64;
+        let phase = (t * self.awg_frequency()).rem_euclid(1.0);
+
+        let shape = match self.cfg.awg_type.as_ref() {
+            Some(AwgType::Square) => {
+                let duty = duty_fraction(self.cfg.awg_duty_square.unwrap_or(0.0));
+                if phase < duty {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Some(AwgType::Ramp) => {
+                let duty = duty_fraction(self.cfg.awg_duty_ramp.unwrap_or(0.0));
+                if phase < duty {
+                    -1.0 + 2.0 * phase / duty
+                } else {
+                    1.0 - 2.0 * (phase - duty) / (1.0 - duty)
+                }
+            }
+            Some(AwgType::Trap) => trapezoid(
+                self.cfg.awg_duty_trap.as_ref().unwrap_or(&TrapDuty::ZERO),
+                phase,
+            ),
+            _ => (2.0 * PI * phase).sin(),
+        };
+
+        offset + amplitude * shape
+    }
+
+    /// Triangular-ish noise in [-1, 1] from a xorshift generator.
+    fn next_noise(&mut self) -> f64 {
+        let mut next = || {
+            self.noise_state ^= self.noise_state << 13;
+            self.noise_state ^= self.noise_state >> 7;
+            self.noise_state ^= self.noise_state << 17;
+            (self.noise_state >> 11) as f64 / (1u64 << 53) as f64
+        };
+        next() + next() - 1.0
+    }
+}
+
+/// Switches CH1 between AC and DC coupling, which shows on screen whenever
+/// the AWG has an offset.
+fn toggle_coupling(cfg: &mut HantekConfig) {
+    let coupling = match cfg.channel_coupling.get(&1).cloned().flatten() {
+        Some(Coupling::AC) => Coupling::DC,
+        _ => Coupling::AC,
+    };
+    cfg.channel_coupling.insert(1, Some(coupling));
+}
+
+/// Duty cycles may be given as a fraction or a percentage, anything outside
+/// (0, 1) falls back to 50%.
+fn duty_fraction(duty: f32) -> f64 {
+    let duty = duty as f64;
+    let duty = if duty > 1.0 { duty / 100.0 } else { duty };
+    if duty <= 0.0 || duty >= 1.0 {
+        0.5
+    } else {
+        duty
+    }
+}
+
+fn trapezoid(duty: &TrapDuty, phase: f64) -> f64 {
+    let total = (2.0 * duty.rise + duty.high + duty.low) as f64;
+    let (rise, high) = match total > 0.0 {
+        true => (duty.rise as f64 / total, duty.high as f64 / total),
+        false => (0.25, 0.25),
+    };
+
+    if phase < rise {
+        -1.0 + 2.0 * phase / rise
+    } else if phase < rise + high {
+        1.0
+    } else if phase < 2.0 * rise + high {
+        1.0 - 2.0 * (phase - rise - high) / rise
+    } else {
+        -1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::waveform::Waveform;
+
+    /// A simulator running a 1 kHz sine of `amplitude` volts on its AWG.
+    fn with_sine(amplitude: f32) -> SimulatedHantek {
+        let mut simulator = SimulatedHantek::new();
+        simulator.set_awg_type(AwgType::Sin).unwrap();
+        simulator.set_awg_frequency(1000.0).unwrap();
+        simulator.set_awg_amplitude(amplitude).unwrap();
+        simulator.set_awg_running(RunningStatus::Start).unwrap();
+        simulator.set_running(RunningStatus::Start).unwrap();
+        simulator
+    }
+
+    fn half_peak_to_peak(values: impl Iterator<Item = f64> + Clone) -> f64 {
+        let max = values.clone().fold(f64::MIN, f64::max);
+        let min = values.fold(f64::MAX, f64::min);
+        (max - min) / 2.0
+    }
+
+    /// Rising zero crossings, with some hysteresis against the noise.
+    fn periods(volts: &[f64], hysteresis: f64) -> usize {
+        let mut below = false;
+        let mut count = 0;
+        for v in volts {
+            if *v < -hysteresis {
+                below = true;
+            } else if below && *v > hysteresis {
+                below = false;
+                count += 1;
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn capture_follows_settings() {
+        let mut simulator = with_sine(1.0);
+        simulator.set_time_scale(TimeScale::ms1).unwrap();
+        for (scale, volts_per_div) in [(Scale::v1, 1.0), (Scale::v2, 2.0)] {
+            simulator.set_channel_scale(1, scale.clone()).unwrap();
+            simulator.set_channel_scale(2, scale).unwrap();
+            let capture = simulator.capture(vec![1, 2], 1000).unwrap();
+            assert_eq!(capture.len(), 2 * 1000);
+
+            // CH1 sees the full amplitude and CH2 half of it, both centered
+            // on whole periods.
+            for (channel, volts) in [(0, 1.0), (1, 0.5)] {
+                let counts = capture.iter().skip(channel).step_by(2).map(|c| *c as f64);
+                let mean = counts.clone().sum::<f64>() / 1000.0;
+                assert!((mean - ADC_CENTER).abs() < 1.0, "mean={}", mean);
+                let amplitude = half_peak_to_peak(counts);
+                let expected = volts / volts_per_div * ADC_COUNTS_PER_DIV;
+                assert!(
+                    (amplitude - expected).abs() < NOISE_COUNTS + 1.0,
+                    "amplitude={} expected={}",
+                    amplitude,
+                    expected
+                );
+            }
+
+            let waveform = Waveform::decode(&capture, &[1, 2], &simulator.cfg);
+            assert_eq!(waveform.num_samples, 1000);
+            assert!((waveform.sample_interval - 10e-6).abs() < 1e-12);
+            let tolerance = (NOISE_COUNTS + 1.0) / ADC_COUNTS_PER_DIV * volts_per_div;
+            for (channel, volts) in [(1, 1.0), (2, 0.5)] {
+                let trace = waveform.trace(channel).unwrap();
+                let amplitude = half_peak_to_peak(trace.volts.iter().copied());
+                assert!(
+                    (amplitude - volts).abs() < tolerance,
+                    "amplitude={}",
+                    amplitude
+                );
+            }
+        }
+
+        // Twice the time per division holds twice the periods.
+        for (time_scale, expected) in [(TimeScale::ms1, 10), (TimeScale::ms2, 20)] {
+            simulator.set_time_scale(time_scale).unwrap();
+            let capture = simulator.capture(vec![1], 1000).unwrap();
+            let waveform = Waveform::decode(&capture, &[1], &simulator.cfg);
+            let found = periods(&waveform.trace(1).unwrap().volts, 0.2);
+            assert!((found as i64 - expected).abs() <= 1, "periods={}", found);
+        }
+    }
+
+    #[test]
+    fn single_mode_stops_after_trigger() {
+        let mut simulator = with_sine(1.0);
+        simulator.set_trigger_mode(TriggerMode::Single).unwrap();
+
+        let first = simulator.capture(vec![1], 100).unwrap();
+        assert!(matches!(
+            simulator.cfg.running_status,
+            Some(RunningStatus::Stop)
+        ));
+        assert_eq!(simulator.capture(vec![1], 100).unwrap(), first);
+    }
+}
@@ -0,0 +1,232 @@
+use std::f64::consts::PI;
+
+use druid::Data;
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+
+use crate::waveform::{Trace, Waveform};
+
+/// Spectra at or below this power (Vrms², -200 dBV) are drawn at the floor.
+const MIN_POWER: f64 = 1e-20;
+
+/// Window applied to the samples before the FFT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum Window {
+    Rectangular,
+    Hann,
+    Hamming,
+    BlackmanHarris,
+    FlatTop,
+}
+
+impl Window {
+    pub(crate) fn my_options() -> Vec<(&'static str, Window)> {
+        vec![
+            ("Rectangular", Window::Rectangular),
+            ("Hann", Window::Hann),
+            ("Hamming", Window::Hamming),
+            ("Blackman-Harris", Window::BlackmanHarris),
+            ("Flat Top", Window::FlatTop),
+        ]
+    }
+
+    /// Cosine-sum coefficients, `w(n) = a0 - a1 cos(x) + a2 cos(2x) - ...`.
+    fn coefficients(&self) -> &'static [f64] {
+        match self {
+            Window::Rectangular => &[1.0],
+            Window::Hann => &[0.5, 0.5],
+            Window::Hamming => &[0.54, 0.46],
+            Window::BlackmanHarris => &[0.35875, 0.48829, 0.14128, 0.01168],
+            Window::FlatTop => &[
+                0.21557895,
+                0.41663158,
+                0.277263158,
+                0.083578947,
+                0.006947368,
+            ],
+        }
+    }
+
+    fn weights(&self, len: usize) -> Vec<f64> {
+        let coefficients = self.coefficients();
+        (0..len)
+            .map(|n| {
+                let x = 2.0 * PI * n as f64 / len as f64;
+                coefficients
+                    .iter()
+                    .enumerate()
+                    .map(|(k, a)| match k % 2 {
+                        0 => a * (k as f64 * x).cos(),
+                        _ => -a * (k as f64 * x).cos(),
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum SpectrumScale {
+    Linear,
+    Dbv,
+}
+
+impl SpectrumScale {
+    pub(crate) fn my_options() -> Vec<(&'static str, SpectrumScale)> {
+        vec![
+            ("Linear", SpectrumScale::Linear),
+            ("dBV", SpectrumScale::Dbv),
+        ]
+    }
+
+    /// Displayed value of a bin with the given power in Vrms².
+    pub(crate) fn value(&self, power: f64) -> f64 {
+        match self {
+            SpectrumScale::Linear => power.sqrt(),
+            SpectrumScale::Dbv => 10.0 * power.max(MIN_POWER).log10(),
+        }
+    }
+}
+
+/// Single-sided amplitude spectrum of one channel.
+#[derive(Debug, Clone)]
+pub(crate) struct Spectrum {
+    pub(crate) channel: usize,
+    /// Power per bin in Vrms², bin `i` is at `i * bin_width` Hz.
+    pub(crate) power: Vec<f64>,
+    pub(crate) bin_width: f64,
+    pub(crate) sample_rate: f64,
+}
+
+impl Spectrum {
+    /// Windowed FFT of `trace`, scaled so a sine on a bin center reads its
+    /// RMS voltage regardless of the window.
+    pub(crate) fn compute(trace: &Trace, sample_interval: f64, window: Window) -> Self {
+        let len = trace.volts.len();
+        if len < 2 || sample_interval <= 0.0 {
+            return Self {
+                channel: trace.channel,
+                power: Vec::new(),
+                bin_width: 0.0,
+                sample_rate: 0.0,
+            };
+        }
+
+        let weights = window.weights(len);
+        let gain: f64 = weights.iter().sum();
+        let mut buffer: Vec<Complex<f64>> = trace
+            .volts
+            .iter()
+            .zip(&weights)
+            .map(|(v, w)| Complex::new(v * w, 0.0))
+            .collect();
+        FftPlanner::new().plan_fft_forward(len).process(&mut buffer);
+
+        let power = buffer[..len / 2 + 1]
+            .iter()
+            .enumerate()
+            .map(|(i, bin)| {
+                let peak = bin.norm() / gain;
+                match i {
+                    // DC has no mirror image, and its "RMS" is the value itself.
+                    0 => peak * peak,
+                    _ => 2.0 * peak * peak,
+                }
+            })
+            .collect();
+
+        Self {
+            channel: trace.channel,
+            power,
+            bin_width: 1.0 / (sample_interval * len as f64),
+            sample_rate: 1.0 / sample_interval,
+        }
+    }
+
+    pub(crate) fn frequency(&self, bin: usize) -> f64 {
+        bin as f64 * self.bin_width
+    }
+
+    /// Nyquist frequency, the right edge of the spectrum.
+    pub(crate) fn max_frequency(&self) -> f64 {
+        self.sample_rate / 2.0
+    }
+
+    /// Whether `other` can be averaged into this spectrum.
+    fn is_compatible(&self, other: &Spectrum) -> bool {
+        self.channel == other.channel
+            && self.power.len() == other.power.len()
+            && self.bin_width == other.bin_width
+    }
+
+    /// Indices of the `count` strongest local maxima, strongest first. DC is
+    /// left out.
+    pub(crate) fn peaks(&self, count: usize) -> Vec<usize> {
+        let mut peaks: Vec<usize> = (1..self.power.len().saturating_sub(1))
+            .filter(|i| {
+                self.power[*i] > self.power[i - 1]
+                    && self.power[*i] >= self.power[i + 1]
+                    && self.power[*i] > MIN_POWER
+            })
+            .collect();
+        peaks.sort_by(|a, b| self.power[*b].total_cmp(&self.power[*a]));
+        peaks.truncate(count);
+        peaks
+    }
+}
+
+/// Spectra of all channels of the captures seen so far, averaged over up to
+/// `averages` captures.
+#[derive(Debug, Clone)]
+pub(crate) struct Spectra {
+    pub(crate) spectra: Vec<Spectrum>,
+    /// Number of captures that went into the average.
+    pub(crate) count: usize,
+}
+
+impl Spectra {
+    pub(crate) fn compute(waveform: &Waveform, window: Window) -> Self {
+        let spectra = waveform
+            .traces
+            .iter()
+            .map(|trace| Spectrum::compute(trace, waveform.sample_interval, window))
+            .collect();
+        Self { spectra, count: 1 }
+    }
+
+    /// Averages the power of `next` into these spectra. Past `averages`
+    /// captures the average turns exponential, so it keeps following the
+    /// signal. Spectra that don't line up (other channels, sample rate or
+    /// length) replace the average instead.
+    pub(crate) fn average(&self, next: Spectra, averages: usize) -> Self {
+        let compatible = self.spectra.len() == next.spectra.len()
+            && self
+                .spectra
+                .iter()
+                .zip(&next.spectra)
+                .all(|(a, b)| a.is_compatible(b));
+        if !compatible || averages <= 1 {
+            return next;
+        }
+
+        let count = (self.count + 1).min(averages);
+        let spectra = self
+            .spectra
+            .iter()
+            .zip(next.spectra)
+            .map(|(average, mut next)| {
+                for (power, old) in next.power.iter_mut().zip(&average.power) {
+                    *power = old + (*power - old) / count as f64;
+                }
+                next
+            })
+            .collect();
+        Self { spectra, count }
+    }
+
+    pub(crate) fn spectrum(&self, channel: usize) -> Option<&Spectrum> {
+        self.spectra
+            .iter()
+            .find(|spectrum| spectrum.channel == channel)
+    }
+}
@@ -0,0 +1,273 @@
+use druid::Data;
+use hanteker_lib::device::cfg::HantekConfig;
+
+use crate::comm::Control;
+
+/// One setting the device reports differently from what the GUI assumed.
+#[derive(Debug, Clone)]
+pub(crate) struct Mismatch {
+    pub(crate) control: Control,
+    pub(crate) setting: String,
+    pub(crate) gui: String,
+    pub(crate) device: String,
+}
+
+/// Copies every setting `device` reports into `cfg` and returns the ones
+/// that differed. Settings the device can't report are `None` in `device`
+/// and left alone, so are settings whose request is still in flight
+/// (`is_pending`), since the device hasn't seen their new value yet.
+pub(crate) fn reconcile(
+    cfg: &mut HantekConfig,
+    device: &HantekConfig,
+    is_pending: impl Fn(Control) -> bool,
+) -> Vec<Mismatch> {
+    let mut sync = Sync {
+        is_pending,
+        mismatches: Vec::new(),
+    };
+
+    sync.field(
+        Control::DeviceFunction,
+        "device function".to_string(),
+        &mut cfg.device_function,
+        &device.device_function,
+        |value| value.my_to_string().to_string(),
+    );
+    sync.field(
+        Control::Running,
+        "running".to_string(),
+        &mut cfg.running_status,
+        &device.running_status,
+        |value| value.my_to_string().to_string(),
+    );
+
+    for channel in [1, 2] {
+        sync.field(
+            Control::ChannelEnable(channel),
+            format!("CH{} enabled", channel),
+            cfg.enabled_channels.entry(channel).or_insert(None),
+            &device.enabled_channels.get(&channel).copied().flatten(),
+            |value| value.to_string(),
+        );
+        sync.field(
+            Control::Coupling(channel),
+            format!("CH{} coupling", channel),
+            cfg.channel_coupling.entry(channel).or_insert(None),
+            &device.channel_coupling.get(&channel).cloned().flatten(),
+            |value| value.my_to_string().to_string(),
+        );
+        sync.field(
+            Control::Probe(channel),
+            format!("CH{} probe", channel),
+            cfg.channel_probe.entry(channel).or_insert(None),
+            &device.channel_probe.get(&channel).cloned().flatten(),
+            |value| value.my_to_string().to_string(),
+        );
+        sync.field(
+            Control::Scale(channel),
+            format!("CH{} scale", channel),
+            cfg.channel_scale.entry(channel).or_insert(None),
+            &device.channel_scale.get(&channel).cloned().flatten(),
+            |value| value.my_to_string().to_string(),
+        );
+        sync.field(
+            Control::Offset(channel),
+            format!("CH{} offset", channel),
+            cfg.channel_offset.entry(channel).or_insert(None),
+            &device.channel_offset.get(&channel).copied().flatten(),
+            |value| value.to_string(),
+        );
+        sync.field(
+            Control::BwLimit(channel),
+            format!("CH{} bandwidth limit", channel),
+            cfg.channel_bandwidth_limit.entry(channel).or_insert(None),
+            &device
+                .channel_bandwidth_limit
+                .get(&channel)
+                .copied()
+                .flatten(),
+            |value| value.to_string(),
+        );
+    }
+
+    sync.field(
+        Control::TimeScale,
+        "time scale".to_string(),
+        &mut cfg.time_scale,
+        &device.time_scale,
+        |value| value.my_to_string().to_string(),
+    );
+    sync.field(
+        Control::TimeOffset,
+        "time offset".to_string(),
+        &mut cfg.time_offset,
+        &device.time_offset,
+        |value| value.to_string(),
+    );
+    sync.field(
+        Control::TriggerSource,
+        "trigger source".to_string(),
+        &mut cfg.trigger_source_channel,
+        &device.trigger_source_channel,
+        |value| format!("CH{}", value),
+    );
+    sync.field(
+        Control::TriggerMode,
+        "trigger mode".to_string(),
+        &mut cfg.trigger_mode,
+        &device.trigger_mode,
+        |value| value.my_to_string().to_string(),
+    );
+    sync.field(
+        Control::TriggerLevel,
+        "trigger level".to_string(),
+        &mut cfg.trigger_level,
+        &device.trigger_level,
+        |value| value.to_string(),
+    );
+
+    sync.field(
+        Control::AwgRunning,
+        "AWG running".to_string(),
+        &mut cfg.awg_running_status,
+        &device.awg_running_status,
+        |value| value.my_to_string().to_string(),
+    );
+    sync.field(
+        Control::AwgFrequency,
+        "AWG frequency".to_string(),
+        &mut cfg.awg_frequency,
+        &device.awg_frequency,
+        |value| value.to_string(),
+    );
+    sync.field(
+        Control::AwgAmplitude,
+        "AWG amplitude".to_string(),
+        &mut cfg.awg_amplitude,
+        &device.awg_amplitude,
+        |value| value.to_string(),
+    );
+    sync.field(
+        Control::AwgType,
+        "AWG type".to_string(),
+        &mut cfg.awg_type,
+        &device.awg_type,
+        |value| value.my_to_string().to_string(),
+    );
+    sync.field(
+        Control::AwgOffset,
+        "AWG offset".to_string(),
+        &mut cfg.awg_offset,
+        &device.awg_offset,
+        |value| value.to_string(),
+    );
+    sync.field(
+        Control::AwgDutySquare,
+        "AWG square duty".to_string(),
+        &mut cfg.awg_duty_square,
+        &device.awg_duty_square,
+        |value| value.to_string(),
+    );
+    sync.field(
+        Control::AwgDutyRamp,
+        "AWG ramp duty".to_string(),
+        &mut cfg.awg_duty_ramp,
+        &device.awg_duty_ramp,
+        |value| value.to_string(),
+    );
+
+    sync.field(
+        Control::AwgDutyTrap,
+        "AWG trapezoid duty".to_string(),
+        &mut cfg.awg_duty_trap,
+        &device.awg_duty_trap,
+        |trap| format!("{}/{}/{}", trap.high, trap.low, trap.rise),
+    );
+
+    sync.mismatches
+}
+
+struct Sync<P> {
+    is_pending: P,
+    mismatches: Vec<Mismatch>,
+}
+
+impl<P: Fn(Control) -> bool> Sync<P> {
+    fn field<T: Data>(
+        &mut self,
+        control: Control,
+        setting: String,
+        gui: &mut Option<T>,
+        device: &Option<T>,
+        show: impl Fn(&T) -> String,
+    ) {
+        let device = match device {
+            Some(device) if !(self.is_pending)(control) => device,
+            _ => return,
+        };
+        if let Some(gui) = gui {
+            if gui.same(device) {
+                return;
+            }
+        }
+
+        self.mismatches.push(Mismatch {
+            control,
+            setting,
+            gui: gui
+                .as_ref()
+                .map(&show)
+                .unwrap_or_else(|| "unknown".to_string()),
+            device: show(device),
+        });
+        *gui = Some(device.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hanteker_lib::device::cfg::Coupling;
+
+    use super::*;
+
+    /// Settings of the GUI and the device after CH1 was switched from DC to
+    /// AC coupling on the device's front panel.
+    fn front_panel_edit() -> (HantekConfig, HantekConfig) {
+        let mut gui = HantekConfig::new(2);
+        gui.channel_coupling.insert(1, Some(Coupling::DC));
+        let mut device = gui.clone();
+        device.channel_coupling.insert(1, Some(Coupling::AC));
+        (gui, device)
+    }
+
+    #[test]
+    fn front_panel_edit_is_reconciled() {
+        let (mut gui, device) = front_panel_edit();
+        let mismatches = reconcile(&mut gui, &device, |_| false);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].control, Control::Coupling(1));
+        assert_eq!(mismatches[0].setting, "CH1 coupling");
+        assert_eq!(mismatches[0].gui, "DC");
+        assert_eq!(mismatches[0].device, "AC");
+        assert!(matches!(gui.channel_coupling[&1], Some(Coupling::AC)));
+
+        let mismatches = reconcile(&mut gui, &device, |_| false);
+        assert!(mismatches.is_empty());
+    }
+
+    #[test]
+    fn pending_setting_is_left_alone() {
+        let (mut gui, device) = front_panel_edit();
+        let mismatches = reconcile(&mut gui, &device, |control| control == Control::Coupling(1));
+        assert!(mismatches.is_empty());
+        assert!(matches!(gui.channel_coupling[&1], Some(Coupling::DC)));
+    }
+
+    #[test]
+    fn unreported_setting_is_left_alone() {
+        let (mut gui, _) = front_panel_edit();
+        let mismatches = reconcile(&mut gui, &HantekConfig::new(2), |_| false);
+        assert!(mismatches.is_empty());
+        assert!(matches!(gui.channel_coupling[&1], Some(Coupling::DC)));
+    }
+}
@@ -0,0 +1,102 @@
+use std::time::Instant;
+
+use hanteker_lib::device::cfg::TriggerMode;
+
+use crate::waveform::Trace;
+
+/// Captures a device gets to deliver its triggered acquisition before the
+/// software check steps in, for devices that keep running in single mode.
+const FALLBACK_AFTER_CAPTURES: usize = 3;
+
+/// How far below the trigger level, in divisions, `trace` has to go before a
+/// rising crossing counts, so noise around the level doesn't trigger.
+pub(crate) const TRIGGER_HYSTERESIS_DIVS: f64 = 0.2;
+
+/// Index of the first sample at which `trace` crosses `level` (in volts) on a
+/// rising edge, after having been at least `hysteresis` volts below it.
+pub(crate) fn find_rising_edge(trace: &Trace, level: f64, hysteresis: f64) -> Option<usize> {
+    let mut armed = false;
+    for (index, volts) in trace.volts.iter().enumerate() {
+        if *volts < level - hysteresis {
+            armed = true;
+        } else if armed && *volts >= level {
+            return Some(index);
+        }
+    }
+    None
+}
+
+/// A single shot in progress, armed on the device in single trigger mode.
+#[derive(Clone)]
+pub(crate) struct SingleShot {
+    pub(crate) armed_at: Instant,
+    /// Trigger mode to go back to once the single shot is over.
+    pub(crate) restore_mode: TriggerMode,
+    /// Capture shown before arming, which a device still waiting for its
+    /// trigger may keep returning.
+    stale: Option<Vec<u8>>,
+    last: Option<Vec<u8>>,
+    captures: usize,
+}
+
+impl SingleShot {
+    pub(crate) fn new(restore_mode: TriggerMode, stale: Option<Vec<u8>>) -> Self {
+        Self {
+            armed_at: Instant::now(),
+            restore_mode,
+            stale,
+            last: None,
+            captures: 0,
+        }
+    }
+
+    /// Whether the device delivered a triggered acquisition with `capture`:
+    /// after triggering it stops, so the same capture comes back again.
+    pub(crate) fn is_device_triggered(&mut self, capture: &[u8]) -> bool {
+        self.captures += 1;
+        let repeated = self.last.as_deref() == Some(capture);
+        let stale = self.stale.as_deref() == Some(capture);
+        self.last = Some(capture.to_vec());
+        repeated && !stale
+    }
+
+    /// Whether the device had its chance and the software check should
+    /// decide instead.
+    pub(crate) fn is_fallback_due(&self) -> bool {
+        self.captures >= FALLBACK_AFTER_CAPTURES
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace(volts: &[f64]) -> Trace {
+        Trace {
+            channel: 1,
+            volts: volts.to_vec(),
+        }
+    }
+
+    #[test]
+    fn rising_edge_after_hysteresis() {
+        let trace = trace(&[0.0, -1.0, 0.5, 1.2, 0.0]);
+        assert_eq!(find_rising_edge(&trace, 1.0, 0.5), Some(3));
+    }
+
+    #[test]
+    fn noise_around_level_does_not_trigger() {
+        let trace = trace(&[0.9, 1.1, 0.8, 1.05, 0.7, 1.2]);
+        assert_eq!(find_rising_edge(&trace, 1.0, 0.5), None);
+        assert_eq!(find_rising_edge(&trace, 1.0, 0.0), Some(1));
+    }
+
+    #[test]
+    fn frozen_capture_is_triggered() {
+        let mut single = SingleShot::new(TriggerMode::Auto, Some(vec![1, 2]));
+        assert!(!single.is_device_triggered(&[1, 2]));
+        assert!(!single.is_device_triggered(&[1, 2]));
+        assert!(!single.is_device_triggered(&[3, 4]));
+        assert!(single.is_device_triggered(&[3, 4]));
+    }
+}
@@ -0,0 +1,151 @@
+use hanteker_lib::device::cfg::{Probe, Scale, TimeScale};
+
+/// ADC reading of a sample sitting exactly on the screen's center line.
+pub(crate) const ADC_CENTER: f64 = 128.0;
+
+/// ADC counts spanned by one vertical division.
+pub(crate) const ADC_COUNTS_PER_DIV: f64 = 25.0;
+
+/// Number of horizontal divisions covered by one capture.
+pub(crate) const DIVS_X: usize = 10;
+
+/// Number of vertical divisions on screen.
+pub(crate) const DIVS_Y: usize = 8;
+
+pub(crate) fn volts_per_div(scale: &Scale) -> f64 {
+    match scale {
+        Scale::mv10 => 0.01,
+        Scale::mv20 => 0.02,
+        Scale::mv50 => 0.05,
+        Scale::mv100 => 0.1,
+        Scale::mv200 => 0.2,
+        Scale::mv500 => 0.5,
+        Scale::v1 => 1.0,
+        Scale::v2 => 2.0,
+        Scale::v5 => 5.0,
+        Scale::v10 => 10.0,
+    }
+}
+
+pub(crate) fn probe_factor(probe: &Probe) -> f64 {
+    match probe {
+        Probe::X1 => 1.0,
+        Probe::X10 => 10.0,
+        Probe::X100 => 100.0,
+        Probe::X1000 => 1000.0,
+    }
+}
+
+pub(crate) fn seconds_per_div(time_scale: &TimeScale) -> f64 {
+    match time_scale {
+        TimeScale::ns5 => 5e-9,
+        TimeScale::ns10 => 10e-9,
+        TimeScale::ns20 => 20e-9,
+        TimeScale::ns50 => 50e-9,
+        TimeScale::ns100 => 100e-9,
+        TimeScale::ns200 => 200e-9,
+        TimeScale::ns500 => 500e-9,
+        TimeScale::us1 => 1e-6,
+        TimeScale::us2 => 2e-6,
+        TimeScale::us5 => 5e-6,
+        TimeScale::us10 => 10e-6,
+        TimeScale::us20 => 20e-6,
+        TimeScale::us50 => 50e-6,
+        TimeScale::us100 => 100e-6,
+        TimeScale::us200 => 200e-6,
+        TimeScale::us500 => 500e-6,
+        TimeScale::ms1 => 1e-3,
+        TimeScale::ms2 => 2e-3,
+        TimeScale::ms5 => 5e-3,
+        TimeScale::ms10 => 10e-3,
+        TimeScale::ms20 => 20e-3,
+        TimeScale::ms50 => 50e-3,
+        TimeScale::ms100 => 100e-3,
+        TimeScale::ms200 => 200e-3,
+        TimeScale::ms500 => 500e-3,
+        TimeScale::s1 => 1.0,
+        TimeScale::s2 => 2.0,
+        TimeScale::s5 => 5.0,
+        TimeScale::s10 => 10.0,
+        TimeScale::s20 => 20.0,
+        TimeScale::s50 => 50.0,
+        TimeScale::s100 => 100.0,
+        TimeScale::s200 => 200.0,
+        TimeScale::s500 => 500.0,
+    }
+}
+
+/// Formats `value` with an SI prefix and up to three significant digits,
+/// e.g. `format_si(0.0005, "s")` gives `"500µs"`.
+pub(crate) fn format_si(value: f64, unit: &str) -> String {
+    const PREFIXES: &[(f64, &str)] = &[
+        (1e6, "M"),
+        (1e3, "k"),
+        (1.0, ""),
+        (1e-3, "m"),
+        (1e-6, "µ"),
+        (1e-9, "n"),
+    ];
+
+    if value == 0.0 || !value.is_finite() {
+        return format!("{}{}", value, unit);
+    }
+
+    let (multiplier, prefix) = PREFIXES
+        .iter()
+        .find(|(multiplier, _)| value.abs() >= *multiplier * 0.9995)
+        .unwrap_or(&PREFIXES[PREFIXES.len() - 1]);
+    let scaled = value / multiplier;
+    let decimals = (2 - scaled.abs().log10().floor() as i32).max(0) as usize;
+    let number = format!("{:.*}", decimals, scaled);
+    let number = match number.contains('.') {
+        true => number.trim_end_matches('0').trim_end_matches('.'),
+        false => &number,
+    };
+    format!("{}{}{}", number, prefix, unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The quantity a dropdown label such as `mv10`, `ns5` or `X10` stands
+    /// for, trying the unit prefixes in order.
+    fn label_quantity(label: &str, units: &[(&str, f64)]) -> f64 {
+        let label = label.to_ascii_lowercase();
+        units
+            .iter()
+            .find_map(|(prefix, multiplier)| {
+                let number = label.strip_prefix(*prefix)?.parse::<f64>().ok()?;
+                Some(number * multiplier)
+            })
+            .unwrap_or_else(|| panic!("unexpected label '{}'", label))
+    }
+
+    fn assert_close(value: f64, expected: f64, label: &str) {
+        assert!(
+            (value - expected).abs() <= expected * 1e-9,
+            "{}: {} != {}",
+            label,
+            value,
+            expected
+        );
+    }
+
+    #[test]
+    fn every_option_matches_its_label() {
+        for (label, scale) in Scale::my_options() {
+            let expected = label_quantity(&label, &[("mv", 1e-3), ("v", 1.0)]);
+            assert_close(volts_per_div(&scale), expected, &label);
+        }
+        for (label, probe) in Probe::my_options() {
+            let expected = label_quantity(&label, &[("x", 1.0)]);
+            assert_close(probe_factor(&probe), expected, &label);
+        }
+        let second_units = [("ns", 1e-9), ("us", 1e-6), ("ms", 1e-3), ("s", 1.0)];
+        for (label, time_scale) in TimeScale::my_options() {
+            let expected = label_quantity(&label, &second_units);
+            assert_close(seconds_per_div(&time_scale), expected, &label);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use druid::Data;
+
+/// How far the display may be zoomed in or out relative to the device scale.
+const MIN_ZOOM: f64 = 0.1;
+const MAX_ZOOM: f64 = 1000.0;
+
+/// Display-only zoom and pan of one channel, on top of its device scale and
+/// offset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ChannelView {
+    /// Magnification of the vertical axis, 2.0 shows half the volts per division.
+    pub(crate) zoom: f64,
+    /// Volts added to the value shown on the center line.
+    pub(crate) pan: f64,
+}
+
+impl Default for ChannelView {
+    fn default() -> Self {
+        Self {
+            zoom: 1.0,
+            pan: 0.0,
+        }
+    }
+}
+
+/// Display-only zoom and pan of the waveform plot. Changing it only redraws
+/// the last capture and never talks to the device.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct View {
+    /// Magnification of the time axis, 2.0 shows half the seconds per division.
+    pub(crate) time_zoom: f64,
+    /// Seconds added to the time shown at the horizontal center.
+    pub(crate) time_pan: f64,
+    /// CH1, CH2 and the math trace.
+    channels: [ChannelView; 3],
+}
+
+impl Default for View {
+    fn default() -> Self {
+        Self {
+            time_zoom: 1.0,
+            time_pan: 0.0,
+            channels: [ChannelView::default(); 3],
+        }
+    }
+}
+
+impl View {
+    pub(crate) fn channel(&self, channel: usize) -> ChannelView {
+        self.channels[channel - 1]
+    }
+
+    pub(crate) fn channel_mut(&mut self, channel: usize) -> &mut ChannelView {
+        &mut self.channels[channel - 1]
+    }
+
+    pub(crate) fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+pub(crate) fn clamp_zoom(zoom: f64) -> f64 {
+    zoom.clamp(MIN_ZOOM, MAX_ZOOM)
+}
+
+/// What the plot area shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub(crate) enum DisplayMode {
+    Time,
+    Spectrum,
+    Xy,
+}
+
+impl DisplayMode {
+    pub(crate) fn my_options() -> Vec<(&'static str, DisplayMode)> {
+        vec![
+            ("YT", DisplayMode::Time),
+            ("FFT", DisplayMode::Spectrum),
+            ("XY", DisplayMode::Xy),
+        ]
+    }
+}
@@ -0,0 +1,96 @@
+use hanteker_lib::device::cfg::{HantekConfig, Probe, Scale, TimeScale};
+use serde::{Deserialize, Serialize};
+
+use crate::units::{
+    probe_factor, seconds_per_div, volts_per_div, ADC_CENTER, ADC_COUNTS_PER_DIV, DIVS_X,
+};
+
+/// Samples of one channel in volts at the probe tip.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Trace {
+    pub(crate) channel: usize,
+    pub(crate) volts: Vec<f64>,
+}
+
+/// A decoded capture: one [`Trace`] per captured channel on a shared time
+/// axis, in seconds relative to the trigger point.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Waveform {
+    pub(crate) traces: Vec<Trace>,
+    pub(crate) num_samples: usize,
+    /// Time of the first sample.
+    pub(crate) start_time: f64,
+    pub(crate) sample_interval: f64,
+}
+
+impl Waveform {
+    /// Decodes raw ADC bytes, interleaved in the order of `channels`, using
+    /// the settings in `cfg` that were active when the capture was requested.
+    pub(crate) fn decode(raw: &[u8], channels: &[usize], cfg: &HantekConfig) -> Self {
+        let num_samples = match channels.len() {
+            0 => 0,
+            num_channels => raw.len() / num_channels,
+        };
+
+        let traces = channels
+            .iter()
+            .enumerate()
+            .map(|(position, &channel)| {
+                let scale = cfg.channel_scale.get(&channel).cloned().flatten();
+                let probe = cfg.channel_probe.get(&channel).cloned().flatten();
+                let volts_per_div = volts_per_div(&scale.unwrap_or(Scale::v10))
+                    * probe_factor(&probe.unwrap_or(Probe::X1));
+                let offset = cfg.channel_offset.get(&channel).copied().flatten();
+                let offset = offset.unwrap_or(0.0) as f64;
+
+                let volts = raw
+                    .iter()
+                    .skip(position)
+                    .step_by(channels.len())
+                    .take(num_samples)
+                    .map(|sample| {
+                        (*sample as f64 - ADC_CENTER) / ADC_COUNTS_PER_DIV * volts_per_div - offset
+                    })
+                    .collect();
+
+                Trace { channel, volts }
+            })
+            .collect();
+
+        let seconds_per_div = seconds_per_div(cfg.time_scale.as_ref().unwrap_or(&TimeScale::ms1));
+        let span = seconds_per_div * DIVS_X as f64;
+        let time_offset = cfg.time_offset.unwrap_or(0.0) as f64;
+
+        Self {
+            traces,
+            num_samples,
+            start_time: time_offset - span / 2.0,
+            sample_interval: match num_samples {
+                0 => 0.0,
+                _ => span / num_samples as f64,
+            },
+        }
+    }
+
+    pub(crate) fn trace(&self, channel: usize) -> Option<&Trace> {
+        self.traces.iter().find(|trace| trace.channel == channel)
+    }
+
+    /// Time of sample `index` relative to the trigger point.
+    pub(crate) fn time(&self, index: usize) -> f64 {
+        self.start_time + index as f64 * self.sample_interval
+    }
+
+    /// Value of `channel` at the sample closest to `time`, if the capture
+    /// covers that time.
+    pub(crate) fn sample_at(&self, channel: usize, time: f64) -> Option<f64> {
+        if self.num_samples == 0 {
+            return None;
+        }
+        let index = ((time - self.start_time) / self.sample_interval).round();
+        if index < 0.0 || index >= self.num_samples as f64 {
+            return None;
+        }
+        self.trace(channel)?.volts.get(index as usize).copied()
+    }
+}
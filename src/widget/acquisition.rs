@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use druid::widget::Controller;
+use druid::{Env, Event, EventCtx, TimerToken, Widget};
+
+use crate::HantekState;
+
+/// Drives the run/stop acquisition loop: on every tick of its timer it lets
+/// the state issue the next capture and read back the device settings when
+/// due, then re-arms the timer with whatever interval is configured at that
+/// moment.
+pub struct Acquisition {
+    timer: TimerToken,
+}
+
+impl Acquisition {
+    pub fn new() -> Self {
+        Self {
+            timer: TimerToken::INVALID,
+        }
+    }
+
+    fn arm(&mut self, ctx: &mut EventCtx, state: &HantekState) {
+        self.timer = ctx.request_timer(Duration::from_millis(
+            state.get_acquisition_interval() as u64
+        ));
+    }
+}
+
+impl<W: Widget<HantekState>> Controller<HantekState, W> for Acquisition {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut HantekState,
+        env: &Env,
+    ) {
+        match event {
+            Event::WindowConnected => self.arm(ctx, data),
+            Event::Timer(token) if *token == self.timer => {
+                data.on_acquisition_tick();
+                self.arm(ctx, data);
+                ctx.set_handled();
+                return;
+            }
+            _ => {}
+        }
+        child.event(ctx, event, data, env)
+    }
+}
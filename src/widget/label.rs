@@ -1,7 +1,8 @@
-use druid::widget::Label;
+use druid::widget::{Flex, Label};
 use druid::{Data, UnitPoint, Widget, WidgetExt};
 
-use crate::t;
+use crate::comm::Control;
+use crate::{t, HantekState};
 
 pub fn label<T: Data>(text: &'static str) -> impl Widget<T> {
     Label::new(t(text)).align_horizontal(UnitPoint::LEFT)
@@ -15,3 +16,17 @@ pub fn label_ct<T: Data>(text: impl Into<String>) -> impl Widget<T> {
     let text: String = text.into();
     Label::new(text).align_horizontal(UnitPoint::CENTER)
 }
+
+/// A [`label`] that gets an ellipsis while a request for `control` is in flight.
+pub(crate) fn label_pending(text: &'static str, control: Control) -> impl Widget<HantekState> {
+    let ellipsis = Label::dynamic(
+        move |state: &HantekState, _| match state.is_pending(control) {
+            true => " …".to_string(),
+            false => String::new(),
+        },
+    );
+    Flex::row()
+        .with_child(Label::new(t(text)))
+        .with_child(ellipsis)
+        .align_horizontal(UnitPoint::LEFT)
+}
@@ -1,9 +1,12 @@
 use druid::{Lens, LocalizedString};
 
+pub(crate) mod acquisition;
 pub(crate) mod f32_formatter;
 pub(crate) mod label;
 pub(crate) mod scope;
+pub(crate) mod spectrum;
 pub(crate) mod usize_formatter;
+pub(crate) mod xy;
 
 pub fn t<T>(text: &'static str) -> LocalizedString<T> {
     tt(text, text)
@@ -1,16 +1,398 @@
-use druid::kurbo::Line;
-use druid::piet::StrokeStyle;
+use druid::kurbo::{BezPath, Line};
+use druid::piet::{FontFamily, StrokeStyle, Text, TextLayout, TextLayoutBuilder};
 use druid::{
-    BoxConstraints, Color, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx,
-    RenderContext, Size, UpdateCtx, Widget,
+    BoxConstraints, Color, Cursor, Data, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx,
+    MouseButton, PaintCtx, Point, Rect, RenderContext, Size, UpdateCtx, Vec2, Widget,
 };
 
+use crate::cursors::Cursors;
+use crate::math::{channel_name, MATH_CHANNEL};
+use crate::reference::reference_name;
+use crate::units::{format_si, probe_factor, seconds_per_div, volts_per_div, DIVS_X, DIVS_Y};
+use crate::view::{clamp_zoom, View};
+use crate::waveform::Waveform;
 use crate::HantekState;
 
-pub struct ScopeGraph;
+pub(crate) const GRID_COLOR: Color = Color::rgba8(255, 255, 255, 128);
+pub(crate) const AXIS_COLOR: Color = Color::rgba8(255, 255, 255, 200);
+pub(crate) const TEXT_COLOR: Color = Color::rgba8(255, 255, 255, 220);
+const TRIGGER_COLOR: Color = Color::rgb8(255, 165, 0);
+const BOX_COLOR: Color = Color::rgb8(100, 150, 255);
+const CURSOR_COLOR: Color = Color::rgb8(255, 255, 0);
+const ANNOTATION_ERROR_COLOR: Color = Color::rgb8(255, 80, 80);
+pub(crate) const GRID_DASH: StrokeStyle = StrokeStyle::new().dash_pattern(&[4.0, 4.0]);
+const CURSOR_DASH: StrokeStyle = StrokeStyle::new().dash_pattern(&[8.0, 3.0]);
+
+const LABEL_FONT_SIZE: f64 = 12.0;
+const MARKER_SIZE: f64 = 8.0;
+const MINOR_TICKS: usize = 5;
+/// Height of the boxes of decoded items above a trace.
+const ANNOTATION_HEIGHT: f64 = 18.0;
+
+/// Zoom factor of one mouse wheel notch.
+const ZOOM_STEP: f64 = 1.25;
+const WHEEL_NOTCH: f64 = 120.0;
+/// Smaller boxes are taken for a stray click rather than a box zoom.
+const MIN_BOX_SIZE: f64 = 5.0;
+/// Distance in pixels within which a cursor line can be grabbed.
+const CURSOR_GRAB: f64 = 4.0;
+
+pub fn channel_color(channel: usize) -> Color {
+    match channel {
+        1 => Color::rgba8(255, 0, 0, 180),
+        2 => Color::rgba8(0, 255, 0, 180),
+        MATH_CHANNEL => Color::rgba8(255, 0, 255, 180),
+        _ => panic!("unexpected channel number: {}", channel),
+    }
+}
+
+/// Color of reference `slot`, counting from zero.
+pub fn reference_color(slot: usize) -> Color {
+    match slot {
+        0 => Color::rgba8(255, 165, 0, 160),
+        1 => Color::rgba8(0, 160, 255, 160),
+        2 => Color::rgba8(255, 255, 255, 140),
+        _ => Color::rgba8(180, 120, 255, 160),
+    }
+}
+
+/// Vertical setup of one channel as currently shown on screen.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ChannelAxis {
+    pub(crate) channel: usize,
+    /// Volts per division set on the device, probe attenuation included.
+    device_volts_per_div: f64,
+    /// Offset set on the device.
+    device_offset: f64,
+    /// Volts per division after display zoom.
+    pub(crate) volts_per_div: f64,
+    /// Offset after display pan, a trace at `-offset` sits on the center line.
+    pub(crate) offset: f64,
+}
+
+/// Maps volts and seconds onto the plot area. Traces are drawn with the
+/// current settings rather than the ones they were captured with, so a
+/// stopped capture follows scale/offset changes like on a real scope.
+pub(crate) struct Viewport {
+    pub(crate) size: Size,
+    view: View,
+    device_seconds_per_div: f64,
+    device_time_offset: f64,
+    channels: Vec<ChannelAxis>,
+}
+
+impl Viewport {
+    pub(crate) fn new(size: Size, state: &HantekState) -> Self {
+        let view = state.get_view();
+        let channels = state
+            .shown_channels()
+            .into_iter()
+            .map(|channel| {
+                let (device_volts_per_div, device_offset) = match channel {
+                    MATH_CHANNEL => (state.math.volts_per_div, state.math.offset),
+                    _ => (
+                        volts_per_div(&state.get_scale(channel))
+                            * probe_factor(&state.get_probe(channel)),
+                        state.get_offset(channel) as f64,
+                    ),
+                };
+                let channel_view = view.channel(channel);
+                ChannelAxis {
+                    channel,
+                    device_volts_per_div,
+                    device_offset,
+                    volts_per_div: device_volts_per_div / channel_view.zoom,
+                    offset: device_offset - channel_view.pan,
+                }
+            })
+            .collect();
+
+        Self {
+            size,
+            view,
+            device_seconds_per_div: seconds_per_div(&state.get_time_scale()),
+            device_time_offset: state.get_time_offset() as f64,
+            channels,
+        }
+    }
+
+    pub(crate) fn div_width(&self) -> f64 {
+        self.size.width / DIVS_X as f64
+    }
+
+    pub(crate) fn div_height(&self) -> f64 {
+        self.size.height / DIVS_Y as f64
+    }
+
+    fn seconds_per_div(&self) -> f64 {
+        self.device_seconds_per_div / self.view.time_zoom
+    }
+
+    /// Time shown at the horizontal center of the screen.
+    fn time_center(&self) -> f64 {
+        self.device_time_offset + self.view.time_pan
+    }
+
+    pub(crate) fn channel(&self, channel: usize) -> Option<&ChannelAxis> {
+        self.channels.iter().find(|axis| axis.channel == channel)
+    }
+
+    fn x(&self, time: f64) -> f64 {
+        self.size.width / 2.0
+            + (time - self.time_center()) / self.seconds_per_div() * self.div_width()
+    }
+
+    fn y(&self, axis: &ChannelAxis, volts: f64) -> f64 {
+        self.size.height / 2.0 - (volts + axis.offset) / axis.volts_per_div * self.div_height()
+    }
+
+    fn time_at(&self, x: f64) -> f64 {
+        self.time_center() + (x - self.size.width / 2.0) / self.div_width() * self.seconds_per_div()
+    }
+
+    fn volts_at(&self, axis: &ChannelAxis, y: f64) -> f64 {
+        (self.size.height / 2.0 - y) / self.div_height() * axis.volts_per_div - axis.offset
+    }
+
+    /// Channel whose trace (or ground level, without a capture) passes
+    /// closest to `pos`.
+    fn nearest_channel(&self, pos: Point, waveform: Option<&Waveform>) -> Option<usize> {
+        let time = self.time_at(pos.x);
+        let distance = |axis: &ChannelAxis| {
+            let volts = waveform
+                .and_then(|waveform| waveform.sample_at(axis.channel, time))
+                .unwrap_or(0.0);
+            (self.y(axis, volts) - pos.y).abs()
+        };
+        self.channels
+            .iter()
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            .map(|axis| axis.channel)
+    }
+
+    /// Times of the time cursors, placed at a quarter and three quarters of
+    /// the screen until they are dragged.
+    fn cursor_times(&self, cursors: &Cursors) -> [f64; 2] {
+        cursors.times.unwrap_or_else(|| {
+            let width = self.size.width;
+            [self.time_at(width * 0.25), self.time_at(width * 0.75)]
+        })
+    }
+
+    /// Axis and voltages of the voltage cursors, if their channel is shown.
+    fn cursor_volts(&self, cursors: &Cursors) -> Option<(&ChannelAxis, [f64; 2])> {
+        let axis = self.channel(cursors.volts_channel)?;
+        let volts = cursors.volts.unwrap_or_else(|| {
+            let height = self.size.height;
+            [
+                self.volts_at(axis, height * 0.25),
+                self.volts_at(axis, height * 0.75),
+            ]
+        });
+        Some((axis, volts))
+    }
+
+    /// Enabled cursor line within grabbing distance of `pos`.
+    fn hit_cursor(&self, cursors: &Cursors, pos: Point) -> Option<CursorLine> {
+        if cursors.time_enabled {
+            let times = self.cursor_times(cursors);
+            if let Some(i) = (0..2).find(|i| (self.x(times[*i]) - pos.x).abs() <= CURSOR_GRAB) {
+                return Some(CursorLine::Time(i));
+            }
+        }
+        if cursors.volts_enabled {
+            if let Some((axis, volts)) = self.cursor_volts(cursors) {
+                if let Some(i) =
+                    (0..2).find(|i| (self.y(axis, volts[*i]) - pos.y).abs() <= CURSOR_GRAB)
+                {
+                    return Some(CursorLine::Volts(i));
+                }
+            }
+        }
+        None
+    }
+
+    /// Cursors with `line` moved to `pos`. The other line of the pair is
+    /// pinned where it is currently shown.
+    fn move_cursor(&self, cursors: &Cursors, line: CursorLine, pos: Point) -> Cursors {
+        let mut cursors = *cursors;
+        match line {
+            CursorLine::Time(i) => {
+                let mut times = self.cursor_times(&cursors);
+                times[i] = self.time_at(pos.x);
+                cursors.times = Some(times);
+            }
+            CursorLine::Volts(i) => {
+                if let Some((axis, mut volts)) = self.cursor_volts(&cursors) {
+                    volts[i] = self.volts_at(axis, pos.y);
+                    cursors.volts = Some(volts);
+                }
+            }
+        }
+        cursors
+    }
+
+    /// View zoomed in by `factor` on the time axis, keeping the time under
+    /// `x` in place.
+    fn zoom_time(&self, x: f64, factor: f64) -> View {
+        let time = self.time_at(x);
+        let mut view = self.view;
+        view.time_zoom = clamp_zoom(view.time_zoom * factor);
+        let seconds_per_div = self.device_seconds_per_div / view.time_zoom;
+        let center = time - (x - self.size.width / 2.0) / self.div_width() * seconds_per_div;
+        view.time_pan = center - self.device_time_offset;
+        view
+    }
+
+    /// View zoomed in by `factor` on the vertical axis of `channel`, keeping
+    /// the voltage under `y` in place.
+    fn zoom_channel(&self, channel: usize, y: f64, factor: f64) -> View {
+        let mut view = self.view;
+        let axis = match self.channel(channel) {
+            Some(axis) => axis,
+            None => return view,
+        };
+        let volts = self.volts_at(axis, y);
+        let channel_view = view.channel_mut(channel);
+        channel_view.zoom = clamp_zoom(channel_view.zoom * factor);
+        let volts_per_div = axis.device_volts_per_div / channel_view.zoom;
+        let offset = (self.size.height / 2.0 - y) / self.div_height() * volts_per_div - volts;
+        channel_view.pan = axis.device_offset - offset;
+        view
+    }
+
+    /// View with the picture dragged by `delta`: horizontally in time, and
+    /// vertically for every shown channel.
+    fn pan(&self, delta: Vec2) -> View {
+        let mut view = self.view;
+        view.time_pan -= delta.x / self.div_width() * self.seconds_per_div();
+        for axis in &self.channels {
+            view.channel_mut(axis.channel).pan += delta.y / self.div_height() * axis.volts_per_div;
+        }
+        view
+    }
+
+    /// View showing exactly what lies inside `rect` on all shown channels.
+    fn zoom_box(&self, rect: Rect) -> View {
+        let mut view = self.view;
+
+        let (start, end) = (self.time_at(rect.x0), self.time_at(rect.x1));
+        view.time_zoom = clamp_zoom(self.device_seconds_per_div * DIVS_X as f64 / (end - start));
+        view.time_pan = (start + end) / 2.0 - self.device_time_offset;
+
+        for axis in &self.channels {
+            let (top, bottom) = (self.volts_at(axis, rect.y0), self.volts_at(axis, rect.y1));
+            let channel_view = view.channel_mut(axis.channel);
+            channel_view.zoom =
+                clamp_zoom(axis.device_volts_per_div * DIVS_Y as f64 / (top - bottom));
+            channel_view.pan = axis.device_offset + (top + bottom) / 2.0;
+        }
+        view
+    }
+}
+
+/// One of the four cursor lines, by index within its pair.
+#[derive(Debug, Clone, Copy)]
+enum CursorLine {
+    Time(usize),
+    Volts(usize),
+}
+
+/// Mouse gesture in progress on the plot.
+#[derive(Debug, Clone, Copy)]
+enum Drag {
+    Pan { last: Point },
+    Box { start: Point, end: Point },
+    Cursor(CursorLine),
+}
+
+/// Waveform display. The mouse wheel zooms the time axis (with Ctrl, the
+/// vertical axis of the trace under the cursor), dragging pans, Shift-drag
+/// zooms into a box and a double click resets the view. Dragging a cursor
+/// line moves that cursor instead of panning.
+pub struct ScopeGraph {
+    drag: Option<Drag>,
+}
+
+impl ScopeGraph {
+    pub fn new() -> Self {
+        Self { drag: None }
+    }
+}
 
 impl Widget<HantekState> for ScopeGraph {
-    fn event(&mut self, _ctx: &mut EventCtx, _event: &Event, _data: &mut HantekState, _env: &Env) {}
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut HantekState, _env: &Env) {
+        let viewport = Viewport::new(ctx.size(), data);
+        match event {
+            Event::Wheel(mouse) => {
+                let factor = ZOOM_STEP.powf(-mouse.wheel_delta.y / WHEEL_NOTCH);
+                let waveform = data.capture.as_deref();
+                let view = match mouse.mods.ctrl() {
+                    true => match viewport.nearest_channel(mouse.pos, waveform) {
+                        Some(channel) => viewport.zoom_channel(channel, mouse.pos.y, factor),
+                        None => return,
+                    },
+                    false => viewport.zoom_time(mouse.pos.x, factor),
+                };
+                data.set_view(view);
+                ctx.set_handled();
+            }
+            Event::MouseDown(mouse) if mouse.button == MouseButton::Left => {
+                if mouse.count == 2 {
+                    data.reset_view();
+                    self.drag = None;
+                } else {
+                    let cursor = viewport.hit_cursor(&data.get_cursors(), mouse.pos);
+                    self.drag = Some(match (cursor, mouse.mods.shift()) {
+                        (_, true) => Drag::Box {
+                            start: mouse.pos,
+                            end: mouse.pos,
+                        },
+                        (Some(line), false) => Drag::Cursor(line),
+                        (None, false) => Drag::Pan { last: mouse.pos },
+                    });
+                    ctx.set_active(true);
+                }
+                ctx.set_handled();
+            }
+            Event::MouseMove(mouse) if ctx.is_active() => {
+                match &mut self.drag {
+                    Some(Drag::Pan { last }) => {
+                        data.set_view(viewport.pan(mouse.pos - *last));
+                        *last = mouse.pos;
+                    }
+                    Some(Drag::Box { end, .. }) => {
+                        *end = mouse.pos;
+                        ctx.request_paint();
+                    }
+                    Some(Drag::Cursor(line)) => {
+                        let cursors = viewport.move_cursor(&data.get_cursors(), *line, mouse.pos);
+                        data.set_cursors(cursors);
+                    }
+                    None => {}
+                }
+                ctx.set_handled();
+            }
+            Event::MouseMove(mouse) => match viewport.hit_cursor(&data.get_cursors(), mouse.pos) {
+                Some(CursorLine::Time(_)) => ctx.set_cursor(&Cursor::ResizeLeftRight),
+                Some(CursorLine::Volts(_)) => ctx.set_cursor(&Cursor::ResizeUpDown),
+                None => ctx.clear_cursor(),
+            },
+            Event::MouseUp(mouse) if ctx.is_active() && mouse.button == MouseButton::Left => {
+                if let Some(Drag::Box { start, end }) = self.drag {
+                    let rect = Rect::from_points(start, end);
+                    if rect.width() >= MIN_BOX_SIZE && rect.height() >= MIN_BOX_SIZE {
+                        data.set_view(viewport.zoom_box(rect));
+                    }
+                    ctx.request_paint();
+                }
+                self.drag = None;
+                ctx.set_active(false);
+                ctx.set_handled();
+            }
+            _ => {}
+        }
+    }
 
     fn lifecycle(
         &mut self,
@@ -23,11 +405,21 @@ impl Widget<HantekState> for ScopeGraph {
 
     fn update(
         &mut self,
-        _ctx: &mut UpdateCtx,
-        _old_data: &HantekState,
-        _data: &HantekState,
+        ctx: &mut UpdateCtx,
+        old_data: &HantekState,
+        data: &HantekState,
         _env: &Env,
     ) {
+        if !old_data.capture.same(&data.capture)
+            || !old_data.cfg.same(&data.cfg)
+            || old_data.view != data.view
+            || old_data.cursors != data.cursors
+            || old_data.math != data.math
+            || !old_data.references.same(&data.references)
+            || !old_data.decoded.same(&data.decoded)
+        {
+            ctx.request_paint();
+        }
     }
 
     fn layout(
@@ -46,88 +438,397 @@ impl Widget<HantekState> for ScopeGraph {
     }
 
     fn paint(&mut self, ctx: &mut PaintCtx, data: &HantekState, _env: &Env) {
-        let num_samples_0 = match data
-            .capture
-            .as_ref()
-            .unwrap_or(&Vec::<u8>::with_capacity(0))
-            .len()
-        {
-            0 => 1024,
-            anything => anything,
+        let size = ctx.size();
+        ctx.clip(size.to_rect());
+        render(ctx.render_ctx, size, data);
+
+        if let Some(Drag::Box { start, end }) = self.drag {
+            let rect = Rect::from_points(start, end);
+            ctx.fill(rect, &BOX_COLOR.with_alpha(0.2));
+            ctx.stroke(rect, &BOX_COLOR, 1.0);
+        }
+    }
+}
+
+/// Draws the whole scope screen for `state` into `rc`. Kept independent of
+/// the widget so the same picture can be rendered off-screen.
+pub(crate) fn render<R: RenderContext>(rc: &mut R, size: Size, state: &HantekState) {
+    let viewport = Viewport::new(size, state);
+
+    rc.fill(size.to_rect(), &Color::BLACK);
+    render_graticule(rc, &viewport);
+    render_references(rc, &viewport, state);
+    render_traces(rc, &viewport, state);
+    render_annotations(rc, &viewport, state);
+    render_markers(rc, &viewport, state);
+    render_cursors(rc, &viewport, state);
+    render_labels(rc, &viewport, state);
+}
+
+pub(crate) fn render_graticule<R: RenderContext>(rc: &mut R, viewport: &Viewport) {
+    let Size { width, height } = viewport.size;
+
+    for i in 1..DIVS_X {
+        let x = i as f64 * viewport.div_width();
+        rc.stroke_styled(
+            Line::new((x, 0.0), (x, height)),
+            &GRID_COLOR,
+            0.5,
+            &GRID_DASH,
+        );
+    }
+    for i in 1..DIVS_Y {
+        let y = i as f64 * viewport.div_height();
+        rc.stroke_styled(
+            Line::new((0.0, y), (width, y)),
+            &GRID_COLOR,
+            0.5,
+            &GRID_DASH,
+        );
+    }
+
+    let center = Point::new(width / 2.0, height / 2.0);
+    rc.stroke(
+        Line::new((center.x, 0.0), (center.x, height)),
+        &AXIS_COLOR,
+        1.0,
+    );
+    rc.stroke(
+        Line::new((0.0, center.y), (width, center.y)),
+        &AXIS_COLOR,
+        1.0,
+    );
+
+    let minor_x = viewport.div_width() / MINOR_TICKS as f64;
+    for i in 1..DIVS_X * MINOR_TICKS {
+        let x = i as f64 * minor_x;
+        rc.stroke(
+            Line::new((x, center.y - 3.0), (x, center.y + 3.0)),
+            &AXIS_COLOR,
+            1.0,
+        );
+    }
+    let minor_y = viewport.div_height() / MINOR_TICKS as f64;
+    for i in 1..DIVS_Y * MINOR_TICKS {
+        let y = i as f64 * minor_y;
+        rc.stroke(
+            Line::new((center.x - 3.0, y), (center.x + 3.0, y)),
+            &AXIS_COLOR,
+            1.0,
+        );
+    }
+
+    rc.stroke(viewport.size.to_rect(), &AXIS_COLOR, 1.0);
+}
+
+/// Draws the shown references with their own scale and offset, on the live
+/// time axis so they line up with the capture they are compared against.
+fn render_references<R: RenderContext>(rc: &mut R, viewport: &Viewport, state: &HantekState) {
+    for (slot, reference_slot) in state.references.iter().enumerate() {
+        let reference = match &reference_slot.reference {
+            Some(reference) if reference_slot.shown => reference,
+            _ => continue,
+        };
+        let trace = match reference.waveform.trace(reference_slot.channel) {
+            Some(trace) => trace,
+            None => continue,
         };
 
-        let mut channels: Vec<usize> = Vec::with_capacity(2);
-        let mut num_channels = 0usize;
-        let has_ch1 = data.cfg.enabled_channels[&1].unwrap();
-        let has_ch2 = data.cfg.enabled_channels[&2].unwrap();
-        if has_ch1 {
-            num_channels += 1;
-            channels.push(1);
+        let mut path = BezPath::new();
+        for (i, volts) in trace.volts.iter().enumerate() {
+            let point = (
+                viewport.x(reference.waveform.time(i)),
+                viewport.size.height / 2.0
+                    - (volts + reference_slot.offset) / reference_slot.volts_per_div
+                        * viewport.div_height(),
+            );
+            match i {
+                0 => path.move_to(point),
+                _ => path.line_to(point),
+            }
         }
-        if has_ch2 {
-            num_channels += 1;
-            channels.push(2);
+        rc.stroke(path, &reference_color(slot), 1.5);
+    }
+}
+
+fn render_traces<R: RenderContext>(rc: &mut R, viewport: &Viewport, state: &HantekState) {
+    let waveform = match &state.capture {
+        Some(waveform) => waveform,
+        None => return,
+    };
+
+    for trace in &waveform.traces {
+        let channel = match viewport.channel(trace.channel) {
+            Some(channel) => channel,
+            None => continue,
+        };
+
+        let mut path = BezPath::new();
+        for (i, volts) in trace.volts.iter().enumerate() {
+            let point = (viewport.x(waveform.time(i)), viewport.y(channel, *volts));
+            match i {
+                0 => path.move_to(point),
+                _ => path.line_to(point),
+            }
         }
-        if num_channels == 0 {
-            num_channels = 1;
+        rc.stroke(path, &channel_color(trace.channel), 2.0);
+    }
+}
+
+/// Decoded items in a lane just above the highest point of their trace.
+fn render_annotations<R: RenderContext>(rc: &mut R, viewport: &Viewport, state: &HantekState) {
+    let (waveform, decoded) = match (&state.capture, &state.decoded) {
+        (Some(waveform), Some(decoded)) => (waveform, decoded),
+        _ => return,
+    };
+    let Size { width, height } = viewport.size;
+
+    // Bottom of the lane of each trace on screen, found once per paint.
+    let lanes: Vec<(usize, f64)> = waveform
+        .traces
+        .iter()
+        .filter_map(|trace| {
+            let channel = viewport.channel(trace.channel)?;
+            let max = trace.volts.iter().copied().fold(f64::MIN, f64::max);
+            let bottom = (viewport.y(channel, max) - 4.0)
+                .min(height)
+                .max(ANNOTATION_HEIGHT);
+            Some((trace.channel, bottom))
+        })
+        .collect();
+
+    for annotation in &decoded.annotations {
+        let bottom = match lanes
+            .iter()
+            .find(|(channel, _)| *channel == annotation.channel)
+        {
+            Some((_, bottom)) => *bottom,
+            None => continue,
+        };
+        let x0 = viewport.x(annotation.start);
+        let x1 = viewport.x(annotation.end);
+        if x1 < 0.0 || x0 > width {
+            continue;
         }
 
-        let num_samples: usize = num_channels * num_samples_0;
-        let num_sectors: usize = num_samples / 100;
-        let num_sectors_f: f64 = num_samples as f64 / 100.0f64;
+        let rect = Rect::new(x0, bottom - ANNOTATION_HEIGHT, x1, bottom);
+        let color = match annotation.error {
+            true => ANNOTATION_ERROR_COLOR,
+            false => channel_color(annotation.channel),
+        };
+        rc.fill(rect, &color.clone().with_alpha(0.25));
+        rc.stroke(rect, &color, 1.0);
 
-        let size = ctx.size();
-        let rect = size.to_rect();
-        let stroke_color = Color::rgba(255., 255., 255., 0.5);
-        let dashed = StrokeStyle::new().dash_pattern(&[4.0, 4.0]);
-        let width = size.width;
-        let height = size.height;
-
-        let ch1_stroke_color = Color::rgba(255., 0., 0., 0.7);
-        let ch2_stroke_color = Color::rgba(0., 255., 0., 0.7);
-
-        ctx.fill(rect, &Color::BLACK);
-
-        ctx.paint_with_z_index(1, move |ctx| {
-            for i in 1..num_sectors {
-                let x = (i as f64) * width / num_sectors_f;
-                let path = Line::new((x, 0.0), (x, height));
-                ctx.stroke_styled(path, &stroke_color, 0.5, &dashed);
+        let layout = rc
+            .text()
+            .new_text_layout(annotation.text.clone())
+            .font(FontFamily::MONOSPACE, LABEL_FONT_SIZE)
+            .text_color(TEXT_COLOR)
+            .build();
+        if let Ok(layout) = layout {
+            let size = layout.size();
+            if size.width + 2.0 < rect.width() {
+                let origin = rect.center() - size.to_vec2() / 2.0;
+                rc.draw_text(&layout, origin);
             }
+        }
+    }
+}
 
-            for i in 1..=8 {
-                let y = (i as f64) * height / 8.0;
-                let path = Line::new((0.0, y), (width, y));
-                ctx.stroke_styled(path, &stroke_color, 0.5, &dashed);
-            }
+fn render_markers<R: RenderContext>(rc: &mut R, viewport: &Viewport, state: &HantekState) {
+    let Size { width, height } = viewport.size;
+    let clamp_y = |y: f64| y.max(MARKER_SIZE).min(height - MARKER_SIZE);
+    let clamp_x = |x: f64| x.max(MARKER_SIZE).min(width - MARKER_SIZE);
+
+    // Channel ground level, pointing right from the left edge.
+    for channel in &viewport.channels {
+        let y = clamp_y(viewport.y(channel, 0.0));
+        let mut marker = BezPath::new();
+        marker.move_to((0.0, y - MARKER_SIZE));
+        marker.line_to((MARKER_SIZE * 1.5, y));
+        marker.line_to((0.0, y + MARKER_SIZE));
+        marker.close_path();
+        rc.fill(marker, &channel_color(channel.channel));
+    }
+
+    // Trigger level, pointing left from the right edge.
+    let source = state.get_trigger_source();
+    if let Some(channel) = viewport.channel(source) {
+        let y = clamp_y(viewport.y(channel, state.get_trigger_level() as f64));
+        let mut marker = BezPath::new();
+        marker.move_to((width, y - MARKER_SIZE));
+        marker.line_to((width - MARKER_SIZE * 1.5, y));
+        marker.line_to((width, y + MARKER_SIZE));
+        marker.close_path();
+        rc.fill(marker, &TRIGGER_COLOR);
+    }
+
+    // Trigger position, pointing down from the top edge.
+    let x = clamp_x(viewport.x(0.0));
+    let mut marker = BezPath::new();
+    marker.move_to((x - MARKER_SIZE, 0.0));
+    marker.line_to((x, MARKER_SIZE * 1.5));
+    marker.line_to((x + MARKER_SIZE, 0.0));
+    marker.close_path();
+    rc.fill(marker, &TRIGGER_COLOR);
+}
+
+fn render_labels<R: RenderContext>(rc: &mut R, viewport: &Viewport, state: &HantekState) {
+    let padding = 4.0;
+
+    let time_label = format!(
+        "{}/div  T {}",
+        format_si(viewport.seconds_per_div(), "s"),
+        format_si(viewport.time_center(), "s")
+    );
+    draw_text(rc, &time_label, TEXT_COLOR, |_| {
+        Point::new(MARKER_SIZE * 2.0 + padding, padding)
+    });
+
+    let mut x = MARKER_SIZE * 2.0 + padding;
+    for channel in &viewport.channels {
+        let text = format!(
+            "{} {}/div",
+            channel_name(channel.channel),
+            format_si(channel.volts_per_div, "V")
+        );
+        let height = viewport.size.height;
+        let width = draw_text(rc, &text, channel_color(channel.channel), |size| {
+            Point::new(x, height - size.height - padding)
         });
+        x += width + padding * 4.0;
+    }
 
-        let capture = data
-            .capture
-            .as_ref()
-            .unwrap_or(&Vec::with_capacity(0))
-            .clone();
-
-        ctx.paint_with_z_index(1, move |ctx| {
-            for c in channels {
-                let ch_stroke_color = match c {
-                    1 => &ch1_stroke_color,
-                    2 => &ch2_stroke_color,
-                    _ => panic!("unexpected channel number: {}", c),
-                };
+    for (slot, reference_slot) in state.references.iter().enumerate() {
+        if reference_slot.reference.is_none() || !reference_slot.shown {
+            continue;
+        }
+        let text = format!(
+            "{} {} {}/div",
+            reference_name(slot),
+            channel_name(reference_slot.channel),
+            format_si(reference_slot.volts_per_div, "V")
+        );
+        let height = viewport.size.height;
+        let width = draw_text(rc, &text, reference_color(slot), |size| {
+            Point::new(x, height - size.height - padding)
+        });
+        x += width + padding * 4.0;
+    }
+}
 
-                for i in 1..capture.len() {
-                    if i * 2 + c >= capture.len() {
-                        break;
-                    }
-                    let from_x = ((i - 1) as f64) * width / (num_samples_0 as f64);
-                    let to_x = (i as f64) * width / (num_samples_0 as f64);
-                    let y = capture[i * 2 + c] as i32;
-                    let y = ((y - 29) as f64) * height / 202.;
-                    let path = Line::new((from_x, y), (to_x, y));
-                    ctx.stroke(path, ch_stroke_color, 2.);
-                }
+fn render_cursors<R: RenderContext>(rc: &mut R, viewport: &Viewport, state: &HantekState) {
+    let cursors = state.get_cursors();
+    let Size { width, height } = viewport.size;
+    let mut readout = Vec::new();
+
+    if cursors.time_enabled {
+        let times = viewport.cursor_times(&cursors);
+        for time in times {
+            let x = viewport.x(time);
+            rc.stroke_styled(
+                Line::new((x, 0.0), (x, height)),
+                &CURSOR_COLOR,
+                1.0,
+                &CURSOR_DASH,
+            );
+        }
+
+        let delta = times[1] - times[0];
+        readout.push((
+            format!(
+                "t1 {}  t2 {}",
+                format_si(times[0], "s"),
+                format_si(times[1], "s")
+            ),
+            CURSOR_COLOR,
+        ));
+        readout.push((
+            format!(
+                "Δt {}  1/Δt {}",
+                format_si(delta, "s"),
+                format_si(1.0 / delta.abs(), "Hz")
+            ),
+            CURSOR_COLOR,
+        ));
+
+        if let Some(waveform) = &state.capture {
+            for axis in &viewport.channels {
+                let values = times.map(|time| {
+                    waveform
+                        .sample_at(axis.channel, time)
+                        .map(|volts| format_si(volts, "V"))
+                        .unwrap_or_else(|| "--".to_string())
+                });
+                readout.push((
+                    format!(
+                        "{} @t1 {}  @t2 {}",
+                        channel_name(axis.channel),
+                        values[0],
+                        values[1]
+                    ),
+                    channel_color(axis.channel),
+                ));
             }
+        }
+    }
+
+    if cursors.volts_enabled {
+        if let Some((axis, volts)) = viewport.cursor_volts(&cursors) {
+            let color = channel_color(axis.channel);
+            for value in volts {
+                let y = viewport.y(axis, value);
+                rc.stroke_styled(Line::new((0.0, y), (width, y)), &color, 1.0, &CURSOR_DASH);
+            }
+            readout.push((
+                format!(
+                    "{} V1 {}  V2 {}  ΔV {}",
+                    channel_name(axis.channel),
+                    format_si(volts[0], "V"),
+                    format_si(volts[1], "V"),
+                    format_si(volts[1] - volts[0], "V")
+                ),
+                color,
+            ));
+        }
+    }
+
+    let padding = 4.0;
+    let mut y = MARKER_SIZE * 2.0 + padding;
+    for (text, color) in readout {
+        draw_text(rc, &text, color, |size| {
+            let origin = Point::new(width - MARKER_SIZE * 2.0 - size.width - padding, y);
+            y += size.height + padding;
+            origin
         });
     }
 }
+
+/// Draws `text` at the position `place` picks for the laid out text size and
+/// returns the text width.
+pub(crate) fn draw_text<R: RenderContext>(
+    rc: &mut R,
+    text: &str,
+    color: Color,
+    place: impl FnOnce(Size) -> Point,
+) -> f64 {
+    let layout = rc
+        .text()
+        .new_text_layout(text.to_string())
+        .font(FontFamily::MONOSPACE, LABEL_FONT_SIZE)
+        .text_color(color)
+        .build();
+    match layout {
+        Ok(layout) => {
+            let size = layout.size();
+            let origin = place(size);
+            rc.fill(
+                Rect::from_origin_size(origin, size),
+                &Color::rgba8(0, 0, 0, 160),
+            );
+            rc.draw_text(&layout, origin);
+            size.width
+        }
+        Err(_) => 0.0,
+    }
+}
@@ -0,0 +1,235 @@
+use druid::kurbo::{BezPath, Line};
+use druid::{
+    BoxConstraints, Color, Data, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx,
+    PaintCtx, Point, RenderContext, Size, UpdateCtx, Widget,
+};
+
+use crate::spectrum::{Spectra, SpectrumScale, Window};
+use crate::units::{format_si, DIVS_X, DIVS_Y};
+use crate::widget::scope::{
+    channel_color, draw_text, AXIS_COLOR, GRID_COLOR, GRID_DASH, TEXT_COLOR,
+};
+use crate::HantekState;
+
+const PEAK_COLOR: Color = Color::rgb8(255, 255, 0);
+const DB_PER_DIV: f64 = 10.0;
+/// Number of marked peaks per channel.
+const PEAK_COUNT: usize = 3;
+const PEAK_SIZE: f64 = 5.0;
+
+/// Frequency-domain counterpart of [`ScopeGraph`](crate::widget::scope::ScopeGraph),
+/// showing the averaged spectra of the enabled channels.
+pub struct SpectrumGraph;
+
+impl Widget<HantekState> for SpectrumGraph {
+    fn event(&mut self, _ctx: &mut EventCtx, _event: &Event, _data: &mut HantekState, _env: &Env) {}
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &HantekState,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &HantekState,
+        data: &HantekState,
+        _env: &Env,
+    ) {
+        if !old_data.spectra.same(&data.spectra)
+            || !old_data.cfg.same(&data.cfg)
+            || old_data.spectrum_scale != data.spectrum_scale
+        {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &HantekState,
+        _env: &Env,
+    ) -> Size {
+        if bc.is_width_bounded() && bc.is_height_bounded() {
+            bc.max()
+        } else {
+            let size = Size::new(100.0, 100.0);
+            bc.constrain(size)
+        }
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &HantekState, _env: &Env) {
+        let size = ctx.size();
+        ctx.clip(size.to_rect());
+        render(ctx.render_ctx, size, data);
+    }
+}
+
+/// Vertical range of the plot in displayed units (Vrms or dBV).
+struct Range {
+    top: f64,
+    per_div: f64,
+}
+
+impl Range {
+    fn new(spectra: &Spectra, channels: &[usize], scale: SpectrumScale) -> Self {
+        let max = channels
+            .iter()
+            .filter_map(|channel| spectra.spectrum(*channel))
+            .flat_map(|spectrum| spectrum.power.iter().skip(1))
+            .map(|power| scale.value(*power))
+            .fold(f64::MIN, f64::max);
+
+        match scale {
+            SpectrumScale::Linear => {
+                let per_div = nice_step(max.max(1e-6) / DIVS_Y as f64);
+                Self {
+                    top: per_div * DIVS_Y as f64,
+                    per_div,
+                }
+            }
+            SpectrumScale::Dbv => Self {
+                top: (max / DB_PER_DIV).ceil() * DB_PER_DIV,
+                per_div: DB_PER_DIV,
+            },
+        }
+    }
+
+    fn y(&self, size: Size, value: f64) -> f64 {
+        (self.top - value) / (self.per_div * DIVS_Y as f64) * size.height
+    }
+}
+
+/// Smallest 1-2-5 step not below `value`.
+fn nice_step(value: f64) -> f64 {
+    let magnitude = 10f64.powf(value.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|step| step * magnitude)
+        .find(|step| *step >= value)
+        .unwrap_or(10.0 * magnitude)
+}
+
+/// Draws the spectrum screen for `state` into `rc`.
+pub(crate) fn render<R: RenderContext>(rc: &mut R, size: Size, state: &HantekState) {
+    rc.fill(size.to_rect(), &Color::BLACK);
+
+    let Size { width, height } = size;
+    for i in 1..DIVS_X {
+        let x = i as f64 * width / DIVS_X as f64;
+        rc.stroke_styled(
+            Line::new((x, 0.0), (x, height)),
+            &GRID_COLOR,
+            0.5,
+            &GRID_DASH,
+        );
+    }
+    for i in 1..DIVS_Y {
+        let y = i as f64 * height / DIVS_Y as f64;
+        rc.stroke_styled(
+            Line::new((0.0, y), (width, y)),
+            &GRID_COLOR,
+            0.5,
+            &GRID_DASH,
+        );
+    }
+    rc.stroke(size.to_rect(), &AXIS_COLOR, 1.0);
+
+    let spectra = match &state.spectra {
+        Some(spectra) => spectra,
+        None => return,
+    };
+    let channels = state.shown_channels();
+    let max_frequency = match spectra.spectra.first().map(|s| s.max_frequency()) {
+        Some(frequency) if frequency > 0.0 => frequency,
+        _ => return,
+    };
+    let scale = state.get_spectrum_scale();
+    let range = Range::new(spectra, &channels, scale);
+    let x = |frequency: f64| frequency / max_frequency * width;
+    let format_value = |value: f64| match scale {
+        SpectrumScale::Linear => format_si(value, "V"),
+        SpectrumScale::Dbv => format!("{:.1}dBV", value),
+    };
+
+    for channel in &channels {
+        let spectrum = match spectra.spectrum(*channel) {
+            Some(spectrum) => spectrum,
+            None => continue,
+        };
+        let color = channel_color(*channel);
+
+        let mut path = BezPath::new();
+        for (i, power) in spectrum.power.iter().enumerate() {
+            let point = (x(spectrum.frequency(i)), range.y(size, scale.value(*power)));
+            match i {
+                0 => path.move_to(point),
+                _ => path.line_to(point),
+            }
+        }
+        rc.stroke(path, &color, 1.5);
+
+        for peak in spectrum.peaks(PEAK_COUNT) {
+            let frequency = spectrum.frequency(peak);
+            let value = scale.value(spectrum.power[peak]);
+            let tip = Point::new(x(frequency), range.y(size, value));
+            let mut marker = BezPath::new();
+            marker.move_to((tip.x - PEAK_SIZE, tip.y - PEAK_SIZE * 2.0));
+            marker.line_to(tip);
+            marker.line_to((tip.x + PEAK_SIZE, tip.y - PEAK_SIZE * 2.0));
+            marker.close_path();
+            rc.fill(marker, &PEAK_COLOR);
+
+            let text = format!("{} {}", format_si(frequency, "Hz"), format_value(value));
+            draw_text(rc, &text, color.clone(), |text_size| {
+                let x = (tip.x - text_size.width / 2.0)
+                    .max(0.0)
+                    .min(width - text_size.width);
+                let y = (tip.y - PEAK_SIZE * 2.0 - text_size.height).max(0.0);
+                Point::new(x, y)
+            });
+        }
+    }
+
+    let padding = 4.0;
+    for i in 0..DIVS_Y {
+        let value = range.top - i as f64 * range.per_div;
+        let y = i as f64 * height / DIVS_Y as f64;
+        draw_text(rc, &format_value(value), TEXT_COLOR, |_| {
+            Point::new(padding, y + padding)
+        });
+    }
+    for i in (2..DIVS_X).step_by(2) {
+        let frequency = i as f64 / DIVS_X as f64 * max_frequency;
+        let x = x(frequency);
+        draw_text(rc, &format_si(frequency, "Hz"), TEXT_COLOR, |text_size| {
+            Point::new(
+                x - text_size.width / 2.0,
+                height - text_size.height - padding,
+            )
+        });
+    }
+
+    let window = Window::my_options()
+        .into_iter()
+        .find(|(_, window)| *window == state.get_spectrum_window())
+        .map(|(name, _)| name)
+        .unwrap_or_default();
+    let bin_width = spectra.spectra.first().map(|s| s.bin_width).unwrap_or(0.0);
+    let header = format!(
+        "FFT {}  fs {}  RBW {}  avg {}/{}",
+        window,
+        format_si(max_frequency * 2.0, "Sa/s"),
+        format_si(bin_width, "Hz"),
+        spectra.count,
+        state.get_spectrum_averages()
+    );
+    draw_text(rc, &header, TEXT_COLOR, |text_size| {
+        Point::new(width - text_size.width - padding, padding)
+    });
+}
@@ -0,0 +1,147 @@
+use druid::kurbo::{BezPath, Line};
+use druid::{
+    BoxConstraints, Color, Data, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx,
+    PaintCtx, Point, RenderContext, Size, UpdateCtx, Widget,
+};
+
+use crate::units::format_si;
+use crate::waveform::Waveform;
+use crate::widget::scope::{channel_color, draw_text, render_graticule, Viewport, TEXT_COLOR};
+use crate::HantekState;
+
+const XY_COLOR: Color = Color::rgb8(0, 200, 255);
+const ORIGIN_SIZE: f64 = 6.0;
+
+/// Plots channel 1 against channel 2 from the same capture, with older
+/// captures fading out when persistence is on.
+pub struct XyGraph;
+
+impl Widget<HantekState> for XyGraph {
+    fn event(&mut self, _ctx: &mut EventCtx, _event: &Event, _data: &mut HantekState, _env: &Env) {}
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &HantekState,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &HantekState,
+        data: &HantekState,
+        _env: &Env,
+    ) {
+        if !old_data.xy_history.same(&data.xy_history)
+            || !old_data.cfg.same(&data.cfg)
+            || old_data.view != data.view
+        {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &HantekState,
+        _env: &Env,
+    ) -> Size {
+        if bc.is_width_bounded() && bc.is_height_bounded() {
+            bc.max()
+        } else {
+            let size = Size::new(100.0, 100.0);
+            bc.constrain(size)
+        }
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &HantekState, _env: &Env) {
+        let size = ctx.size();
+        ctx.clip(size.to_rect());
+        render(ctx.render_ctx, size, data);
+    }
+}
+
+/// Draws the XY screen for `state` into `rc`.
+pub(crate) fn render<R: RenderContext>(rc: &mut R, size: Size, state: &HantekState) {
+    let viewport = Viewport::new(size, state);
+
+    rc.fill(size.to_rect(), &Color::BLACK);
+    render_graticule(rc, &viewport);
+
+    let padding = 4.0;
+    let (x_axis, y_axis) = match (viewport.channel(1), viewport.channel(2)) {
+        (Some(x_axis), Some(y_axis)) => (x_axis, y_axis),
+        _ => {
+            draw_text(rc, "XY needs CH1 and CH2 enabled", TEXT_COLOR, |_| {
+                Point::new(padding, padding)
+            });
+            return;
+        }
+    };
+    let point = |x_volts: f64, y_volts: f64| {
+        Point::new(
+            size.width / 2.0
+                + (x_volts + x_axis.offset) / x_axis.volts_per_div * viewport.div_width(),
+            size.height / 2.0
+                - (y_volts + y_axis.offset) / y_axis.volts_per_div * viewport.div_height(),
+        )
+    };
+
+    let num_captures = state.xy_history.len();
+    for (age, waveform) in state.xy_history.iter().rev().enumerate() {
+        let alpha = 1.0 - age as f64 / num_captures as f64;
+        if let Some(path) = xy_path(waveform, point) {
+            rc.stroke(path, &XY_COLOR.with_alpha(alpha), 1.5);
+        }
+    }
+
+    let origin = point(0.0, 0.0);
+    rc.stroke(
+        Line::new(
+            (origin.x - ORIGIN_SIZE, origin.y),
+            (origin.x + ORIGIN_SIZE, origin.y),
+        ),
+        &TEXT_COLOR,
+        1.0,
+    );
+    rc.stroke(
+        Line::new(
+            (origin.x, origin.y - ORIGIN_SIZE),
+            (origin.x, origin.y + ORIGIN_SIZE),
+        ),
+        &TEXT_COLOR,
+        1.0,
+    );
+
+    let mut x = padding;
+    for (name, axis) in [("X", x_axis), ("Y", y_axis)] {
+        let text = format!(
+            "{} CH{} {}/div",
+            name,
+            axis.channel,
+            format_si(axis.volts_per_div, "V")
+        );
+        let width = draw_text(rc, &text, channel_color(axis.channel), |text_size| {
+            Point::new(x, size.height - text_size.height - padding)
+        });
+        x += width + padding * 4.0;
+    }
+}
+
+fn xy_path(waveform: &Waveform, point: impl Fn(f64, f64) -> Point) -> Option<BezPath> {
+    let x_trace = waveform.trace(1)?;
+    let y_trace = waveform.trace(2)?;
+
+    let mut path = BezPath::new();
+    for (i, (x, y)) in x_trace.volts.iter().zip(&y_trace.volts).enumerate() {
+        match i {
+            0 => path.move_to(point(*x, *y)),
+            _ => path.line_to(point(*x, *y)),
+        }
+    }
+    Some(path)
+}